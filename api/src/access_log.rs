@@ -0,0 +1,36 @@
+use actix_web::middleware::Logger;
+
+/// Request logger with the default access log format that skips requests to `excluded_paths`, such as
+/// health checks polled by a load balancer. Paths must match exactly.
+pub(crate) fn logger(excluded_paths: &[String]) -> Logger {
+  excluded_paths
+    .iter()
+    .fold(Logger::default(), |logger, path| logger.exclude(path.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{App, HttpResponse, test, web};
+
+  use super::*;
+  use crate::test_logger;
+
+  #[actix_web::test]
+  async fn test_excluded_paths_are_not_logged() {
+    test_logger::init();
+
+    let app = test::init_service(
+      App::new()
+        .wrap(logger(&[String::from("/health/details")]))
+        .route("/health/details", web::get().to(HttpResponse::Ok))
+        .route("/notes", web::get().to(HttpResponse::Ok))
+    ).await;
+
+    test::call_service(&app, test::TestRequest::get().uri("/health/details").to_request()).await;
+    test::call_service(&app, test::TestRequest::get().uri("/notes").to_request()).await;
+
+    let lines = test_logger::lines(log::Level::Info);
+    assert!(lines.iter().any(|line| line.contains("GET /notes HTTP")));
+    assert!(!lines.iter().any(|line| line.contains("/health/details")));
+  }
+}
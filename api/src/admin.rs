@@ -0,0 +1,378 @@
+use actix_web::{get, patch, post, HttpResponse, web::{Data, Json, ServiceConfig}};
+use db::{HealthRepository, RawColumn, RawValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use service::{NoteService, WriteLock};
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors};
+use crate::auth::ApiKey;
+use crate::error::ApiError;
+use crate::domain::{ErrorResponse, MessageResponse};
+use crate::note::Note;
+use crate::note_id::NoteId;
+use crate::timestamp;
+
+pub(super) fn configure(health_repository: Data<Box<dyn HealthRepository>>, write_lock: Data<WriteLock>) -> impl FnOnce(&mut ServiceConfig) {
+  |config: &mut ServiceConfig| {
+    config
+      .app_data(health_repository)
+      .app_data(write_lock)
+      .service(migrate)
+      .service(repair)
+      .service(storage)
+      .service(raw_note)
+      .service(set_created_at);
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct MigrateResponse {
+  /// Versions of the migrations that were applied, empty when the schema was already current
+  #[schema(example = json!([20231201120000_i64]))]
+  applied: Vec<i64>,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Pending migrations were applied", body = MigrateResponse),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+    (status = 503, description = "A migration is already running", body = ErrorResponse),
+  ),
+)]
+#[post("/admin/migrate")]
+pub(super) async fn migrate(_api_key: ApiKey, health_repository: Data<Box<dyn HealthRepository>>, write_lock: Data<WriteLock>) -> Result<HttpResponse, ApiError> {
+  // Fails with 503 while a note write is in flight; once held, writes are rejected with 503 until the guard is dropped and reads carry on
+  let _guard = write_lock.try_lock().ok_or(ApiError::WritesLocked)?;
+  let applied = health_repository.migrate().await?;
+  log::info!("Applied migrations {:?}", applied);
+
+  Ok(HttpResponse::Ok().json(MigrateResponse { applied }))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct RepairResponse {
+  /// Tags removed because their note no longer exists
+  #[schema(example = 3)]
+  orphaned_tags: u64,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Rows referring to notes that no longer exist were deleted", body = RepairResponse),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+    (status = 503, description = "A migration is running", body = ErrorResponse),
+  ),
+)]
+#[post("/admin/repair")]
+pub(super) async fn repair(_api_key: ApiKey, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let report = note_service.repair_orphans().await?;
+  log::info!("Repaired orphans {:?}", report);
+
+  Ok(HttpResponse::Ok().json(RepairResponse { orphaned_tags: report.orphaned_tags }))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct StorageResponse {
+  /// Size of the database file, including tags, audit entries and free pages
+  #[schema(example = 57344)]
+  database_bytes: i64,
+  /// Notes stored, including expired notes not yet deleted
+  #[schema(example = 120)]
+  note_count: i64,
+  /// UTF-8 bytes of all note content together
+  #[schema(example = 18450)]
+  content_bytes: i64,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Space taken up by the note database, for capacity planning", body = StorageResponse),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+  ),
+)]
+#[get("/admin/storage")]
+pub(super) async fn storage(_api_key: ApiKey, health_repository: Data<Box<dyn HealthRepository>>) -> Result<HttpResponse, ApiError> {
+  let storage = health_repository.storage().await?;
+
+  Ok(HttpResponse::Ok().json(StorageResponse {
+    database_bytes: storage.database_bytes,
+    note_count: storage.note_count,
+    content_bytes: storage.content_bytes,
+  }))
+}
+
+/// The stored row as one JSON object keyed by column name. Blobs are written as hex strings.
+fn raw_json(columns: Vec<RawColumn>) -> Map<String, Value> {
+  columns.into_iter()
+    .map(|column| {
+      let value = match column.value {
+        RawValue::Null => Value::Null,
+        RawValue::Integer(i) => Value::from(i),
+        RawValue::Real(f) => Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null),
+        RawValue::Text(text) => Value::String(text),
+        RawValue::Blob(bytes) => Value::String(bytes.iter().map(|byte| format!("{:02x}", byte)).collect()),
+      };
+      (column.name, value)
+    })
+    .collect()
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Every stored column of the note, including expired notes, under its column name and unconverted", body = Object, example = json!({"id": "14322988-32fe-447c-ac38-06fb6c699b4a", "title": "Note 1", "content": "This is note #1.", "created_at": "2021-01-01 00:00:00", "updated_at": "2021-01-01 00:00:00", "version": 1, "expires_at": null, "format": "markdown"})),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+    (status = 404, description = "No row with this id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique id")
+  ),
+)]
+#[get("/admin/notes/{id}/raw")]
+pub(super) async fn raw_note(_api_key: ApiKey, id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let columns = note_service.raw(id.as_str()).await?;
+
+  Ok(HttpResponse::Ok().json(raw_json(columns)))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SetCreatedAtRequest {
+  /// New creation date as RFC 3339
+  #[schema(example = "2019-05-01T12:00:00Z")]
+  created_at: String,
+}
+
+#[utoipa::path(
+  request_body = SetCreatedAtRequest,
+  responses(
+    (status = 200, description = "Creation date replaced. The update time and version are left as they were.", body = Note),
+    (status = 400, description = "createdAt is not an RFC 3339 timestamp", body = ErrorResponse),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique id")
+  ),
+)]
+#[patch("/admin/notes/{id}/created-at")]
+pub(super) async fn set_created_at(_api_key: ApiKey, id: NoteId, note_service: Data<Box<dyn NoteService>>, request: Json<SetCreatedAtRequest>) -> Result<HttpResponse, ApiError> {
+  let created_at = timestamp::to_stored(&request.created_at).ok_or_else(|| {
+    let mut error = ValidationError::new("timestamp");
+    error.message = Some("createdAt must be an RFC 3339 timestamp".into());
+    let mut errors = ValidationErrors::new();
+    errors.add("createdAt", error);
+    ApiError::ValidationError(errors)
+  })?;
+  let db_note = note_service.set_created_at(id.as_str(), &created_at).await?;
+
+  Ok(HttpResponse::Ok().json(Note::from(db_note)))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test;
+  use super::*;
+  use crate::mocks::{MockHealth, MockService};
+  use actix_web::App;
+  use mockall::predicate;
+  use crate::auth::API_KEY_HEADER;
+  use crate::config::Config;
+  use crate::note;
+
+  fn config() -> Config {
+    Config {
+      api_key: Some(String::from("secret")),
+      ..Config::default()
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_migrate_locks_writes_while_running() {
+    let write_lock = WriteLock::new();
+    let observed_lock = write_lock.clone();
+    let mut mock_health = MockHealth::new();
+    mock_health.expect_migrate()
+      .times(1)
+      .returning(move || {
+        assert!(observed_lock.is_locked());
+        Ok(vec![20231201120000])
+      });
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(Data::new(Box::new(mock_health) as Box<dyn HealthRepository>), Data::new(write_lock.clone())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/admin/migrate")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let body: MigrateResponse = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(body.applied, vec![20231201120000]);
+    assert!(!write_lock.is_locked());
+  }
+
+  #[actix_web::test]
+  async fn test_migrate_while_locked() {
+    let write_lock = WriteLock::new();
+    let _guard = write_lock.try_lock().unwrap();
+    let mut mock_health = MockHealth::new();
+    mock_health.expect_migrate().times(0);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(Data::new(Box::new(mock_health) as Box<dyn HealthRepository>), Data::new(write_lock.clone())))
+    ).await;
+
+    let req = test::TestRequest::post().uri("/admin/migrate").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 401);
+
+    let req = test::TestRequest::post()
+      .uri("/admin/migrate")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 503);
+    assert!(resp.headers().contains_key("Retry-After"));
+  }
+
+  #[actix_web::test]
+  async fn test_storage() {
+    let mut mock_health = MockHealth::new();
+    mock_health.expect_storage()
+      .times(1)
+      .returning(|| Ok(db::StorageFootprint { database_bytes: 57344, note_count: 2, content_bytes: 21 }));
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(Data::new(Box::new(mock_health) as Box<dyn HealthRepository>), Data::new(WriteLock::new())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/admin/storage").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 401);
+
+    let req = test::TestRequest::get()
+      .uri("/admin/storage")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body, serde_json::json!({"databaseBytes": 57344, "noteCount": 2, "contentBytes": 21}));
+  }
+
+  #[actix_web::test]
+  async fn test_raw_note_includes_internal_fields() {
+    let stored = || db::Note {
+      id: String::from("a"),
+      title: String::from("Title"),
+      content: String::from("content"),
+      created_at: String::from("2021-01-01 00:00:00"),
+      updated_at: String::from("2021-01-01 00:00:00"),
+      version: 2,
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .returning(move |_| Ok(Some(stored())));
+    mock_service.expect_raw()
+      .times(1)
+      .returning(|_| Ok(vec![
+        RawColumn { name: String::from("id"), value: RawValue::Text(String::from("a")) },
+        RawColumn { name: String::from("created_at"), value: RawValue::Text(String::from("2021-01-01 00:00:00")) },
+        RawColumn { name: String::from("version"), value: RawValue::Integer(2) },
+        RawColumn { name: String::from("expires_at"), value: RawValue::Null },
+      ]));
+
+    let mut app = test::init_service(
+      App::new()
+        .configure(note::configure(Data::new(Box::new(mock_service) as Box<dyn NoteService>), Data::new(config())))
+        .configure(configure(Data::new(Box::new(MockHealth::new()) as Box<dyn HealthRepository>), Data::new(WriteLock::new())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/admin/notes/a/raw").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 401);
+
+    let req = test::TestRequest::get().uri("/notes/a").to_request();
+    let got: Value = test::call_and_read_body_json(&mut app, req).await;
+    let req = test::TestRequest::get()
+      .uri("/admin/notes/a/raw")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let raw: Value = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(raw["created_at"], "2021-01-01 00:00:00");
+    assert_eq!(raw["version"], 2);
+    assert!(raw.as_object().unwrap().contains_key("expires_at"));
+    assert!(got["note"].get("created_at").is_none());
+    assert!(got["note"].get("expires_at").is_none());
+  }
+
+  #[actix_web::test]
+  async fn test_repair() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_repair_orphans()
+      .times(1)
+      .returning(|| Ok(db::RepairReport { orphaned_tags: 2 }));
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .app_data(Data::new(Box::new(mock_service) as Box<dyn NoteService>))
+        .configure(configure(Data::new(Box::new(MockHealth::new()) as Box<dyn HealthRepository>), Data::new(WriteLock::new())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/admin/repair")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let body: RepairResponse = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(body.orphaned_tags, 2);
+  }
+
+  #[actix_web::test]
+  async fn test_set_created_at() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_set_created_at()
+      .with(predicate::eq("a"), predicate::eq("2019-05-01 12:00:00"))
+      .times(1)
+      .returning(|id, created_at| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Title"),
+        content: String::from("content"),
+        created_at: created_at.to_string(),
+        updated_at: String::from("2021-01-01 00:00:00"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .app_data(Data::new(Box::new(mock_service) as Box<dyn NoteService>))
+        .configure(configure(Data::new(Box::new(MockHealth::new()) as Box<dyn HealthRepository>), Data::new(WriteLock::new())))
+    ).await;
+
+    let req = test::TestRequest::patch()
+      .uri("/admin/notes/a/created-at")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .set_json(serde_json::json!({"createdAt": "2019-05-01T14:00:00+02:00"}))
+      .to_request();
+    let body: Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body["createdAt"], "2019-05-01T12:00:00Z");
+
+    let req = test::TestRequest::patch()
+      .uri("/admin/notes/a/created-at")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .set_json(serde_json::json!({"createdAt": "May 1st, 2019"}))
+      .to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+}
@@ -0,0 +1,250 @@
+use actix_web::{get, HttpResponse, web::{Data, Path, Query, ServiceConfig}};
+use actix_web::dev::ServiceRequest;
+use db::AuditRepository;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use crate::auth::ApiKey;
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::timestamp;
+use crate::domain::MessageResponse;
+
+/// Header identifying the user on whose behalf a request is made.
+pub(super) const USER_ID_HEADER: &str = "X-User-Id";
+
+/// Header naming the editing session a mutation belongs to, chosen by the client.
+pub(super) const SESSION_ID_HEADER: &str = "X-Session-Id";
+
+pub(super) fn configure(audit_repository: Data<Box<dyn AuditRepository>>) -> impl FnOnce(&mut ServiceConfig) {
+  |config: &mut ServiceConfig| {
+    config
+      .app_data(audit_repository)
+      .service(list_audit)
+      .service(session_changes);
+  }
+}
+
+/// Reads the acting user from the request headers.
+pub(super) fn actor(req: &ServiceRequest) -> Option<String> {
+  req.headers()
+    .get(USER_ID_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .map(String::from)
+}
+
+/// Reads the editing session from the request headers.
+pub(super) fn session(req: &ServiceRequest) -> Option<String> {
+  req.headers()
+    .get(SESSION_ID_HEADER)
+    .and_then(|value| value.to_str().ok())
+    .map(String::from)
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct AuditEntry {
+  #[schema(example = 1)]
+  id: i64,
+  /// Mutation that was performed: create, update or delete
+  #[schema(example = "create")]
+  operation: String,
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  note_id: String,
+  /// Value of the X-User-Id header of the request, if any
+  #[schema(example = "user-1")]
+  user_id: Option<String>,
+  /// Value of the X-Session-Id header of the request, if any
+  #[schema(example = "session-1")]
+  session_id: Option<String>,
+  #[schema(example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
+  created_at: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct ListAuditQuery {
+  /// Maximum number of entries to return, newest first
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct ListAuditResponse {
+  entries: Vec<AuditEntry>,
+}
+
+impl From<db::AuditEntry> for AuditEntry {
+  fn from(db_entry: db::AuditEntry) -> Self {
+    Self {
+      id: db_entry.id,
+      operation: db_entry.operation,
+      note_id: db_entry.note_id,
+      user_id: db_entry.user_id,
+      session_id: db_entry.session_id,
+      created_at: db_entry.created_at,
+    }
+  }
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Most recent audit entries", body = ListAuditResponse),
+    (status = 401, description = "Missing or invalid API key", body = MessageResponse, example = json ! (MessageResponse{message: String::from("Unauthorized")})),
+  ),
+  params(ListAuditQuery)
+)]
+#[get("/audit")]
+pub(super) async fn list_audit(_api_key: ApiKey, audit_repository: Data<Box<dyn AuditRepository>>, config: Data<Config>, query: Query<ListAuditQuery>) -> Result<HttpResponse, ApiError> {
+  let db_entries = audit_repository.recent(config.page_size(query.limit)).await?;
+  let entries: Vec<AuditEntry> = db_entries.into_iter().map(AuditEntry::from).collect();
+
+  Ok(HttpResponse::Ok().json(ListAuditResponse { entries }))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SessionChangesResponse {
+  /// Mutations made with the session's `X-Session-Id`, oldest first
+  changes: Vec<AuditEntry>,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Mutations made in the editing session, oldest first. Empty for unknown sessions.", body = SessionChangesResponse),
+  ),
+  params(
+    ("session_id", description = "Value of the X-Session-Id header sent with the mutations")
+  )
+)]
+#[get("/notes/session/{session_id}/changes")]
+pub(super) async fn session_changes(audit_repository: Data<Box<dyn AuditRepository>>, session_id: Path<String>) -> Result<HttpResponse, ApiError> {
+  let db_entries = audit_repository.by_session(&session_id).await?;
+  let changes: Vec<AuditEntry> = db_entries.into_iter().map(AuditEntry::from).collect();
+
+  Ok(HttpResponse::Ok().json(SessionChangesResponse { changes }))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test;
+  use super::*;
+  use anyhow::Result;
+  use async_trait::async_trait;
+  use actix_web::App;
+  use mockall::mock;
+  use crate::auth::API_KEY_HEADER;
+
+  mock! {
+    Audit {}
+    #[async_trait]
+    impl db::AuditRepository for Audit {
+      async fn record(&self, entry: &db::NewAuditEntry) -> Result<db::AuditEntry>;
+      async fn recent(&self, limit: i64) -> Result<Vec<db::AuditEntry>>;
+      async fn by_session(&self, session_id: &str) -> Result<Vec<db::AuditEntry>>;
+    }
+  }
+
+  fn config() -> Config {
+    Config {
+      api_key: Some(String::from("secret")),
+      ..Config::default()
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_list_audit() {
+    let mut mock_audit = MockAudit::new();
+
+    mock_audit.expect_recent()
+      .times(1)
+      .returning(|_| Ok(vec![db::AuditEntry {
+        id: 1,
+        operation: String::from("create"),
+        note_id: String::from("new-id"),
+        user_id: Some(String::from("user-1")),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        session_id: None,
+      }]));
+
+    let audit_data = Data::new(Box::new(mock_audit) as Box<dyn AuditRepository>);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(audit_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get()
+      .uri("/audit")
+      .insert_header((API_KEY_HEADER, "secret"))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: ListAuditResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.entries.len(), 1);
+    assert_eq!(returned.entries[0].operation, "create");
+  }
+
+  #[actix_web::test]
+  async fn test_list_audit_requires_api_key() {
+    let mut mock_audit = MockAudit::new();
+    mock_audit.expect_recent().times(0);
+
+    let audit_data = Data::new(Box::new(mock_audit) as Box<dyn AuditRepository>);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(audit_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/audit").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 401);
+
+    let req = test::TestRequest::get()
+      .uri("/audit")
+      .insert_header((API_KEY_HEADER, "wrong"))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 401);
+  }
+
+  #[actix_web::test]
+  async fn test_session_changes() {
+    let mut mock_audit = MockAudit::new();
+
+    mock_audit.expect_by_session()
+      .with(mockall::predicate::eq("session-1"))
+      .times(1)
+      .returning(|session_id| Ok(vec![db::AuditEntry {
+        id: 1,
+        operation: String::from("update"),
+        note_id: String::from("some-id"),
+        user_id: None,
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        session_id: Some(session_id.to_string()),
+      }]));
+
+    let audit_data = Data::new(Box::new(mock_audit) as Box<dyn AuditRepository>);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config()))
+        .configure(configure(audit_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/session/session-1/changes").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: SessionChangesResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.changes.len(), 1);
+    assert_eq!(returned.changes[0].session_id.as_deref(), Some("session-1"));
+  }
+}
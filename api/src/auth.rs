@@ -0,0 +1,29 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest};
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+pub(crate) const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Extractor guarding admin endpoints. Succeeds only when the request's `X-Api-Key`
+/// header matches the configured `API_KEY`; with no key configured every request is rejected.
+pub(crate) struct ApiKey;
+
+impl FromRequest for ApiKey {
+  type Error = ApiError;
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+    let expected = req.app_data::<Data<Config>>().and_then(|config| config.api_key.clone());
+    let provided = req.headers().get(API_KEY_HEADER).and_then(|value| value.to_str().ok());
+
+    ready(match (expected, provided) {
+      (Some(expected), Some(provided)) if expected == provided => Ok(ApiKey),
+      _ => Err(ApiError::Unauthorized),
+    })
+  }
+}
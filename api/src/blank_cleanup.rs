@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use anyhow::Result;
+use service::NoteService;
+
+/// Deletes notes whose title and content are both blank every `interval` for as long as the server
+/// runs. With `dry_run` the notes are only logged.
+pub(crate) fn spawn(note_service: Data<Box<dyn NoteService>>, interval: Duration, dry_run: bool) {
+  actix_web::rt::spawn(async move {
+    let mut ticks = actix_web::rt::time::interval(interval);
+    loop {
+      ticks.tick().await;
+      match clean(note_service.as_ref().as_ref(), dry_run).await {
+        Ok(ids) if ids.is_empty() => {}
+        Ok(ids) if dry_run => log::info!("Would delete {} blank notes: {:?}", ids.len(), ids),
+        Ok(ids) => log::info!("Deleted {} blank notes: {:?}", ids.len(), ids),
+        Err(e) => log::error!("Failed to delete blank notes: {:?}", e),
+      }
+    }
+  });
+}
+
+/// Deletes the notes whose title and content are empty or only whitespace, unless `dry_run`, and
+/// returns their ids.
+pub(crate) async fn clean(note_service: &dyn NoteService, dry_run: bool) -> Result<Vec<String>> {
+  let ids: Vec<String> = note_service.all().await?
+    .into_iter()
+    .filter(|note| note.title.trim().is_empty() && note.content.trim().is_empty())
+    .map(|note| note.id)
+    .collect();
+  if !dry_run {
+    for id in &ids {
+      note_service.delete(id).await?;
+    }
+  }
+
+  Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mocks::MockService;
+  use mockall::predicate;
+
+  fn note(id: &str, title: &str, content: &str) -> db::Note {
+    db::Note {
+      id: String::from(id),
+      title: String::from(title),
+      content: String::from(content),
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      updated_at: String::from("2021-01-01T00:00:00Z"),
+      version: 1,
+      expires_at: None,
+      format: String::from("markdown"),
+    }
+  }
+
+  fn notes() -> Result<Vec<db::Note>> {
+    Ok(vec![note("blank", " ", "\n "), note("untitled", " ", "content"), note("empty", "Title", " ")])
+  }
+
+  #[actix_web::test]
+  async fn test_clean_deletes_blank_notes() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_all().times(1).returning(notes);
+    mock_service.expect_delete()
+      .with(predicate::eq("blank"))
+      .times(1)
+      .returning(|id| Ok(note(id, " ", "\n ")));
+
+    assert_eq!(clean(&mock_service, false).await.unwrap(), vec!["blank"]);
+  }
+
+  #[actix_web::test]
+  async fn test_clean_dry_run() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_all().times(1).returning(notes);
+    mock_service.expect_delete().times(0);
+
+    assert_eq!(clean(&mock_service, true).await.unwrap(), vec!["blank"]);
+  }
+}
@@ -0,0 +1,59 @@
+use std::future::Future;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{CACHE_CONTROL, HeaderValue};
+use actix_web::Error;
+
+/// Responses that only change with a deployment and may be cached by proxies.
+const CACHEABLE_PATHS: &[&str] = &["/api-docs/openapi.json", "/version"];
+
+/// `Cache-Control` value for a request path: public for the paths in `CACHEABLE_PATHS`, `no-store` for everything else.
+pub(super) fn cache_control(path: &str, max_age: u32) -> HeaderValue {
+  if CACHEABLE_PATHS.contains(&path) {
+    HeaderValue::from_str(&format!("public, max-age={}", max_age)).expect("valid header value")
+  } else {
+    HeaderValue::from_static("no-store")
+  }
+}
+
+/// `wrap_fn` middleware adding a `Cache-Control` header to responses that don't set one themselves.
+pub(super) fn add_cache_control<S, B>(req: ServiceRequest, srv: &S, max_age: u32) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+  let value = cache_control(req.path(), max_age);
+  let response = srv.call(req);
+
+  async move {
+    let mut response = response.await?;
+    if !response.headers().contains_key(CACHE_CONTROL) {
+      response.headers_mut().insert(CACHE_CONTROL, value);
+    }
+
+    Ok(response)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, web, App, HttpResponse};
+  use super::*;
+
+  #[actix_web::test]
+  async fn test_cache_control_per_route() {
+    let mut app = test::init_service(
+      App::new()
+        .wrap_fn(|req, srv| add_cache_control(req, srv, 600))
+        .service(crate::health::get_version)
+        .route("/notes", web::get().to(HttpResponse::Ok))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/version").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "public, max-age=600");
+
+    let req = test::TestRequest::get().uri("/notes").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+  }
+}
@@ -0,0 +1,21 @@
+use sha2::{Digest, Sha256};
+
+/// Lowercase hex SHA-256 of a note's title, a newline and its content, as stored.
+pub(crate) fn sha256(title: &str, content: &str) -> String {
+  let mut hasher = Sha256::new();
+  hasher.update(title.as_bytes());
+  hasher.update(b"\n");
+  hasher.update(content.as_bytes());
+  format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_sha256() {
+    // printf 'Note 1\nThis is note #1.' | sha256sum
+    assert_eq!(sha256("Note 1", "This is note #1."), "ec944fb8447cf093c5d5d59fe036d80e1a570e92df2f3d245e5cc734ef94139b");
+  }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashSet;
+
+use similar::TextDiff;
+
+/// Jaccard similarity of the lowercased words of `a` and `b`: 1.0 for the same words, in any order,
+/// and 0.0 when no word is shared. Two contents without words are identical.
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+  let a = words(a);
+  let b = words(b);
+  if a.is_empty() && b.is_empty() {
+    return 1.0;
+  }
+
+  a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+fn words(text: &str) -> HashSet<String> {
+  text
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .map(str::to_lowercase)
+    .collect()
+}
+
+/// Line based unified diff turning `a` into `b`, with `a_label` and `b_label` as file names. Empty
+/// when the contents are equal.
+pub(crate) fn unified_diff(a: &str, b: &str, a_label: &str, b_label: &str) -> String {
+  TextDiff::from_lines(a, b)
+    .unified_diff()
+    .header(a_label, b_label)
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_similarity() {
+    assert_eq!(similarity("Buy milk and eggs", "buy MILK and eggs!"), 1.0);
+    assert_eq!(similarity("Buy milk", "Call mom"), 0.0);
+    assert_eq!(similarity("a b c", "a b d"), 0.5);
+    assert_eq!(similarity("", "  "), 1.0);
+  }
+
+  #[test]
+  fn test_unified_diff() {
+    let diff = unified_diff("one\ntwo\n", "one\nthree\n", "a", "b");
+
+    assert!(diff.starts_with("--- a\n+++ b\n"));
+    assert!(diff.contains("-two\n+three\n"));
+    assert_eq!(unified_diff("same\n", "same\n", "a", "b"), "");
+  }
+}
@@ -0,0 +1,391 @@
+use std::collections::HashSet;
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use actix_web::http::KeepAlive;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use service::TitleCase;
+
+use crate::ip_filter::{parse_cidr_list, Cidr};
+use crate::reading_time;
+use crate::timestamp::TimestampFormat;
+use crate::word_frequency::DEFAULT_STOPWORDS;
+
+/// Client-supplied note ids must be UUIDs unless `NOTE_ID_PATTERN` says otherwise.
+const UUID_PATTERN: &str = "^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$";
+
+/// Runtime settings read from the environment on startup.
+#[derive(Clone, Debug)]
+pub(crate) struct Config {
+  /// Primary database, used for every write.
+  pub(crate) database_url: String,
+  /// Optional read replica used for note reads. Reads go to the primary when unset.
+  pub(crate) database_read_url: Option<String>,
+  /// Page size used by list endpoints when the client doesn't ask for one.
+  pub(crate) default_page_size: i64,
+  /// Hard upper bound for the `limit` query parameter.
+  pub(crate) max_page_size: i64,
+  /// Whether a note list page without notes is answered with `204 No Content` instead of an empty list.
+  pub(crate) empty_list_no_content: bool,
+  /// Most ids returned in one page of `GET /notes/ids`.
+  pub(crate) max_ids_page_size: i64,
+  /// Number of most recent notes included in the Atom feed.
+  pub(crate) feed_size: i64,
+  /// Most notes one ZIP export may contain. Zero disables the cap.
+  pub(crate) max_export_notes: i64,
+  /// Maximum number of notes a single search request returns.
+  pub(crate) max_search_results: i64,
+  /// Longest accepted search query in characters, after whitespace is collapsed.
+  pub(crate) max_search_query_length: usize,
+  /// Most distinct tags one tag-filtered request may name.
+  pub(crate) max_filter_tags: usize,
+  /// Characters of context in the highlighted snippet of each search result. Zero disables snippets.
+  pub(crate) search_snippet_length: usize,
+  /// BCP 47 locale whose rules order notes sorted by title, such as `en` or `sv`.
+  pub(crate) title_locale: String,
+  /// Reading speed behind the `readingTimeMinutes` estimate of each note.
+  pub(crate) reading_words_per_minute: usize,
+  /// Whether request bodies must be sent with `Content-Type: application/json`.
+  pub(crate) require_json_content_type: bool,
+  /// HTTP worker threads. Zero uses actix's default of one per physical CPU core.
+  pub(crate) http_workers: usize,
+  /// Seconds an idle connection is kept open for further requests. Zero closes it after each response.
+  pub(crate) keep_alive_secs: u64,
+  /// Milliseconds a client has to send the request head. Zero waits indefinitely.
+  pub(crate) client_request_timeout_ms: u64,
+  /// `max-age` in seconds sent with cacheable responses such as the OpenAPI document and `/version`.
+  pub(crate) static_cache_max_age: u32,
+  /// Requests per minute a client may make to plain note CRUD endpoints. Zero disables the limit.
+  pub(crate) crud_rate_limit: u32,
+  /// Requests per minute a client may make to search endpoints. Zero disables the limit.
+  pub(crate) search_rate_limit: u32,
+  /// Requests per minute a client may make to the ZIP and CSV exports and the feed. Zero disables the limit.
+  pub(crate) export_rate_limit: u32,
+  /// Requests per minute a client may make to stats, activity and word frequency. Zero disables the limit.
+  pub(crate) stats_rate_limit: u32,
+  /// Format of note timestamps in responses.
+  pub(crate) timestamp_format: TimestampFormat,
+  /// Whether 5xx responses hide error details from clients. Details are always logged.
+  pub(crate) hide_error_details: bool,
+  /// Whether startup creates, reads, updates and deletes a temporary note and aborts if any step fails.
+  pub(crate) startup_selftest: bool,
+  /// Whether a graceful shutdown copies the note database into `backup_dir`.
+  pub(crate) backup_on_shutdown: bool,
+  /// Directory that receives the timestamped shutdown backups.
+  pub(crate) backup_dir: String,
+  /// Whether note ids in paths must be UUIDs. Other ids are rejected with 400 instead of looked up.
+  pub(crate) strict_id_validation: bool,
+  /// Pattern the whole of a client-supplied note id must match on create. `None` accepts any id.
+  pub(crate) note_id_pattern: Option<Regex>,
+  /// Whether note titles and content are left out of debug output such as logged errors.
+  pub(crate) redact_note_content: bool,
+  /// JSON file of notes loaded on startup when the database has no notes.
+  pub(crate) seed_file: Option<String>,
+  /// Whether notes are served over one lazily opened connection instead of a pool.
+  pub(crate) single_connection: bool,
+  /// Connections to the note database opened on startup, before the first request.
+  pub(crate) min_connections: u32,
+  /// Seconds to keep retrying the database connection on startup.
+  pub(crate) database_wait_secs: u64,
+  /// Number of note writes allowed to run against the database at once. Others wait their turn.
+  pub(crate) max_concurrent_writes: usize,
+  /// Repository operations slower than this many milliseconds are logged as warnings.
+  pub(crate) slow_query_ms: u64,
+  /// Key required by admin endpoints. Admin endpoints are disabled when unset.
+  pub(crate) api_key: Option<String>,
+  /// Minimum note content length in characters. Zero disables the check.
+  pub(crate) min_content_length: usize,
+  /// Fewest distinct tags a note may be created or retagged with. Zero disables the check.
+  pub(crate) min_tags: usize,
+  /// Most distinct tags a note may carry. Zero disables the check.
+  pub(crate) max_tags: usize,
+  /// Content length in characters above which create and update responses carry a `Warning` header. Zero disables it.
+  pub(crate) content_warning_length: usize,
+  /// Whether notes created with a blank title get one from the first line of their content.
+  pub(crate) auto_title: bool,
+  /// How titles of created and updated notes are cased.
+  pub(crate) title_case: TitleCase,
+  /// Whether notes with the same content as an existing note are rejected.
+  pub(crate) unique_content: bool,
+  /// Whether content with control characters or U+FFFD replacement characters is rejected.
+  pub(crate) reject_binary_content: bool,
+  /// Whether tags are stored in lowercase.
+  pub(crate) lowercase_tags: bool,
+  /// Content of notes created without content. Empty content is rejected while unset.
+  pub(crate) default_note_content: Option<String>,
+  /// Networks allowed to use the API. Empty allows every peer that isn't denied.
+  pub(crate) ip_allowlist: Vec<Cidr>,
+  /// Networks refused with 403, even when they are also allowed.
+  pub(crate) ip_denylist: Vec<Cidr>,
+  /// Whether a background task deletes expired notes. Expired notes are hidden from reads either way.
+  pub(crate) expiry_reaper: bool,
+  /// Seconds between two runs of the expiry reaper.
+  pub(crate) expiry_reaper_interval_secs: u64,
+  /// Whether a background task deletes notes whose title and content are both blank.
+  pub(crate) blank_note_cleanup: bool,
+  /// Seconds between two runs of the blank note cleanup.
+  pub(crate) blank_note_cleanup_interval_secs: u64,
+  /// Whether the blank note cleanup only logs the notes it would delete.
+  pub(crate) blank_note_cleanup_dry_run: bool,
+  /// Words left out of word frequency counts.
+  pub(crate) stopwords: HashSet<String>,
+  /// Request paths left out of the access log.
+  pub(crate) log_excluded_paths: Vec<String>,
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      database_url: String::from("./notes.db"),
+      database_read_url: None,
+      default_page_size: 50,
+      max_page_size: 200,
+      empty_list_no_content: false,
+      max_ids_page_size: 1000,
+      feed_size: 20,
+      max_export_notes: 10_000,
+      max_search_results: 100,
+      max_search_query_length: 200,
+      max_filter_tags: 20,
+      search_snippet_length: 80,
+      title_locale: String::from("und"),
+      reading_words_per_minute: reading_time::DEFAULT_WORDS_PER_MINUTE,
+      require_json_content_type: true,
+      http_workers: 0,
+      keep_alive_secs: 5,
+      client_request_timeout_ms: 5000,
+      static_cache_max_age: 3600,
+      crud_rate_limit: 0,
+      search_rate_limit: 0,
+      export_rate_limit: 0,
+      stats_rate_limit: 0,
+      timestamp_format: TimestampFormat::default(),
+      hide_error_details: !cfg!(debug_assertions),
+      single_connection: false,
+      min_connections: 0,
+      database_wait_secs: 0,
+      max_concurrent_writes: db::DEFAULT_MAX_CONCURRENT_WRITES,
+      startup_selftest: false,
+      backup_on_shutdown: false,
+      backup_dir: String::from("backups"),
+      redact_note_content: true,
+      strict_id_validation: false,
+      note_id_pattern: Some(Regex::new(UUID_PATTERN).expect("valid UUID pattern")),
+      seed_file: None,
+      slow_query_ms: 500,
+      api_key: None,
+      min_content_length: 0,
+      min_tags: 0,
+      max_tags: 0,
+      content_warning_length: 0,
+      auto_title: false,
+      title_case: TitleCase::default(),
+      unique_content: false,
+      reject_binary_content: false,
+      lowercase_tags: false,
+      default_note_content: None,
+      ip_allowlist: Vec::new(),
+      ip_denylist: Vec::new(),
+      expiry_reaper: true,
+      expiry_reaper_interval_secs: 60,
+      blank_note_cleanup: false,
+      blank_note_cleanup_interval_secs: 3600,
+      blank_note_cleanup_dry_run: true,
+      stopwords: DEFAULT_STOPWORDS.iter().map(|word| word.to_string()).collect(),
+      log_excluded_paths: vec![],
+    }
+  }
+}
+
+impl Config {
+  pub(crate) fn from_env() -> Result<Self> {
+    let defaults = Self::default();
+    let config = Self {
+      database_url: env::var("DATABASE_URL").ok().filter(|url| !url.is_empty()).unwrap_or(defaults.database_url),
+      database_read_url: env::var("DATABASE_READ_URL").ok().filter(|url| !url.is_empty()),
+      default_page_size: env_or("DEFAULT_PAGE_SIZE", defaults.default_page_size)?,
+      max_page_size: env_or("MAX_PAGE_SIZE", defaults.max_page_size)?,
+      empty_list_no_content: env_or("EMPTY_LIST_NO_CONTENT", defaults.empty_list_no_content)?,
+      max_ids_page_size: env_or("MAX_IDS_PAGE_SIZE", defaults.max_ids_page_size)?,
+      feed_size: env_or("FEED_SIZE", defaults.feed_size)?,
+      max_export_notes: env_or("MAX_EXPORT_NOTES", defaults.max_export_notes)?,
+      max_search_results: env_or("MAX_SEARCH_RESULTS", defaults.max_search_results)?,
+      max_search_query_length: env_or("MAX_SEARCH_QUERY_LENGTH", defaults.max_search_query_length)?,
+      max_filter_tags: env_or("MAX_FILTER_TAGS", defaults.max_filter_tags)?,
+      search_snippet_length: env_or("SEARCH_SNIPPET_LENGTH", defaults.search_snippet_length)?,
+      title_locale: env_or("TITLE_LOCALE", defaults.title_locale)?,
+      reading_words_per_minute: env_or("READING_WORDS_PER_MINUTE", defaults.reading_words_per_minute)?,
+      require_json_content_type: env_or("REQUIRE_JSON_CONTENT_TYPE", defaults.require_json_content_type)?,
+      http_workers: env_or("HTTP_WORKERS", defaults.http_workers)?,
+      keep_alive_secs: env_or("KEEP_ALIVE_SECS", defaults.keep_alive_secs)?,
+      client_request_timeout_ms: env_or("CLIENT_REQUEST_TIMEOUT_MS", defaults.client_request_timeout_ms)?,
+      static_cache_max_age: env_or("STATIC_CACHE_MAX_AGE", defaults.static_cache_max_age)?,
+      crud_rate_limit: env_or("CRUD_RATE_LIMIT", defaults.crud_rate_limit)?,
+      search_rate_limit: env_or("SEARCH_RATE_LIMIT", defaults.search_rate_limit)?,
+      export_rate_limit: env_or("EXPORT_RATE_LIMIT", defaults.export_rate_limit)?,
+      stats_rate_limit: env_or("STATS_RATE_LIMIT", defaults.stats_rate_limit)?,
+      timestamp_format: env_or("TIMESTAMP_FORMAT", defaults.timestamp_format)?,
+      hide_error_details: env_or("HIDE_ERROR_DETAILS", defaults.hide_error_details)?,
+      single_connection: env_or("DATABASE_SINGLE_CONNECTION", defaults.single_connection)?,
+      min_connections: env_or("DATABASE_MIN_CONNECTIONS", defaults.min_connections)?,
+      database_wait_secs: env_or("DATABASE_WAIT_SECS", defaults.database_wait_secs)?,
+      max_concurrent_writes: env_or("MAX_CONCURRENT_WRITES", defaults.max_concurrent_writes)?,
+      startup_selftest: env_or("STARTUP_SELFTEST", defaults.startup_selftest)?,
+      backup_on_shutdown: env_or("BACKUP_ON_SHUTDOWN", defaults.backup_on_shutdown)?,
+      backup_dir: env::var("BACKUP_DIR").ok().filter(|dir| !dir.is_empty()).unwrap_or(defaults.backup_dir),
+      redact_note_content: env_or("REDACT_NOTE_CONTENT", defaults.redact_note_content)?,
+      strict_id_validation: env_or("STRICT_ID_VALIDATION", defaults.strict_id_validation)?,
+      note_id_pattern: match env::var("NOTE_ID_PATTERN") {
+        Ok(value) if value.is_empty() => None,
+        Ok(value) => Some(Regex::new(&format!("^(?:{})$", value)).context("NOTE_ID_PATTERN has an invalid value")?),
+        Err(_) => defaults.note_id_pattern,
+      },
+      seed_file: env::var("SEED_FILE").ok().filter(|path| !path.is_empty()),
+      slow_query_ms: env_or("SLOW_QUERY_MS", defaults.slow_query_ms)?,
+      api_key: env::var("API_KEY").ok().filter(|key| !key.is_empty()),
+      min_content_length: env_or("MIN_CONTENT_LENGTH", defaults.min_content_length)?,
+      min_tags: env_or("MIN_TAGS", defaults.min_tags)?,
+      max_tags: env_or("MAX_TAGS", defaults.max_tags)?,
+      content_warning_length: env_or("CONTENT_WARNING_LENGTH", defaults.content_warning_length)?,
+      auto_title: env_or("AUTO_TITLE", defaults.auto_title)?,
+      title_case: env_or("TITLE_CASE", defaults.title_case)?,
+      unique_content: env_or("UNIQUE_CONTENT", defaults.unique_content)?,
+      reject_binary_content: env_or("REJECT_BINARY_CONTENT", defaults.reject_binary_content)?,
+      lowercase_tags: env_or("LOWERCASE_TAGS", defaults.lowercase_tags)?,
+      default_note_content: env::var("DEFAULT_NOTE_CONTENT").ok().filter(|content| !content.trim().is_empty()),
+      ip_allowlist: match env::var("IP_ALLOWLIST") {
+        Ok(value) => parse_cidr_list(&value).context("IP_ALLOWLIST has an invalid value")?,
+        Err(_) => defaults.ip_allowlist,
+      },
+      ip_denylist: match env::var("IP_DENYLIST") {
+        Ok(value) => parse_cidr_list(&value).context("IP_DENYLIST has an invalid value")?,
+        Err(_) => defaults.ip_denylist,
+      },
+      expiry_reaper: env_or("EXPIRY_REAPER", defaults.expiry_reaper)?,
+      expiry_reaper_interval_secs: env_or("EXPIRY_REAPER_INTERVAL_SECS", defaults.expiry_reaper_interval_secs)?,
+      blank_note_cleanup: env_or("BLANK_NOTE_CLEANUP", defaults.blank_note_cleanup)?,
+      blank_note_cleanup_interval_secs: env_or("BLANK_NOTE_CLEANUP_INTERVAL_SECS", defaults.blank_note_cleanup_interval_secs)?,
+      blank_note_cleanup_dry_run: env_or("BLANK_NOTE_CLEANUP_DRY_RUN", defaults.blank_note_cleanup_dry_run)?,
+      stopwords: match env::var("STOPWORDS") {
+        Ok(value) => value.split(',').map(|word| word.trim().to_lowercase()).filter(|word| !word.is_empty()).collect(),
+        Err(_) => defaults.stopwords,
+      },
+      log_excluded_paths: match env::var("LOG_EXCLUDED_PATHS") {
+        Ok(value) => value.split(',').map(|path| path.trim().to_string()).filter(|path| !path.is_empty()).collect(),
+        Err(_) => defaults.log_excluded_paths,
+      },
+    };
+
+    if config.max_page_size < 1 {
+      return Err(anyhow!("MAX_PAGE_SIZE must be at least 1"));
+    }
+    if config.max_ids_page_size < 1 {
+      return Err(anyhow!("MAX_IDS_PAGE_SIZE must be at least 1"));
+    }
+    if config.max_concurrent_writes < 1 {
+      return Err(anyhow!("MAX_CONCURRENT_WRITES must be at least 1"));
+    }
+    if config.max_export_notes < 0 {
+      return Err(anyhow!("MAX_EXPORT_NOTES must not be negative"));
+    }
+    if config.max_filter_tags < 1 {
+      return Err(anyhow!("MAX_FILTER_TAGS must be at least 1"));
+    }
+    if config.feed_size < 1 {
+      return Err(anyhow!("FEED_SIZE must be at least 1"));
+    }
+    if config.max_search_results < 1 {
+      return Err(anyhow!("MAX_SEARCH_RESULTS must be at least 1"));
+    }
+    if config.expiry_reaper_interval_secs < 1 {
+      return Err(anyhow!("EXPIRY_REAPER_INTERVAL_SECS must be at least 1"));
+    }
+    if config.blank_note_cleanup_interval_secs < 1 {
+      return Err(anyhow!("BLANK_NOTE_CLEANUP_INTERVAL_SECS must be at least 1"));
+    }
+    config.check_server_tuning()?;
+
+    Ok(config)
+  }
+
+  fn check_server_tuning(&self) -> Result<()> {
+    if self.http_workers > MAX_HTTP_WORKERS {
+      return Err(anyhow!("HTTP_WORKERS must be at most {}", MAX_HTTP_WORKERS));
+    }
+    if self.keep_alive_secs > MAX_KEEP_ALIVE_SECS {
+      return Err(anyhow!("KEEP_ALIVE_SECS must be at most {}", MAX_KEEP_ALIVE_SECS));
+    }
+    if self.client_request_timeout_ms > MAX_CLIENT_REQUEST_TIMEOUT_MS {
+      return Err(anyhow!("CLIENT_REQUEST_TIMEOUT_MS must be at most {}", MAX_CLIENT_REQUEST_TIMEOUT_MS));
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn keep_alive(&self) -> KeepAlive {
+    match self.keep_alive_secs {
+      0 => KeepAlive::Disabled,
+      secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+    }
+  }
+
+  pub(crate) fn client_request_timeout(&self) -> Duration {
+    Duration::from_millis(self.client_request_timeout_ms)
+  }
+
+  /// Clamps a requested page size to `1..=max_page_size`, falling back to the default page size.
+  pub(crate) fn page_size(&self, requested: Option<i64>) -> i64 {
+    requested
+      .unwrap_or(self.default_page_size)
+      .clamp(1, self.max_page_size)
+  }
+
+  /// Clamps a requested number of search results to `1..=max_search_results`.
+  pub(crate) fn search_limit(&self, requested: Option<i64>) -> i64 {
+    requested
+      .unwrap_or(self.max_search_results)
+      .clamp(1, self.max_search_results)
+  }
+}
+
+const MAX_HTTP_WORKERS: usize = 256;
+const MAX_KEEP_ALIVE_SECS: u64 = 3600;
+const MAX_CLIENT_REQUEST_TIMEOUT_MS: u64 = 60_000;
+
+fn env_or<T: FromStr>(key: &str, default: T) -> Result<T> {
+  match env::var(key) {
+    Ok(value) => value.parse().map_err(|_| anyhow!("{} has an invalid value: {}", key, value)),
+    Err(_) => Ok(default),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_page_size_is_clamped() {
+    let config = Config::default();
+
+    assert_eq!(config.page_size(None), config.default_page_size);
+    assert_eq!(config.page_size(Some(10)), 10);
+    assert_eq!(config.page_size(Some(10000)), config.max_page_size);
+    assert_eq!(config.page_size(Some(0)), 1);
+  }
+
+  #[test]
+  fn test_server_tuning() {
+    let config = Config { keep_alive_secs: 0, client_request_timeout_ms: 2500, ..Config::default() };
+
+    assert_eq!(config.keep_alive(), KeepAlive::Disabled);
+    assert_eq!(Config::default().keep_alive(), KeepAlive::Timeout(Duration::from_secs(5)));
+    assert_eq!(config.client_request_timeout(), Duration::from_millis(2500));
+    assert!(config.check_server_tuning().is_ok());
+
+    let error = Config { http_workers: 1000, ..Config::default() }.check_server_tuning().unwrap_err();
+    assert_eq!(error.to_string(), "HTTP_WORKERS must be at most 256");
+    assert!(Config { keep_alive_secs: 86400, ..Config::default() }.check_server_tuning().is_err());
+  }
+}
@@ -7,6 +7,16 @@ pub(super) struct ErrorResponse {
   pub(crate) error: String,
 }
 
+/// Body of the `409 Conflict` returned for a note whose content duplicates an existing note.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DuplicateContentResponse {
+  pub(crate) message: String,
+  pub(crate) error: String,
+  /// Id of the note that already has this content
+  pub(crate) existing_id: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub(super) struct MessageResponse {
   pub(crate) message: String,
@@ -1,14 +1,37 @@
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
 use actix_web::http::StatusCode;
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use actix_web::error::JsonPayloadError;
 use db::DbError;
+use service::{DuplicateContent, WritesLocked};
+use uuid::Uuid;
 use validator::ValidationErrors;
-use crate::domain::{ErrorResponse, MessageResponse};
+use crate::domain::{DuplicateContentResponse, ErrorResponse, MessageResponse};
+
+/// Seconds clients are told to wait before retrying a write rejected during a migration.
+const WRITES_LOCKED_RETRY_AFTER_SECS: u64 = 5;
+
+/// Whether 5xx responses hide error details. Set once on startup from `Config::hide_error_details`.
+static HIDE_ERROR_DETAILS: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_hide_error_details(hide: bool) {
+  HIDE_ERROR_DETAILS.store(hide, Ordering::Relaxed);
+}
 
 #[derive(Debug)]
 pub enum ApiError {
   ValidationError(ValidationErrors),
   DbError(DbError),
+  Unauthorized,
+  Forbidden,
+  UnsupportedMediaType(String),
+  TooManyRequests,
+  WritesLocked,
+  PayloadTooLarge(String),
+  DuplicateContent(String),
+  InvalidEncoding(String),
   InternalError(anyhow::Error),
 }
 
@@ -17,6 +40,14 @@ impl Display for ApiError {
     match self {
       ApiError::ValidationError(e) => write!(f, "Validation error: {:?}", e),
       ApiError::DbError(e) => write!(f, "Database error: {:?}", e),
+      ApiError::Unauthorized => write!(f, "Unauthorized"),
+      ApiError::Forbidden => write!(f, "Forbidden"),
+      ApiError::UnsupportedMediaType(e) => write!(f, "Unsupported media type: {}", e),
+      ApiError::TooManyRequests => write!(f, "Too many requests"),
+      ApiError::WritesLocked => write!(f, "Writes are paused"),
+      ApiError::PayloadTooLarge(e) => write!(f, "Payload too large: {}", e),
+      ApiError::InvalidEncoding(e) => write!(f, "Invalid encoding: {}", e),
+      ApiError::DuplicateContent(id) => write!(f, "Duplicate of note {}", id),
       ApiError::InternalError(e) => write!(f, "Internal error: {:?}", e),
     }
   }
@@ -27,6 +58,15 @@ impl ResponseError for ApiError {
     match *self {
       ApiError::ValidationError(_) => StatusCode::BAD_REQUEST,
       ApiError::DbError(ref e) if matches!(e, DbError::NotFound) => StatusCode::NOT_FOUND,
+      ApiError::DbError(ref e) if matches!(e, DbError::Conflict | DbError::IdTaken) => StatusCode::CONFLICT,
+      ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+      ApiError::Forbidden => StatusCode::FORBIDDEN,
+      ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      ApiError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+      ApiError::WritesLocked => StatusCode::SERVICE_UNAVAILABLE,
+      ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+      ApiError::InvalidEncoding(_) => StatusCode::BAD_REQUEST,
+      ApiError::DuplicateContent(_) => StatusCode::CONFLICT,
       ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
       _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
@@ -41,18 +81,67 @@ impl ResponseError for ApiError {
       ApiError::DbError(ref e) if matches!(e, DbError::NotFound) => HttpResponse::NotFound().json(MessageResponse {
         message: "Not Found".to_string(),
       }),
-      ApiError::InternalError(_) => HttpResponse::InternalServerError().json(ErrorResponse {
-        message: "Internal server error".to_string(),
-        error: format!("{:?}", self),
+      ApiError::DbError(ref e) if matches!(e, DbError::Conflict) => HttpResponse::Conflict().json(ErrorResponse {
+        message: "Conflict".to_string(),
+        error: "note was modified concurrently".to_string(),
+      }),
+      ApiError::DbError(ref e) if matches!(e, DbError::IdTaken) => HttpResponse::Conflict().json(ErrorResponse {
+        message: "Conflict".to_string(),
+        error: "a note with this id already exists".to_string(),
+      }),
+      ApiError::Unauthorized => HttpResponse::Unauthorized().json(MessageResponse {
+        message: "Unauthorized".to_string(),
+      }),
+      ApiError::Forbidden => HttpResponse::Forbidden().json(MessageResponse {
+        message: "Forbidden".to_string(),
+      }),
+      ApiError::UnsupportedMediaType(ref e) => HttpResponse::UnsupportedMediaType().json(ErrorResponse {
+        message: "Unsupported media type".to_string(),
+        error: e.clone(),
+      }),
+      ApiError::TooManyRequests => HttpResponse::TooManyRequests().json(MessageResponse {
+        message: "Too Many Requests".to_string(),
+      }),
+      ApiError::WritesLocked => HttpResponse::ServiceUnavailable()
+        .insert_header((RETRY_AFTER, WRITES_LOCKED_RETRY_AFTER_SECS.to_string()))
+        .json(ErrorResponse {
+          message: "Service Unavailable".to_string(),
+          error: WritesLocked.to_string(),
+        }),
+      ApiError::PayloadTooLarge(ref e) => HttpResponse::PayloadTooLarge().json(ErrorResponse {
+        message: "Payload Too Large".to_string(),
+        error: e.clone(),
+      }),
+      ApiError::InvalidEncoding(ref e) => HttpResponse::BadRequest().json(ErrorResponse {
+        message: "Request body must be valid UTF-8".to_string(),
+        error: e.clone(),
       }),
-      _ => HttpResponse::InternalServerError().json(ErrorResponse {
-        message: "Internal server error".to_string(),
-        error: format!("{:?}", self),
-      })
+      ApiError::DuplicateContent(ref id) => HttpResponse::Conflict().json(DuplicateContentResponse {
+        message: "Conflict".to_string(),
+        error: "a note with this content already exists".to_string(),
+        existing_id: id.clone(),
+      }),
+      _ => HttpResponse::InternalServerError().json(internal_error_body(self, HIDE_ERROR_DETAILS.load(Ordering::Relaxed))),
     }
   }
 }
 
+/// Logs the full error under a new correlation id and builds the 500 body. With `hide_details`
+/// the body only carries the correlation id, so internal errors such as sqlx messages don't reach clients.
+fn internal_error_body(error: &ApiError, hide_details: bool) -> ErrorResponse {
+  let correlation_id = Uuid::new_v4();
+  log::error!("internal error {}: {:?}", correlation_id, error);
+
+  ErrorResponse {
+    message: "Internal server error".to_string(),
+    error: if hide_details {
+      format!("internal error, reference {}", correlation_id)
+    } else {
+      format!("{:?}", error)
+    },
+  }
+}
+
 impl From<ValidationErrors> for ApiError {
   fn from(e: ValidationErrors) -> Self {
     ApiError::ValidationError(e)
@@ -70,6 +159,81 @@ impl From<anyhow::Error> for ApiError {
     e.downcast::<ValidationErrors>()
       .map(ApiError::ValidationError)
       .or_else(|e| e.downcast::<DbError>().map(ApiError::DbError))
+      .or_else(|e| e.downcast::<WritesLocked>().map(|_| ApiError::WritesLocked))
+      .or_else(|e| e.downcast::<DuplicateContent>().map(|duplicate| ApiError::DuplicateContent(duplicate.existing_id)))
       .unwrap_or_else(ApiError::InternalError)
   }
 }
+
+/// Turns a request body with the wrong content type into a 415, and one that isn't valid UTF-8
+/// into a 400, both carrying an `ErrorResponse`. Other JSON errors keep actix's default handling.
+pub(crate) fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+  match err {
+    JsonPayloadError::ContentType => ApiError::UnsupportedMediaType("expected Content-Type: application/json".to_string()).into(),
+    // serde_json has no public error kind for this, only the message
+    JsonPayloadError::Deserialize(ref e) if e.to_string().starts_with("invalid unicode code point") => ApiError::InvalidEncoding(e.to_string()).into(),
+    _ => err.into(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::test_logger;
+
+  #[test]
+  fn test_internal_error_details_are_hidden() {
+    test_logger::init();
+    let error = ApiError::InternalError(anyhow::anyhow!("no such table: note"));
+
+    let body = internal_error_body(&error, true);
+
+    assert!(!body.error.contains("no such table"));
+    let correlation_id = body.error.rsplit(' ').next().unwrap().to_string();
+    let errors = test_logger::lines(log::Level::Error);
+    assert!(errors.iter().any(|logged| logged.contains(&correlation_id) && logged.contains("no such table: note")));
+  }
+
+  #[test]
+  fn test_writes_locked_asks_to_retry() {
+    let error = ApiError::from(anyhow::Error::from(WritesLocked));
+
+    let response = error.error_response();
+
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "5");
+  }
+
+  #[actix_web::test]
+  async fn test_taken_id_is_not_reported_as_concurrent_change() {
+    let error = ApiError::from(anyhow::Error::from(DbError::IdTaken));
+
+    let response = error.error_response();
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+    let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.error, "a note with this id already exists");
+  }
+
+  #[actix_web::test]
+  async fn test_duplicate_content_names_existing_note() {
+    let error = ApiError::from(anyhow::Error::from(DuplicateContent { existing_id: String::from("existing-id") }));
+
+    let response = error.error_response();
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body = actix_web::body::to_bytes(response.into_body()).await.unwrap();
+    let body: DuplicateContentResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body.existing_id, "existing-id");
+  }
+
+  #[test]
+  fn test_internal_error_details_are_shown() {
+    let error = ApiError::InternalError(anyhow::anyhow!("no such table: note"));
+
+    let body = internal_error_body(&error, false);
+
+    assert!(body.error.contains("no such table: note"));
+  }
+}
@@ -0,0 +1,177 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+
+use anyhow::Result;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Longest file name stem used inside an export, in characters.
+const MAX_STEM_LENGTH: usize = 60;
+
+fn tags_by_note(tags: &[db::NoteTag]) -> HashMap<&str, Vec<&str>> {
+  let mut tags_by_note: HashMap<&str, Vec<&str>> = HashMap::new();
+  for tag in tags {
+    tags_by_note.entry(tag.note_id.as_str()).or_default().push(tag.tag.as_str());
+  }
+
+  tags_by_note
+}
+
+/// Builds a ZIP with one Markdown file per note, each starting with a front-matter block.
+pub(crate) fn markdown_zip(notes: &[db::Note], tags: &[db::NoteTag]) -> Result<Vec<u8>> {
+  let tags_by_note = tags_by_note(tags);
+
+  let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+  let mut used_names = HashSet::new();
+  for note in notes {
+    let name = unique_file_name(&slug(&note.title, &note.id), &mut used_names);
+    let note_tags = tags_by_note.get(note.id.as_str()).cloned().unwrap_or_default();
+
+    writer.start_file(name, FileOptions::default())?;
+    writer.write_all(markdown(note, &note_tags)?.as_bytes())?;
+  }
+
+  Ok(writer.finish()?.into_inner())
+}
+
+/// Header row of the CSV export.
+pub(crate) const CSV_HEADER: &str = "id,title,content,created_at,updated_at,tags\r\n";
+
+/// Builds RFC 4180 CSV rows, one per note, to follow `CSV_HEADER`. A note's tags share one field,
+/// separated by commas, which tags can't contain.
+pub(crate) fn csv_rows(notes: &[db::Note], tags: &[db::NoteTag]) -> String {
+  let tags_by_note = tags_by_note(tags);
+
+  let mut csv = String::new();
+  for note in notes {
+    let note_tags = tags_by_note.get(note.id.as_str()).map(|tags| tags.join(",")).unwrap_or_default();
+    let fields = [note.id.as_str(), note.title.as_str(), note.content.as_str(), note.created_at.as_str(), note.updated_at.as_str(), note_tags.as_str()];
+    csv.push_str(&fields.map(csv_field).join(","));
+    csv.push_str("\r\n");
+  }
+
+  csv
+}
+
+/// Quotes a field containing a separator, quote or line break, doubling the quotes inside it. A field a
+/// spreadsheet would evaluate as a formula gets a leading `'` so it opens as text.
+fn csv_field(value: &str) -> String {
+  let value = if value.starts_with(['=', '+', '-', '@']) {
+    format!("'{}", value)
+  } else {
+    value.to_string()
+  };
+
+  if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value
+  }
+}
+
+fn markdown(note: &db::Note, tags: &[&str]) -> Result<String> {
+  // JSON strings and arrays are valid YAML, which keeps quoting of arbitrary titles and tags correct.
+  Ok(format!(
+    "---\nid: {}\ntitle: {}\ncreated_at: {}\ntags: {}\nformat: {}\n---\n\n{}\n",
+    note.id,
+    serde_json::to_string(&note.title)?,
+    note.created_at,
+    serde_json::to_string(tags)?,
+    note.format,
+    note.content,
+  ))
+}
+
+/// Turns a title into a lowercase, dash-separated file name stem, falling back to the id sanitized
+/// the same way, and to "note" when neither leaves anything usable.
+fn slug(title: &str, id: &str) -> String {
+  [title, id]
+    .into_iter()
+    .map(sanitize)
+    .find(|slug| !slug.is_empty())
+    .unwrap_or_else(|| String::from("note"))
+}
+
+/// Keeps letters and digits, collapsing every run of anything else into one dash.
+fn sanitize(text: &str) -> String {
+  let mut slug = String::new();
+  for c in text.chars().flat_map(char::to_lowercase) {
+    if c.is_alphanumeric() {
+      slug.push(c);
+    } else if !slug.is_empty() && !slug.ends_with('-') {
+      slug.push('-');
+    }
+  }
+  let slug: String = slug.trim_end_matches('-').chars().take(MAX_STEM_LENGTH).collect();
+  slug.trim_end_matches('-').to_string()
+}
+
+fn unique_file_name(stem: &str, used_names: &mut HashSet<String>) -> String {
+  let mut name = format!("{}.md", stem);
+  let mut suffix = 2;
+  while !used_names.insert(name.clone()) {
+    name = format!("{}-{}.md", stem, suffix);
+    suffix += 1;
+  }
+
+  name
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_slug() {
+    assert_eq!(slug("Hello, World!", "id"), "hello-world");
+    assert_eq!(slug("../../etc/passwd", "id"), "etc-passwd");
+    assert_eq!(slug("Caf\u{e9} notes", "id"), "caf\u{e9}-notes");
+    assert_eq!(slug("???", "fallback-id"), "fallback-id");
+    assert_eq!(slug("???", "../../x"), "x");
+    assert_eq!(slug("???", "../.."), "note");
+  }
+
+  #[test]
+  fn test_csv() {
+    let note = |id: &str, title: &str, content: &str| db::Note {
+      id: String::from(id),
+      title: String::from(title),
+      content: String::from(content),
+      created_at: String::from("2021-01-01 00:00:00"),
+      updated_at: String::from("2021-01-02 00:00:00"),
+      version: 1,
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+    let notes = [note("1", "Groceries", "Milk, eggs\nand \"good\" bread"), note("2", "Plain", "Nothing to escape")];
+    let tags = [
+      db::NoteTag { note_id: String::from("1"), tag: String::from("food") },
+      db::NoteTag { note_id: String::from("1"), tag: String::from("todo") },
+    ];
+
+    assert_eq!(
+      csv_rows(&notes, &tags),
+      "1,Groceries,\"Milk, eggs\nand \"\"good\"\" bread\",2021-01-01 00:00:00,2021-01-02 00:00:00,\"food,todo\"\r\n\
+       2,Plain,Nothing to escape,2021-01-01 00:00:00,2021-01-02 00:00:00,\r\n",
+    );
+  }
+
+  #[test]
+  fn test_csv_field() {
+    assert_eq!(csv_field("plain"), "plain");
+    assert_eq!(csv_field("=HYPERLINK(\"http://example.com\")"), "\"'=HYPERLINK(\"\"http://example.com\"\")\"");
+    assert_eq!(csv_field("+1"), "'+1");
+    assert_eq!(csv_field("-2+3"), "'-2+3");
+    assert_eq!(csv_field("@SUM(A1:A2)"), "'@SUM(A1:A2)");
+    assert_eq!(csv_field("a = b"), "a = b");
+  }
+
+  #[test]
+  fn test_unique_file_name() {
+    let mut used_names = HashSet::new();
+
+    assert_eq!(unique_file_name("note", &mut used_names), "note.md");
+    assert_eq!(unique_file_name("note", &mut used_names), "note-2.md");
+    assert_eq!(unique_file_name("note", &mut used_names), "note-3.md");
+  }
+}
@@ -0,0 +1,44 @@
+use chrono::Utc;
+
+/// Renders notes as an Atom feed. Notes are expected newest first.
+pub(crate) fn atom(notes: &[db::Note]) -> String {
+  let updated = notes
+    .iter()
+    .map(|note| note.updated_at.as_str())
+    .max()
+    .map(String::from)
+    .unwrap_or_else(|| Utc::now().to_rfc3339());
+
+  let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+  feed.push_str("  <id>urn:note-taking-app:notes</id>\n");
+  feed.push_str("  <title>Notes</title>\n");
+  feed.push_str(&format!("  <updated>{}</updated>\n", escape(&updated)));
+  for note in notes {
+    feed.push_str("  <entry>\n");
+    feed.push_str(&format!("    <id>urn:uuid:{}</id>\n", escape(&note.id)));
+    feed.push_str(&format!("    <title>{}</title>\n", escape(&note.title)));
+    feed.push_str(&format!("    <published>{}</published>\n", escape(&note.created_at)));
+    feed.push_str(&format!("    <updated>{}</updated>\n", escape(&note.updated_at)));
+    feed.push_str(&format!("    <summary>{}</summary>\n", escape(&note.content)));
+    feed.push_str("  </entry>\n");
+  }
+  feed.push_str("</feed>\n");
+
+  feed
+}
+
+fn escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+  }
+
+  escaped
+}
@@ -0,0 +1,84 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+use validator::{ValidationError, ValidationErrors};
+
+/// Longest accepted `find`, in characters.
+pub(crate) const MAX_FIND_LENGTH: usize = 200;
+/// Upper bound for the compiled size of a `find` regex, so a pathological pattern is rejected
+/// instead of compiled. Matching itself is linear in the content length.
+const REGEX_SIZE_LIMIT: usize = 1 << 16;
+
+/// A replacement of every match of a literal string or regex in note content.
+pub(crate) struct FindReplace {
+  pattern: Regex,
+  replace: String,
+  /// Whether `replace` may refer to capture groups, as `$1` or `${name}`
+  expand: bool,
+}
+
+impl FindReplace {
+  pub(crate) fn new(find: &str, replace: &str, regex: bool) -> Result<Self, ValidationErrors> {
+    let length = find.chars().count();
+    if length == 0 || length > MAX_FIND_LENGTH {
+      let mut error = ValidationError::new("length");
+      error.message = Some(format!("find must be between 1 and {} characters long", MAX_FIND_LENGTH).into());
+      return Err(find_error(error));
+    }
+
+    let source = if regex { find.to_string() } else { regex::escape(find) };
+    let pattern = RegexBuilder::new(&source)
+      .size_limit(REGEX_SIZE_LIMIT)
+      .build()
+      .map_err(|error| {
+        let mut validation_error = ValidationError::new("regex");
+        validation_error.message = Some(error.to_string().into());
+        find_error(validation_error)
+      })?;
+
+    Ok(FindReplace { pattern, replace: replace.to_string(), expand: regex })
+  }
+
+  /// The content with every match replaced, or `None` when the content doesn't change.
+  pub(crate) fn apply(&self, content: &str) -> Option<String> {
+    let replaced = if self.expand {
+      self.pattern.replace_all(content, self.replace.as_str())
+    } else {
+      self.pattern.replace_all(content, NoExpand(&self.replace))
+    };
+
+    Some(replaced.into_owned()).filter(|replaced| replaced != content)
+  }
+}
+
+fn find_error(error: ValidationError) -> ValidationErrors {
+  let mut errors = ValidationErrors::new();
+  errors.add("find", error);
+  errors
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_literal() {
+    let find_replace = FindReplace::new("teh (draft)", "the $1", false).unwrap();
+
+    assert_eq!(find_replace.apply("teh (draft) and teh (draft)"), Some(String::from("the $1 and the $1")));
+    assert_eq!(find_replace.apply("nothing to fix"), None);
+  }
+
+  #[test]
+  fn test_regex() {
+    let find_replace = FindReplace::new(r"(\d{4})-(\d{2})", "$2/$1", true).unwrap();
+
+    assert_eq!(find_replace.apply("due 2021-03"), Some(String::from("due 03/2021")));
+  }
+
+  #[test]
+  fn test_invalid_find() {
+    assert!(FindReplace::new("", "x", false).is_err());
+    assert!(FindReplace::new(&"x".repeat(MAX_FIND_LENGTH + 1), "x", false).is_err());
+    assert!(FindReplace::new("(unclosed", "x", true).is_err());
+    assert!(FindReplace::new(r"\w{1000}{1000}", "x", true).is_err());
+  }
+}
@@ -0,0 +1,145 @@
+use actix_web::{get, HttpResponse, web::{Data, ServiceConfig}};
+use db::HealthRepository;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::error::ApiError;
+
+pub(super) fn configure(health_repository: Data<Box<dyn HealthRepository>>) -> impl FnOnce(&mut ServiceConfig) {
+  |config: &mut ServiceConfig| {
+    config
+      .app_data(health_repository)
+      .service(health_details)
+      .service(get_version);
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct HealthDetailsResponse {
+  /// "ok", or "pending_migrations" when the schema is behind
+  #[schema(example = "ok")]
+  status: String,
+  /// Database backend in use
+  #[schema(example = "sqlite")]
+  backend: String,
+  /// Versions of the migrations applied to the database
+  #[schema(example = json!([20231124190400_i64]))]
+  applied_migrations: Vec<i64>,
+  /// Versions of the migrations known to the server but not applied yet
+  #[schema(example = json!([]))]
+  pending_migrations: Vec<i64>,
+  #[schema(example = 1)]
+  note_count: i64,
+}
+
+impl From<db::HealthStatus> for HealthDetailsResponse {
+  fn from(status: db::HealthStatus) -> Self {
+    Self {
+      status: if status.pending_migrations.is_empty() { "ok" } else { "pending_migrations" }.to_string(),
+      backend: status.backend,
+      applied_migrations: status.applied_migrations,
+      pending_migrations: status.pending_migrations,
+      note_count: status.note_count,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct VersionResponse {
+  /// Version of the running server
+  #[schema(example = "0.1.0")]
+  version: String,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Version of the running server", body = VersionResponse),
+  )
+)]
+#[get("/version")]
+pub(super) async fn get_version() -> HttpResponse {
+  HttpResponse::Ok().json(VersionResponse { version: env!("CARGO_PKG_VERSION").to_string() })
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Database schema is up to date", body = HealthDetailsResponse),
+    (status = 503, description = "Migrations are pending", body = HealthDetailsResponse),
+  )
+)]
+#[get("/health/details")]
+pub(super) async fn health_details(health_repository: Data<Box<dyn HealthRepository>>) -> Result<HttpResponse, ApiError> {
+  let status = health_repository.status().await?;
+  let response = HealthDetailsResponse::from(status);
+
+  if response.pending_migrations.is_empty() {
+    Ok(HttpResponse::Ok().json(response))
+  } else {
+    Ok(HttpResponse::ServiceUnavailable().json(response))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test;
+  use super::*;
+  use crate::mocks::MockHealth;
+  use actix_web::App;
+
+  #[actix_web::test]
+  async fn test_health_details() {
+    let mut mock_health = MockHealth::new();
+
+    mock_health.expect_status()
+      .times(1)
+      .returning(|| Ok(db::HealthStatus {
+        backend: String::from("sqlite"),
+        applied_migrations: vec![20231124190400],
+        pending_migrations: vec![],
+        note_count: 3,
+      }));
+
+    let health_data = Data::new(Box::new(mock_health) as Box<dyn HealthRepository>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(health_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/health/details").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 200);
+
+    let body = test::read_body(resp).await;
+    let returned: HealthDetailsResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.status, "ok");
+    assert_eq!(returned.backend, "sqlite");
+    assert_eq!(returned.note_count, 3);
+  }
+
+  #[actix_web::test]
+  async fn test_health_details_pending_migrations() {
+    let mut mock_health = MockHealth::new();
+
+    mock_health.expect_status()
+      .times(1)
+      .returning(|| Ok(db::HealthStatus {
+        backend: String::from("sqlite"),
+        applied_migrations: vec![20231124190400],
+        pending_migrations: vec![20231201120000],
+        note_count: 3,
+      }));
+
+    let health_data = Data::new(Box::new(mock_health) as Box<dyn HealthRepository>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(health_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/health/details").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 503);
+  }
+}
@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::Error;
+use anyhow::anyhow;
+
+use crate::error::ApiError;
+
+/// An IP network such as `10.0.0.0/8` or `fd00::/8`. A bare address matches only itself.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Cidr {
+  network: IpAddr,
+  prefix: u8,
+}
+
+impl Cidr {
+  pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+    let ip = match ip {
+      IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+      ip => ip,
+    };
+
+    match (self.network, ip) {
+      (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(u32::from(network).into(), u32::from(ip).into(), 32, self.prefix),
+      (IpAddr::V6(network), IpAddr::V6(ip)) => prefix_matches(u128::from(network), u128::from(ip), 128, self.prefix),
+      _ => false,
+    }
+  }
+}
+
+fn prefix_matches(network: u128, ip: u128, bits: u8, prefix: u8) -> bool {
+  let host_bits = u32::from(bits - prefix);
+  network.checked_shr(host_bits).unwrap_or(0) == ip.checked_shr(host_bits).unwrap_or(0)
+}
+
+impl FromStr for Cidr {
+  type Err = anyhow::Error;
+
+  fn from_str(value: &str) -> anyhow::Result<Self> {
+    let (address, prefix) = match value.split_once('/') {
+      Some((address, prefix)) => (address, Some(prefix)),
+      None => (value, None),
+    };
+    let network: IpAddr = address.trim().parse().map_err(|_| anyhow!("invalid IP address: {}", value))?;
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+      Some(prefix) => prefix.trim().parse::<u8>().ok().filter(|prefix| *prefix <= bits).ok_or_else(|| anyhow!("invalid prefix length: {}", value))?,
+      None => bits,
+    };
+
+    Ok(Cidr { network, prefix })
+  }
+}
+
+/// Parses a comma separated list of networks, ignoring blank entries.
+pub(crate) fn parse_cidr_list(value: &str) -> anyhow::Result<Vec<Cidr>> {
+  value.split(',').map(str::trim).filter(|entry| !entry.is_empty()).map(Cidr::from_str).collect()
+}
+
+/// Decides which peers may use the API. The deny list wins over the allow list, and an empty
+/// allow list allows every peer that isn't denied.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IpFilter {
+  allow: Vec<Cidr>,
+  deny: Vec<Cidr>,
+}
+
+impl IpFilter {
+  pub(crate) fn new(allow: Vec<Cidr>, deny: Vec<Cidr>) -> Self {
+    IpFilter { allow, deny }
+  }
+
+  /// Peers without a known address are only allowed when both lists are empty.
+  pub(crate) fn allows(&self, peer: Option<IpAddr>) -> bool {
+    match peer {
+      Some(ip) => !self.deny.iter().any(|cidr| cidr.contains(ip)) && (self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip))),
+      None => self.allow.is_empty() && self.deny.is_empty(),
+    }
+  }
+}
+
+/// `wrap_fn` middleware answering 403 to peers `filter` doesn't allow.
+pub(super) fn filter_ip<S, B>(req: ServiceRequest, srv: &S, filter: &IpFilter) -> impl Future<Output = Result<ServiceResponse<EitherBody<B>>, Error>>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+  let peer = req.peer_addr().map(|addr| addr.ip());
+  let response = if filter.allows(peer) { Ok(srv.call(req)) } else { Err(req) };
+
+  async move {
+    match response {
+      Ok(response) => response.await.map(ServiceResponse::map_into_left_body),
+      Err(req) => Ok(req.error_response(ApiError::Forbidden).map_into_right_body()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, web, App, HttpResponse};
+  use super::*;
+
+  #[test]
+  fn test_cidr_contains() {
+    let network: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(network.contains("10.1.2.3".parse().unwrap()));
+    assert!(network.contains("::ffff:10.1.2.3".parse().unwrap()));
+    assert!(!network.contains("11.0.0.1".parse().unwrap()));
+
+    let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(everything.contains("192.168.1.1".parse().unwrap()));
+
+    let single: Cidr = "fd00::1".parse().unwrap();
+    assert!(single.contains("fd00::1".parse().unwrap()));
+    assert!(!single.contains("fd00::2".parse().unwrap()));
+
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("not-an-ip".parse::<Cidr>().is_err());
+  }
+
+  #[actix_web::test]
+  async fn test_denied_peer_is_forbidden() {
+    let filter = IpFilter::new(parse_cidr_list("10.0.0.0/8").unwrap(), parse_cidr_list("10.0.0.66").unwrap());
+    let mut app = test::init_service(
+      App::new()
+        .wrap_fn(move |req, srv| filter_ip(req, srv, &filter))
+        .route("/notes", web::get().to(HttpResponse::Ok))
+    ).await;
+
+    for (peer, status) in [("10.0.0.5:4000", 200), ("10.0.0.66:4000", 403), ("192.168.0.1:4000", 403)] {
+      let req = test::TestRequest::get().uri("/notes").peer_addr(peer.parse().unwrap()).to_request();
+      assert_eq!(test::call_service(&mut app, req).await.status(), status, "{}", peer);
+    }
+  }
+}
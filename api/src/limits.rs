@@ -0,0 +1,97 @@
+use actix_web::{get, HttpResponse, web::{Data, ServiceConfig}};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::config::Config;
+
+pub(super) fn configure() -> impl FnOnce(&mut ServiceConfig) {
+  |config: &mut ServiceConfig| {
+    config.service(get_limits);
+  }
+}
+
+/// Validation limits in force, so clients don't have to hardcode them. Lengths are in characters.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct LimitsResponse {
+  #[schema(example = 1)]
+  title_min_length: usize,
+  #[schema(example = 50)]
+  title_max_length: usize,
+  /// Shortest accepted content, ignoring surrounding whitespace
+  #[schema(example = 1)]
+  content_min_length: usize,
+  #[schema(example = 200)]
+  content_max_length: usize,
+  #[schema(example = 30)]
+  tag_max_length: usize,
+  /// Fewest distinct tags a note may be created or retagged with
+  #[schema(example = 0)]
+  min_tags: usize,
+  /// Most distinct tags a note may carry, or null when unlimited
+  #[schema(example = json!(null))]
+  max_tags: Option<usize>,
+  #[schema(example = 200)]
+  max_search_query_length: usize,
+  #[schema(example = 200)]
+  max_page_size: i64,
+  /// Most notes in one export, or null when unlimited
+  #[schema(example = 10000)]
+  max_export_notes: Option<i64>,
+}
+
+impl From<&Config> for LimitsResponse {
+  fn from(config: &Config) -> Self {
+    Self {
+      title_min_length: db::TITLE_MIN_LENGTH,
+      title_max_length: db::TITLE_MAX_LENGTH,
+      content_min_length: db::CONTENT_MIN_LENGTH.max(config.min_content_length),
+      content_max_length: db::CONTENT_MAX_LENGTH,
+      tag_max_length: db::TAG_MAX_LENGTH,
+      min_tags: config.min_tags,
+      max_tags: (config.max_tags > 0).then_some(config.max_tags),
+      max_search_query_length: config.max_search_query_length,
+      max_page_size: config.max_page_size,
+      max_export_notes: (config.max_export_notes > 0).then_some(config.max_export_notes),
+    }
+  }
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Validation limits in force on this server", body = LimitsResponse),
+  )
+)]
+#[get("/limits")]
+pub(super) async fn get_limits(config: Data<Config>) -> HttpResponse {
+  HttpResponse::Ok().json(LimitsResponse::from(config.get_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::{test, App};
+  use super::*;
+
+  #[actix_web::test]
+  async fn test_get_limits() {
+    let config = Config { min_content_length: 10, max_tags: 5, max_export_notes: 0, ..Config::default() };
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(config))
+        .configure(configure())
+    ).await;
+
+    let req = test::TestRequest::get().uri("/limits").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: LimitsResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.title_max_length, db::TITLE_MAX_LENGTH);
+    assert_eq!(returned.content_min_length, 10);
+    assert_eq!(returned.max_tags, Some(5));
+    assert_eq!(returned.max_export_notes, None);
+  }
+}
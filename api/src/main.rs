@@ -1,19 +1,56 @@
 use std::{
     error::Error,
     net::Ipv4Addr,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
 };
 
-use actix_web::{App, HttpResponse, HttpServer, middleware, web};
+use actix_web::{App, HttpResponse, HttpServer, web};
+use actix_web::dev::Service;
 use actix_web::web::Data;
-use db::SqliteNoteRepository;
-use service::{NoteService, NoteServiceImpl};
+use db::{AuditRepository, HealthRepository, ReplicatedNoteRepository, SavedSearchRepository, SlowQueryLoggingRepository, SqliteAuditRepository, SqliteHealthRepository, SqliteNoteRepository, SqliteSavedSearchRepository};
+use service::{AuditingNoteService, NoteService, NoteServiceImpl, WriteLock, WriteLockedNoteService};
 use utoipa::OpenApi;
 use utoipa_rapidoc::RapiDoc;
 use utoipa_swagger_ui::SwaggerUi;
 
+use crate::config::Config;
 use crate::domain::MessageResponse;
+use crate::ip_filter::IpFilter;
+use crate::rate_limit::{RateLimiter, RouteClass};
 
+mod access_log;
+mod admin;
+mod audit;
+mod auth;
+mod blank_cleanup;
+mod cache;
+mod checksum;
+mod compare;
+mod config;
+mod export;
+mod feed;
+mod find_replace;
+mod health;
+mod ip_filter;
+mod limits;
+mod markdown;
+#[cfg(test)]
+mod mocks;
 mod note;
+mod note_id;
+mod patterns;
+mod rate_limit;
+mod reading_time;
+mod reaper;
+mod saved_search;
+mod seed;
+mod snippet;
+#[cfg(test)]
+mod test_logger;
+mod timestamp;
+mod word_frequency;
 mod error;
 mod domain;
 
@@ -25,16 +62,76 @@ async fn main() -> Result<(), impl Error> {
     #[openapi(
         paths(
             note::list_notes,
+            note::autocomplete_notes,
+            note::search_notes,
+            note::export_notes_zip,
+            note::export_notes_csv,
+            note::get_word_frequency,
+            note::notes_feed,
+            note::note_activity,
+            note::note_heatmap,
+            note::note_patterns,
+            note::note_archive,
+            note::note_stats,
+            note::count_notes,
+            note::related_notes,
+            note::note_context,
+            note::note_ids,
+            note::find_replace_notes,
+            note::oldest_note,
+            note::newest_note,
+            note::recently_viewed_notes,
+            note::most_edited_notes,
+            note::unread_notes,
+            note::notes_by_tag_overlap,
             note::get_note,
+            note::get_note_html,
+            note::get_note_plaintext,
+            note::get_note_checksum,
+            note::get_note_size,
+            note::export_portable_note,
+            note::import_portable_note,
+            note::compare_notes,
             note::create_note,
             note::put_note,
-            note::delete_note
+            note::delete_note,
+            note::touch_note,
+            note::append_note,
+            note::prepend_note,
+            note::insert_note,
+            note::touch_notes,
+            note::get_note_tags,
+            note::put_note_tags,
+            note::rename_tag,
+            note::tag_colors,
+            note::tag_co_occurrence,
+            note::tag_trend,
+            note::put_tag_color,
+            note::delete_tag_color,
+            saved_search::list_saved_searches,
+            saved_search::create_saved_search,
+            saved_search::saved_search_results,
+            audit::list_audit,
+            audit::session_changes,
+            limits::get_limits,
+            admin::migrate,
+            admin::repair,
+            admin::storage,
+            admin::raw_note,
+            admin::set_created_at,
+            health::health_details,
+            health::get_version
         ),
         components(
-            schemas(note::Note, note::ListNotesResponse, note::GetNoteResponse, note::CreateNoteRequest, note::CreateNoteResponse, note::UpdateNoteRequest, note::UpdateNoteResponse, domain::ErrorResponse, domain::MessageResponse)
+            schemas(note::Note, note::NoteFormat, note::ListNotesResponse, note::TagMatch, note::NoteTitle, note::AutocompleteResponse, note::ViewedNote, note::RecentlyViewedResponse, note::MostEditedResponse, note::UnreadResponse, note::SearchHit, note::SearchNotesResponse, note::GetNoteResponse, note::CreateNoteRequest, note::CreateNoteResponse, note::PortableNote, note::UpdateNoteRequest, note::UpdateNoteResponse, note::DeleteNoteResponse, note::NoteTagsResponse, note::RenameTagRequest, note::RenameTagResponse, note::TagPair, note::TagCoOccurrenceResponse, note::TagTrendResponse, note::SetTagColorRequest, note::TagColorsResponse, note::WordCount, note::WordFrequencyResponse, note::ActivityBucket, note::ActivityCount, note::ActivityResponse, note::HeatmapDay, note::HeatmapResponse, note::WeekdayCount, note::HourCount, note::PatternsResponse, note::ArchiveResponse, note::NoteStatsResponse, note::CountNotesResponse, note::NoteIdsResponse, note::RelatedNotesResponse, note::TagOverlapHit, note::TagOverlapResponse, note::NoteContextResponse, note::CompareNotesResponse, note::NoteChecksumResponse, note::NoteSizeResponse, note::TouchNoteResponse, note::TouchNotesRequest, note::AppendNoteRequest, note::FindReplaceRequest, note::FindReplaceResponse, note::TouchNotesResponse, saved_search::SavedSearchSort, saved_search::SavedSearch, saved_search::CreateSavedSearchRequest, saved_search::SavedSearchResponse, saved_search::ListSavedSearchesResponse, saved_search::SavedSearchResultsResponse, audit::AuditEntry, audit::ListAuditResponse, audit::SessionChangesResponse, admin::MigrateResponse, admin::RepairResponse, admin::StorageResponse, admin::SetCreatedAtRequest, health::HealthDetailsResponse, health::VersionResponse, limits::LimitsResponse, domain::ErrorResponse, domain::DuplicateContentResponse, domain::MessageResponse)
         ),
+        modifiers(&note::NoteLengthLimits),
         tags(
-            (name = "notes", description = "Note management endpoints.")
+            (name = "notes", description = "Note management endpoints."),
+            (name = "saved-searches", description = "Saved searches and their results."),
+            (name = "audit", description = "Audit trail of note mutations."),
+            (name = "admin", description = "Maintenance endpoints guarded by the API key."),
+            (name = "health", description = "Service health endpoints.")
         )
     )]
     struct ApiDoc;
@@ -42,24 +139,133 @@ async fn main() -> Result<(), impl Error> {
     // Make instance variable of ApiDoc so all worker threads gets the same instance.
     let openapi = ApiDoc::openapi();
 
-    let note_repository = SqliteNoteRepository::new("./notes.db").await.expect("Failed to connect to database.");
-    let note_service = NoteServiceImpl::new(note_repository);
+    let config = Data::new(Config::from_env().expect("Invalid configuration."));
+    error::set_hide_error_details(config.hide_error_details);
+    db::set_redact_content(config.redact_note_content);
+    db::set_title_locale(&config.title_locale).expect("Invalid TITLE_LOCALE.");
+    reading_time::set_words_per_minute(config.reading_words_per_minute);
+
+    let database_url = config.database_url.as_str();
+    let note_repository = if config.single_connection {
+        SqliteNoteRepository::single_connection(database_url).expect("Failed to connect to database.")
+    } else {
+        db::connect_with_retry(Duration::from_secs(config.database_wait_secs), || async {
+            if config.min_connections > 0 {
+                SqliteNoteRepository::new_with_min_connections(database_url, config.min_connections).await
+            } else {
+                SqliteNoteRepository::new(database_url).await
+            }
+        }).await.expect("Failed to connect to database.")
+    }
+        .with_max_concurrent_writes(config.max_concurrent_writes);
+    let read_repository = match &config.database_read_url {
+        Some(url) if config.single_connection => SqliteNoteRepository::single_connection(url).expect("Failed to connect to read replica."),
+        Some(url) => SqliteNoteRepository::new(url).await.expect("Failed to connect to read replica."),
+        None => note_repository.clone(),
+    };
+    // With one connection, an in-memory database only exists on it, so every repository shares it
+    let shared_pool = config.single_connection.then(|| note_repository.pool());
+    let note_repository = ReplicatedNoteRepository::new(read_repository, note_repository);
+    let note_repository = SlowQueryLoggingRepository::new(note_repository, Duration::from_millis(config.slow_query_ms));
+    if config.startup_selftest {
+        db::self_test(&note_repository).await.expect("Startup self-test failed.");
+        log::info!("Startup self-test passed");
+    }
+    let audit_repository = match &shared_pool {
+      Some(pool) => SqliteAuditRepository::from_pool(pool.clone()),
+      None => SqliteAuditRepository::new(database_url).await.expect("Failed to connect to database."),
+    };
+    let note_service = NoteServiceImpl::new(note_repository)
+        .with_min_content_length(config.min_content_length)
+        .with_tag_count_limits(config.min_tags, config.max_tags)
+        .with_auto_title(config.auto_title)
+        .with_default_content(config.default_note_content.clone())
+        .with_lowercase_tags(config.lowercase_tags)
+        .with_unique_content(config.unique_content)
+        .with_reject_binary_content(config.reject_binary_content)
+        .with_title_case(config.title_case);
+    let note_service = AuditingNoteService::new(note_service, audit_repository.clone());
+    let write_lock = WriteLock::new();
+    let note_service = WriteLockedNoteService::new(note_service, write_lock.clone());
+    if let Some(seed_file) = &config.seed_file {
+        let seeded = seed::seed(&note_service, seed_file).await.expect("Failed to seed database.");
+        log::info!("Seeded {} notes from {}", seeded, seed_file);
+    }
     let note_service_data = Data::new(Box::new(note_service) as Box<dyn NoteService>);
+    if config.expiry_reaper {
+        reaper::spawn(note_service_data.clone(), Duration::from_secs(config.expiry_reaper_interval_secs));
+    }
+    if config.blank_note_cleanup {
+        blank_cleanup::spawn(note_service_data.clone(), Duration::from_secs(config.blank_note_cleanup_interval_secs), config.blank_note_cleanup_dry_run);
+    }
+    let audit_repository_data = Data::new(Box::new(audit_repository) as Box<dyn AuditRepository>);
+    let saved_search_repository = match &shared_pool {
+      Some(pool) => SqliteSavedSearchRepository::from_pool(pool.clone()),
+      None => SqliteSavedSearchRepository::new(database_url).await.expect("Failed to connect to database."),
+    };
+    let saved_search_repository_data = Data::new(Box::new(saved_search_repository) as Box<dyn SavedSearchRepository>);
+    let health_repository = match &shared_pool {
+      Some(pool) => SqliteHealthRepository::from_pool(pool.clone()),
+      None => SqliteHealthRepository::new(database_url).await.expect("Failed to connect to database."),
+    };
+    let health_repository_data = Data::new(Box::new(health_repository) as Box<dyn HealthRepository>);
+    let write_lock_data = Data::new(write_lock);
 
-    HttpServer::new(move || {
+    let static_cache_max_age = config.static_cache_max_age;
+    let http_workers = config.http_workers;
+    let keep_alive = config.keep_alive();
+    let client_request_timeout = config.client_request_timeout();
+    let timestamp_format = config.timestamp_format;
+    let log_excluded_paths = config.log_excluded_paths.clone();
+    let ip_filter = IpFilter::new(config.ip_allowlist.clone(), config.ip_denylist.clone());
+    let rate_limiter = Arc::new(
+        RateLimiter::new(Duration::from_secs(60))
+            .with_limit(RouteClass::Crud, config.crud_rate_limit)
+            .with_limit(RouteClass::Search, config.search_rate_limit)
+            .with_limit(RouteClass::Export, config.export_rate_limit)
+            .with_limit(RouteClass::Stats, config.stats_rate_limit)
+    );
+    let shutdown_backup = config.backup_on_shutdown.then(|| (config.database_url.clone(), PathBuf::from(&config.backup_dir)));
+    let server = HttpServer::new(move || {
         // This factory closure is called on each worker thread independently.
+        timestamp::set_format(timestamp_format);
+        let rate_limiter = rate_limiter.clone();
+        let ip_filter = ip_filter.clone();
         App::new()
-            .wrap(middleware::Logger::default())
-            .configure(note::configure(note_service_data.clone()))
+            .wrap_fn(|req, srv| service::with_actor(audit::actor(&req), srv.call(req)))
+            .wrap_fn(|req, srv| service::with_session(audit::session(&req), srv.call(req)))
+            .wrap_fn(move |req, srv| rate_limit::limit_rate(req, srv, &rate_limiter))
+            .wrap_fn(move |req, srv| cache::add_cache_control(req, srv, static_cache_max_age))
+            .wrap_fn(move |req, srv| ip_filter::filter_ip(req, srv, &ip_filter))
+            .wrap(access_log::logger(&log_excluded_paths))
+            .configure(note::configure(note_service_data.clone(), config.clone()))
+            .configure(saved_search::configure(saved_search_repository_data.clone()))
+            .configure(audit::configure(audit_repository_data.clone()))
+            .configure(health::configure(health_repository_data.clone()))
+            .configure(limits::configure())
+            .configure(admin::configure(health_repository_data.clone(), write_lock_data.clone()))
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api-docs/openapi.json", openapi.clone()),
             )
             .service(RapiDoc::new("/api-docs/openapi.json").path("/rapidoc"))
             .default_service(web::route().to(not_found))
     })
+        .keep_alive(keep_alive)
+        .client_request_timeout(client_request_timeout);
+    let server = if http_workers > 0 { server.workers(http_workers) } else { server };
+    let served = server
         .bind((Ipv4Addr::UNSPECIFIED, 8081))?
         .run()
-        .await
+        .await;
+
+    if let Some((database_url, backup_dir)) = shutdown_backup {
+        match db::backup(&database_url, &backup_dir).await {
+            Ok(path) => log::info!("Backed up the database to {}", path.display()),
+            Err(error) => log::error!("Failed to back up the database on shutdown: {:#}", error),
+        }
+    }
+
+    served
 }
 
 async fn not_found() -> HttpResponse {
@@ -0,0 +1,81 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+/// Renders Markdown to HTML, stripping scripts, event handlers and anything else ammonia considers unsafe.
+pub(crate) fn to_safe_html(markdown: &str) -> String {
+  let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS);
+  let mut unsafe_html = String::new();
+  html::push_html(&mut unsafe_html, parser);
+
+  ammonia::clean(&unsafe_html)
+}
+
+/// Renders Markdown to plain text: the text of every block on its own line, without markup.
+/// Inline HTML is dropped.
+pub(crate) fn to_plain_text(markdown: &str) -> String {
+  let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS);
+  let mut text = String::new();
+  for event in parser {
+    match event {
+      Event::Text(fragment) | Event::Code(fragment) => text.push_str(&fragment),
+      Event::SoftBreak | Event::HardBreak | Event::Rule => text.push('\n'),
+      Event::TaskListMarker(checked) => text.push_str(if checked { "[x] " } else { "[ ] " }),
+      Event::End(Tag::TableCell) => text.push(' '),
+      Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::Item | Tag::CodeBlock(_) | Tag::TableHead | Tag::TableRow) => {
+        if !text.ends_with('\n') {
+          text.push('\n');
+        }
+      }
+      _ => {}
+    }
+  }
+
+  text.trim_end().to_string()
+}
+
+/// Removes every tag from HTML, keeping the text between them.
+pub(crate) fn html_to_plain_text(html: &str) -> String {
+  ammonia::Builder::empty()
+    .clean(html)
+    .to_string()
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&amp;", "&")
+}
+
+/// Shows plain text as preformatted HTML, escaping anything that would otherwise be markup.
+pub(crate) fn plain_to_html(text: &str) -> String {
+  let escaped = text
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+    .replace('\'', "&#39;");
+
+  format!("<pre>{}</pre>", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_to_safe_html_strips_scripts() {
+    let html = to_safe_html("Hi <script>alert(1)</script><a href=\"javascript:alert(1)\">x</a>");
+
+    assert!(!html.contains("<script"));
+    assert!(!html.contains("javascript:"));
+  }
+
+  #[test]
+  fn test_to_plain_text() {
+    assert_eq!(to_plain_text("**bold**"), "bold");
+    assert_eq!(to_plain_text("# Title\n\nSome *text* with `code` and [a link](https://example.com).\n\n- [x] done\n- open"), "Title\nSome text with code and a link.\n[x] done\nopen");
+  }
+
+  #[test]
+  fn test_html_to_plain_text() {
+    assert_eq!(html_to_plain_text("<p>Fish &amp; <b>chips</b></p>"), "Fish & chips");
+  }
+}
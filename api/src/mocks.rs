@@ -0,0 +1,80 @@
+//! Mocks shared by the handler tests.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mockall::mock;
+
+mock! {
+  pub(crate) Service {}
+  #[async_trait]
+  impl service::NoteService for Service {
+    async fn all(&self) -> Result<Vec<db::Note>>;
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+    async fn count(&self) -> Result<i64>;
+    async fn get(&self, id: &str) -> Result<db::Note>;
+    async fn create(&self, note: &db::NewNote) -> Result<db::Note>;
+    async fn update(&self, id: &str, note: &db::UpdateNote) -> Result<db::Note>;
+    async fn delete(&self, id: &str) -> Result<db::Note>;
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<db::Note>;
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<db::Note>>;
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<db::NoteTitle>>;
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+    async fn replace_tags(&self, id: &str, tags: &db::NoteTags) -> Result<Vec<String>>;
+    async fn all_tags(&self) -> Result<Vec<db::NoteTag>>;
+    async fn recent(&self, limit: i64) -> Result<Vec<db::Note>>;
+    async fn create_with_tags(&self, note: &db::NewNote, tags: &db::NoteTags) -> Result<(db::Note, Vec<String>)>;
+    async fn create_many(&self, notes: &[db::NewNote]) -> Result<Vec<db::Note>>;
+    async fn activity(&self, from: &str, to: &str, bucket: db::ActivityBucket) -> Result<Vec<db::ActivityCount>>;
+    async fn count_by_tag(&self, tag: &str) -> Result<i64>;
+    async fn search_by(&self, filter: &db::SearchFilter, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<db::Note>;
+    async fn insert(&self, id: &str, offset: usize, text: &str, updated_at: &str) -> Result<db::Note>;
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>>;
+    async fn try_get(&self, id: &str) -> Result<Option<db::Note>>;
+    async fn exists_any(&self) -> Result<bool>;
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>>;
+    async fn count_by(&self, filter: &db::SearchFilter) -> Result<i64>;
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<db::Note>>;
+    async fn by_tags(&self, tags: &[String], matching: db::TagMatch, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+    async fn count_by_tags(&self, tags: &[String], matching: db::TagMatch) -> Result<i64>;
+    async fn oldest(&self) -> Result<Option<db::Note>>;
+    async fn newest(&self) -> Result<Option<db::Note>>;
+    async fn neighbors(&self, id: &str) -> Result<db::NoteNeighbors>;
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>>;
+    async fn search_in_tag(&self, tag: &str, query: &str, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<db::Note>, i64)>;
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>>;
+    async fn creation_minutes(&self) -> Result<Vec<db::ActivityCount>>;
+    async fn update_many(&self, updates: &[(String, db::UpdateNote)]) -> Result<Vec<db::Note>>;
+    async fn raw(&self, id: &str) -> Result<Vec<db::RawColumn>>;
+    async fn repair_orphans(&self) -> Result<db::RepairReport>;
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<db::Note>;
+    async fn all_by(&self, filter: &db::SearchFilter) -> Result<Vec<db::Note>>;
+    async fn get_with_tags(&self, id: &str) -> Result<(db::Note, Vec<String>)>;
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()>;
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<db::ViewedNote>>;
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(db::Note, i64)>>;
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()>;
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool>;
+    async fn tag_colors(&self) -> Result<Vec<db::TagColor>>;
+    async fn most_edited(&self, limit: i64) -> Result<Vec<db::Note>>;
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<db::TagPair>>;
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<db::Note>>;
+    async fn storage_size(&self, id: &str) -> Result<db::NoteSize>;
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<db::Note>>;
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<db::ActivityCount>>;
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64>;
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<db::NoteTag>>;
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<db::Note>>;
+  }
+}
+
+mock! {
+  pub(crate) Health {}
+  #[async_trait]
+  impl db::HealthRepository for Health {
+    async fn status(&self) -> Result<db::HealthStatus>;
+    async fn migrate(&self) -> Result<Vec<i64>>;
+    async fn storage(&self) -> Result<db::StorageFootprint>;
+  }
+}
@@ -1,24 +1,95 @@
-use actix_web::{delete, get, HttpResponse, post, put, web::{Data, Path, ServiceConfig}};
-use actix_web::web::Json;
-use db::UpdateNote;
+use std::collections::{BTreeMap, HashSet};
+use std::rc::Rc;
+
+use actix_web::{delete, get, HttpResponse, post, put, web::{Data, ServiceConfig}};
+use actix_web::HttpResponseBuilder;
+use actix_web::http::StatusCode;
+use actix_web::http::header::WARNING;
+use actix_web::web::{Bytes, Json, JsonConfig, Path, Query};
+use futures::{future, stream, StreamExt};
+use db::{DbError, NoteTags, SearchFilter, SearchSort, UpdateNote};
 use serde::{Deserialize, Serialize};
 use service::NoteService;
-use utoipa::ToSchema;
-use uuid::Uuid;
-use crate::error::ApiError;
-use crate::domain::{ErrorResponse, MessageResponse};
+use utoipa::{IntoParams, Modify, ToSchema};
+use utoipa::openapi::{Object, RefOr, Schema};
+use chrono::{Duration, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use validator::{ValidationError, ValidationErrors};
+use crate::checksum;
+use crate::compare;
+use crate::config::Config;
+use crate::error::{ApiError, json_error_handler};
+use crate::export;
+use crate::feed;
+use crate::find_replace::FindReplace;
+use crate::markdown;
+use crate::patterns;
+use crate::reading_time;
+use crate::snippet;
+use crate::note_id::{self, NoteId};
+use crate::timestamp;
+use crate::word_frequency;
+use crate::domain::{DuplicateContentResponse, ErrorResponse, MessageResponse};
 #[cfg(test)]
-use mockall::{mock, predicate::*};
+use mockall::predicate::*;
+
+pub(super) fn configure(note_service: Data<Box<dyn NoteService>>, app_config: Data<Config>) -> impl FnOnce(&mut ServiceConfig) {
+  let json_config = JsonConfig::default()
+    .content_type_required(app_config.require_json_content_type)
+    .error_handler(json_error_handler);
 
-pub(super) fn configure(note_service: Data<Box<dyn NoteService>>) -> impl FnOnce(&mut ServiceConfig) {
   |config: &mut ServiceConfig| {
     config
       .app_data(note_service)
+      .app_data(app_config)
+      .app_data(json_config)
       .service(list_notes)
+      .service(autocomplete_notes)
+      .service(search_notes)
+      .service(export_notes_zip)
+      .service(export_notes_csv)
+      .service(get_word_frequency)
+      .service(notes_feed)
+      .service(note_activity)
+      .service(note_heatmap)
+      .service(note_patterns)
+      .service(note_archive)
+      .service(note_stats)
+      .service(count_notes)
+      .service(note_ids)
+      .service(find_replace_notes)
+      .service(oldest_note)
+      .service(newest_note)
+      .service(recently_viewed_notes)
+      .service(most_edited_notes)
+      .service(unread_notes)
+      .service(notes_by_tag_overlap)
       .service(get_note)
       .service(create_note)
       .service(put_note)
-      .service(delete_note);
+      .service(delete_note)
+      .service(touch_notes)
+      .service(touch_note)
+      .service(get_note_tags)
+      .service(put_note_tags)
+      .service(rename_tag)
+      .service(tag_colors)
+      .service(tag_co_occurrence)
+      .service(tag_trend)
+      .service(put_tag_color)
+      .service(delete_tag_color)
+      .service(get_note_html)
+      .service(get_note_plaintext)
+      .service(get_note_checksum)
+      .service(get_note_size)
+      .service(export_portable_note)
+      .service(import_portable_note)
+      .service(related_notes)
+      .service(note_context)
+      .service(compare_notes)
+      .service(append_note)
+      .service(prepend_note)
+      .service(insert_note);
   }
 }
 
@@ -31,17 +102,538 @@ pub(super) struct Note {
   /// Title of the note
   #[schema(example = "Note 1")]
   title: String,
-  /// Content of the note
+  /// Content of the note, omitted in previews
   #[schema(example = "This is note #1.")]
-  content: String,
-  /// Date of creation
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  content: Option<String>,
+  /// Size of the content in bytes
+  #[schema(example = 16)]
+  content_length: usize,
+  /// Estimated minutes to read the content, rounded up, at `READING_WORDS_PER_MINUTE`
+  #[schema(example = 1)]
+  reading_time_minutes: usize,
+  /// Date of creation, as RFC 3339 or epoch milliseconds depending on `TIMESTAMP_FORMAT`
   #[schema(example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
   created_at: String,
+  /// Date of the last update, in the same format as `createdAt`
+  #[schema(example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
+  updated_at: String,
+  /// Incremented on every update
+  #[schema(example = 1)]
+  version: i64,
+  /// When the note is deleted, in the same format as `createdAt`. Absent for notes that don't expire.
+  #[schema(value_type = Option<String>, example = "2021-01-08T00:00:00Z")]
+  #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "timestamp::serialize_option", deserialize_with = "timestamp::deserialize_option")]
+  expires_at: Option<String>,
+  /// How the content is written
+  #[serde(default)]
+  format: NoteFormat,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum NoteFormat {
+  /// Rendered as Markdown
+  #[default]
+  Markdown,
+  /// Shown as is
+  Plain,
+  /// Sanitized HTML
+  Html,
+}
+
+impl From<NoteFormat> for db::NoteFormat {
+  fn from(format: NoteFormat) -> Self {
+    match format {
+      NoteFormat::Markdown => db::NoteFormat::Markdown,
+      NoteFormat::Plain => db::NoteFormat::Plain,
+      NoteFormat::Html => db::NoteFormat::Html,
+    }
+  }
+}
+
+impl From<db::NoteFormat> for NoteFormat {
+  fn from(format: db::NoteFormat) -> Self {
+    match format {
+      db::NoteFormat::Markdown => NoteFormat::Markdown,
+      db::NoteFormat::Plain => NoteFormat::Plain,
+      db::NoteFormat::Html => NoteFormat::Html,
+    }
+  }
+}
+
+/// Carries the total of a note list sent without its `ListNotesResponse` envelope.
+const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+/// Set to `true` on a note list whose requested limit was above the maximum page size.
+const RESULT_TRUNCATED_HEADER: &str = "X-Result-Truncated";
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct ListNotesQuery {
+  /// Maximum number of notes to return. Clamped to the server's maximum page size, in which case
+  /// the response has an `X-Result-Truncated: true` header.
+  limit: Option<i64>,
+  /// Number of notes to skip
+  offset: Option<i64>,
+  /// Omit note content and only report its length
+  preview_only: Option<bool>,
+  /// Only notes whose title or content contains this text
+  q: Option<String>,
+  /// Only notes with this tag
+  tag: Option<String>,
+  /// Comma-separated tags. Only notes with all or any of them, depending on `match`. Can't be combined with `q` or `tag`.
+  tags: Option<String>,
+  /// Whether notes need all of `tags` or just one of them. Defaults to all.
+  #[serde(rename = "match")]
+  tag_match: Option<TagMatch>,
+  /// Wrap the notes in a `ListNotesResponse`. With `false` the body is a bare array of notes and
+  /// the total is sent in the `X-Total-Count` header. Defaults to true.
+  envelope: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum TagMatch {
+  All,
+  Any,
+}
+
+impl From<TagMatch> for db::TagMatch {
+  fn from(matching: TagMatch) -> Self {
+    match matching {
+      TagMatch::All => db::TagMatch::All,
+      TagMatch::Any => db::TagMatch::Any,
+    }
+  }
+}
+
+/// Parses the `tags` list of `list_notes`, or `None` when it isn't given.
+fn tag_set(query: &ListNotesQuery, config: &Config) -> Result<Option<(Vec<String>, db::TagMatch)>, ValidationErrors> {
+  let Some(tags) = &query.tags else {
+    return Ok(None);
+  };
+  let tags: Vec<String> = tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(str::to_string).collect();
+  let message = if tags.is_empty() {
+    "tags must name at least one tag"
+  } else if query.q.is_some() || query.tag.is_some() {
+    "tags can't be combined with q or tag"
+  } else {
+    check_filter_tag_count(&tags, config)?;
+    return Ok(Some((tags, query.tag_match.unwrap_or(TagMatch::All).into())));
+  };
+
+  let mut error = ValidationError::new("tags");
+  error.message = Some(message.into());
+  let mut errors = ValidationErrors::new();
+  errors.add("tags", error);
+  Err(errors)
+}
+
+/// Rejects tag filters naming more distinct tags than `max_filter_tags`, which would make for
+/// expensive queries.
+fn check_filter_tag_count(tags: &[String], config: &Config) -> Result<(), ValidationErrors> {
+  let count = tags.iter().collect::<HashSet<_>>().len();
+  if count <= config.max_filter_tags {
+    return Ok(());
+  }
+
+  let mut error = ValidationError::new("tag_count");
+  error.message = Some(format!("tags may name at most {} tags", config.max_filter_tags).into());
+  error.add_param("max".into(), &config.max_filter_tags);
+  error.add_param("value".into(), &count);
+  let mut errors = ValidationErrors::new();
+  errors.add("tags", error);
+  Err(errors)
+}
+
+/// Filters shared by the note count and the export, matching those of the note list.
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct NoteFilterQuery {
+  /// Only notes whose title or content contains this text
+  q: Option<String>,
+  /// Only notes with this tag
+  tag: Option<String>,
+}
+
+/// Notes read per query while streaming the CSV export.
+const CSV_EXPORT_BATCH_SIZE: i64 = 500;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct ExportCsvQuery {
+  /// First day notes were created on, as YYYY-MM-DD
+  from: String,
+  /// Last day notes were created on, inclusive, as YYYY-MM-DD
+  to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct CountNotesResponse {
+  /// Number of notes matching the filter
+  #[schema(example = 3)]
+  count: i64,
+}
+
+/// Builds the filter shared by `list_notes` and `count_notes`, or `None` when neither `q` nor `tag` is given.
+fn note_filter(q: Option<&str>, tag: Option<&str>, config: &Config) -> Result<Option<SearchFilter>, ValidationErrors> {
+  if q.is_none() && tag.is_none() {
+    return Ok(None);
+  }
+
+  Ok(Some(SearchFilter {
+    query: normalize_search_query(q.unwrap_or_default(), config.max_search_query_length)?,
+    tag: tag.map(str::to_string),
+    // Same order as an unfiltered list
+    sort: SearchSort::Oldest,
+  }))
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub(super) struct ListNotesResponse {
   notes: Vec<Note>,
+  /// Total number of notes
+  total: i64,
+  /// Page size actually applied, which may be lower than the requested one
+  limit: i64,
+  offset: i64,
+}
+
+/// Upper bound for the number of autocomplete suggestions.
+const MAX_AUTOCOMPLETE_LIMIT: i64 = 20;
+const DEFAULT_AUTOCOMPLETE_LIMIT: i64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct AutocompleteQuery {
+  /// Prefix the titles must start with
+  q: String,
+  /// Maximum number of suggestions, at most 20
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteTitle {
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  id: String,
+  #[schema(example = "Note 1")]
+  title: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct GetNoteQuery {
+  /// Count this request as a view, shown by `GET /notes/recently-viewed`. Defaults to false.
+  track: Option<bool>,
+}
+
+/// Upper bound for the number of recently viewed notes.
+const MAX_RECENTLY_VIEWED_LIMIT: i64 = 50;
+const DEFAULT_RECENTLY_VIEWED_LIMIT: i64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct RecentlyViewedQuery {
+  /// Maximum number of notes, at most 50
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct ViewedNote {
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  id: String,
+  #[schema(example = "Note 1")]
+  title: String,
+  /// Number of tracked views
+  #[schema(example = 3)]
+  view_count: i64,
+  /// Date of the last tracked view, in the same format as `createdAt`
+  #[schema(example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
+  last_viewed_at: String,
+}
+
+impl From<db::ViewedNote> for ViewedNote {
+  fn from(note: db::ViewedNote) -> Self {
+    ViewedNote { id: note.id, title: note.title, view_count: note.view_count, last_viewed_at: note.last_viewed_at }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct RecentlyViewedResponse {
+  /// Most recently viewed first
+  notes: Vec<ViewedNote>,
+}
+
+const MAX_MOST_EDITED_LIMIT: i64 = 50;
+const DEFAULT_MOST_EDITED_LIMIT: i64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct MostEditedQuery {
+  /// Maximum number of notes, at most 50
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct MostEditedResponse {
+  /// Highest version first, ties broken by the most recently updated
+  notes: Vec<Note>,
+}
+
+const MAX_UNREAD_LIMIT: i64 = 50;
+const DEFAULT_UNREAD_LIMIT: i64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct UnreadQuery {
+  /// Maximum number of notes, at most 50
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct UnreadResponse {
+  /// Most recently updated first
+  notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct AutocompleteResponse {
+  notes: Vec<NoteTitle>,
+}
+
+impl From<db::NoteTitle> for NoteTitle {
+  fn from(db_title: db::NoteTitle) -> Self {
+    Self {
+      id: db_title.id,
+      title: db_title.title,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct SearchNotesQuery {
+  /// Text to look for in titles and contents
+  q: String,
+  /// Only notes with this tag
+  tag: Option<String>,
+  /// Maximum number of notes to return. Clamped to the server's maximum number of search results.
+  limit: Option<i64>,
+  /// Number of matching notes to skip
+  offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SearchHit {
+  #[serde(flatten)]
+  note: Note,
+  /// Text around the first match in the content, or the title when only the title matches, with the match wrapped in `**`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  snippet: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SearchNotesResponse {
+  notes: Vec<SearchHit>,
+  /// Whether more notes match than were returned
+  truncated: bool,
+  /// Number of results actually applied, which may be lower than the requested one
+  limit: i64,
+  offset: i64,
+}
+
+/// Upper bound for the number of words returned by the word frequency endpoint.
+const MAX_WORD_FREQUENCY_TOP: usize = 100;
+const DEFAULT_WORD_FREQUENCY_TOP: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct WordFrequencyQuery {
+  /// Number of words to return, at most 100
+  top: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct WordCount {
+  #[schema(example = "milk")]
+  word: String,
+  #[schema(example = 3)]
+  count: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct WordFrequencyResponse {
+  words: Vec<WordCount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ActivityBucket {
+  Day,
+  Week,
+  Month,
+}
+
+impl From<ActivityBucket> for db::ActivityBucket {
+  fn from(bucket: ActivityBucket) -> Self {
+    match bucket {
+      ActivityBucket::Day => db::ActivityBucket::Day,
+      ActivityBucket::Week => db::ActivityBucket::Week,
+      ActivityBucket::Month => db::ActivityBucket::Month,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct ActivityQuery {
+  /// First day of the range, as YYYY-MM-DD
+  from: String,
+  /// Last day of the range, inclusive, as YYYY-MM-DD
+  to: String,
+  /// Bucket size: day, week or month. Defaults to day.
+  bucket: Option<ActivityBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct ActivityCount {
+  /// Day (2021-01-31), week (2021-W04) or month (2021-01)
+  #[schema(example = "2021-01-31")]
+  bucket: String,
+  #[schema(example = 3)]
+  count: i64,
+}
+
+/// Longest range `GET /notes/heatmap` reports on, a year including a leap day.
+const MAX_HEATMAP_DAYS: i64 = 366;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct HeatmapQuery {
+  /// First day of the range, as YYYY-MM-DD
+  from: String,
+  /// Last day of the range, inclusive, as YYYY-MM-DD. At most a year after `from`.
+  to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct HeatmapDay {
+  #[schema(example = "2021-01-31")]
+  date: String,
+  #[schema(example = 3)]
+  count: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct HeatmapResponse {
+  /// Every day of the range, oldest first, including days without notes
+  days: Vec<HeatmapDay>,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct PatternsQuery {
+  /// IANA time zone the days and hours are counted in, such as `Europe/Berlin`. Defaults to UTC.
+  tz: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct WeekdayCount {
+  #[schema(example = "monday")]
+  day: String,
+  #[schema(example = 3)]
+  count: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct HourCount {
+  /// Hour of the day, from 0 to 23
+  #[schema(example = 9)]
+  hour: usize,
+  #[schema(example = 3)]
+  count: i64,
+}
+
+/// Parses an IANA time zone name, defaulting to UTC.
+fn time_zone(tz: Option<&str>) -> Result<Tz, ValidationErrors> {
+  let Some(tz) = tz else {
+    return Ok(Tz::UTC);
+  };
+
+  tz.parse::<Tz>().map_err(|_| {
+    let mut error = ValidationError::new("tz");
+    error.message = Some(format!("unknown time zone {}", tz).into());
+    let mut errors = ValidationErrors::new();
+    errors.add("tz", error);
+    errors
+  })
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct ArchiveQuery {
+  /// IANA time zone the month is taken in, such as `Europe/Berlin`. Defaults to UTC.
+  tz: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct ArchiveResponse {
+  #[schema(example = 2023)]
+  year: i32,
+  #[schema(example = 12)]
+  month: u32,
+  /// Time zone the month is taken in
+  #[schema(example = "UTC")]
+  tz: String,
+  /// Notes created in the month, oldest first
+  notes: Vec<Note>,
+}
+
+/// Start of the month and of the month after, in UTC in the format of `created_at`.
+fn month_range(year: i32, month: u32, tz: Tz) -> Result<(String, String), ValidationErrors> {
+  let start = NaiveDate::from_ymd_opt(year, month, 1);
+  let end = start.and_then(|_| if month == 12 { NaiveDate::from_ymd_opt(year + 1, 1, 1) } else { NaiveDate::from_ymd_opt(year, month + 1, 1) });
+  let (Some(start), Some(end)) = (start, end) else {
+    let mut error = ValidationError::new("range");
+    error.message = Some(format!("no month {} in year {}", month, year).into());
+    let mut errors = ValidationErrors::new();
+    errors.add("month", error);
+    return Err(errors);
+  };
+  let utc = |date: NaiveDate| {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight exists");
+    // Where a DST change skips midnight, the first hour of the day is near enough
+    let start = tz.from_local_datetime(&midnight).earliest().unwrap_or_else(|| tz.from_utc_datetime(&midnight));
+    start.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string()
+  };
+
+  Ok((utc(start), utc(end)))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct PatternsResponse {
+  /// Time zone the counts are in
+  #[schema(example = "UTC")]
+  tz: String,
+  /// Notes created on each day of the week, Monday first
+  weekdays: Vec<WeekdayCount>,
+  /// Notes created in each hour of the day, midnight first
+  hours: Vec<HourCount>,
+  /// Notes by day of the week, Monday first, then by hour of the day
+  grid: Vec<Vec<i64>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct ActivityResponse {
+  /// Buckets with at least one note, oldest first
+  counts: Vec<ActivityCount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct NoteStatsQuery {
+  /// Tag to count notes for
+  tag: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteStatsResponse {
+  /// Number of notes
+  #[schema(example = 42)]
+  total: i64,
+  /// Number of notes with the requested tag, present when `tag` was given
+  #[serde(skip_serializing_if = "Option::is_none")]
+  #[schema(example = 7)]
+  tagged: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
@@ -52,17 +644,72 @@ pub(super) struct GetNoteResponse {
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub(super) struct CreateNoteRequest {
+  /// Id to store the note under, for clients that assign their own. Generated when absent.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  id: Option<String>,
   /// Title of the note
   #[schema(example = "Note 1")]
   title: String,
-  /// Content of the note
+  /// Content of the note. `DEFAULT_NOTE_CONTENT` is used when it is empty or absent, if set.
+  #[serde(default)]
+  #[schema(example = "This is note #1.")]
+  content: String,
+  /// Initial tags, stored in the same transaction as the note
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schema(example = json!(["groceries"]))]
+  tags: Option<Vec<String>>,
+  /// When the note should be deleted, as RFC 3339 or epoch milliseconds
+  #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "timestamp::serialize_option", deserialize_with = "timestamp::deserialize_option")]
+  #[schema(value_type = Option<String>, example = "2021-01-08T00:00:00Z")]
+  expires_at: Option<String>,
+  /// How the content is written. Defaults to markdown.
+  #[serde(default)]
+  format: NoteFormat,
+}
+
+/// Shape of the `PortableNote` bundles written by this version. Bump it when a change to the bundle
+/// would make older servers misread it.
+const PORTABLE_SCHEMA_VERSION: u32 = 1;
+
+fn portable_schema_version() -> u32 {
+  // Bundles exported before the version was recorded have the shape of version 1
+  1
+}
+
+/// Self-contained copy of a note and its tags for moving it to another instance. Carries no id,
+/// as the note gets a new one where it is imported.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PortableNote {
+  /// Bundle format, checked on import. Bundles without one are read as version 1.
+  #[serde(default = "portable_schema_version")]
+  #[schema(example = 1)]
+  schema_version: u32,
+  #[schema(example = "Note 1")]
+  title: String,
   #[schema(example = "This is note #1.")]
   content: String,
+  #[serde(default)]
+  format: NoteFormat,
+  /// Date of creation, kept on import
+  #[schema(value_type = String, example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
+  created_at: String,
+  #[schema(value_type = Option<String>, example = "2021-01-08T00:00:00Z")]
+  #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "timestamp::serialize_option", deserialize_with = "timestamp::deserialize_option")]
+  expires_at: Option<String>,
+  #[serde(default)]
+  #[schema(example = json!(["groceries"]))]
+  tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub(super) struct CreateNoteResponse {
   note: Note,
+  /// Tags stored with the note, present when the request contained tags
+  #[serde(skip_serializing_if = "Option::is_none")]
+  tags: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
@@ -74,6 +721,10 @@ pub(super) struct UpdateNoteRequest {
   /// Content of the note
   #[schema(example = "This is note #1.")]
   content: String,
+  /// Version the update is based on. If the note changed since, the update is rejected with 409.
+  #[schema(example = 1)]
+  #[serde(default)]
+  version: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, ToSchema)]
@@ -81,313 +732,4147 @@ pub(super) struct UpdateNoteResponse {
   note: Note,
 }
 
-impl From<db::Note> for Note {
-  fn from(db_note: db::Note) -> Self {
-    Self {
-      id: db_note.id,
-      title: db_note.title,
-      content: db_note.content,
-      created_at: db_note.created_at.to_string(),
-    }
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct DeleteNoteQuery {
+  /// Respond with 200 and the deleted note instead of 204 without a body
+  #[serde(rename = "return")]
+  return_note: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct DeleteNoteResponse {
+  note: Note,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteTagsResponse {
+  /// Tags of the note, sorted alphabetically
+  #[schema(example = json!(["todo", "work"]))]
+  tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteSizeResponse {
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  id: String,
+  /// UTF-8 bytes of the title
+  #[schema(example = 6)]
+  title: i64,
+  /// UTF-8 bytes of the content
+  #[schema(example = 16)]
+  content: i64,
+  /// UTF-8 bytes of all tags together
+  #[schema(example = 8)]
+  tags: i64,
+  /// Sum of the above; ids, timestamps and database overhead aren't counted
+  #[schema(example = 30)]
+  total: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteChecksumResponse {
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  id: String,
+  #[schema(example = "sha256")]
+  algorithm: String,
+  /// Lowercase hex SHA-256 of the title, a newline and the content
+  #[schema(example = "ec944fb8447cf093c5d5d59fe036d80e1a570e92df2f3d245e5cc734ef94139b")]
+  checksum: String,
+  #[schema(example = 1)]
+  version: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TouchNoteResponse {
+  note: Note,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct AppendNoteRequest {
+  /// Text added to the end of the content as is; include a leading newline to start a new line
+  #[schema(example = "\n2021-01-01: went for a run")]
+  text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct FindReplaceRequest {
+  /// Text to look for in the content of every note
+  #[schema(example = "teh")]
+  find: String,
+  /// Text put in place of each match. With `regex`, `$1` or `${name}` insert capture groups.
+  #[schema(example = "the")]
+  replace: String,
+  /// Whether `find` is a regular expression rather than literal text
+  #[serde(default)]
+  regex: bool,
+  /// Report the notes that would change without changing them
+  #[serde(default, alias = "dry_run")]
+  dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct FindReplaceResponse {
+  /// Number of notes whose content changed, or would change in a dry run
+  #[schema(example = 1)]
+  changed: usize,
+  /// Ids of those notes
+  #[schema(example = json!(["14322988-32fe-447c-ac38-06fb6c699b4a"]))]
+  ids: Vec<String>,
+  dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct InsertNoteQuery {
+  /// Character offset to insert at, from 0 up to the content's length
+  offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct RenameTagRequest {
+  #[schema(example = "js")]
+  from: String,
+  #[schema(example = "javascript")]
+  to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct RenameTagResponse {
+  /// Ids of the notes that carried the old tag
+  #[schema(example = json!(["14322988-32fe-447c-ac38-06fb6c699b4a"]))]
+  note_ids: Vec<String>,
+}
+
+const MAX_TAG_PAIRS: i64 = 100;
+const DEFAULT_TAG_PAIRS: i64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct TagCoOccurrenceQuery {
+  /// Maximum number of tag pairs, at most 100
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TagPair {
+  #[schema(example = "rust")]
+  tag: String,
+  /// Sorts after `tag`
+  #[schema(example = "web")]
+  other_tag: String,
+  /// Number of notes carrying both tags
+  #[schema(example = 3)]
+  count: i64,
+}
+
+impl From<db::TagPair> for TagPair {
+  fn from(pair: db::TagPair) -> Self {
+    TagPair { tag: pair.tag, other_tag: pair.other_tag, count: pair.count }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TagCoOccurrenceResponse {
+  /// Most often used together first
+  pairs: Vec<TagPair>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TagTrendResponse {
+  #[schema(example = "rust")]
+  tag: String,
+  /// Notes with the tag by the month they were created (2021-01), oldest first. Months without any are left out.
+  months: Vec<ActivityCount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SetTagColorRequest {
+  /// `#rrggbb` hex color
+  #[schema(example = "#1e90ff")]
+  color: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TagColorsResponse {
+  /// Color of each tag that has one, by tag name
+  #[schema(example = json!({"work": "#1e90ff"}))]
+  colors: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TouchNotesRequest {
+  /// Ids of the notes to touch
+  #[schema(example = json!(["14322988-32fe-447c-ac38-06fb6c699b4a"]))]
+  ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TouchNotesResponse {
+  notes: Vec<Note>,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct NoteIdsQuery {
+  /// `nextCursor` of the previous page. The first page is returned without it.
+  cursor: Option<String>,
+  /// Maximum number of ids in the page, clamped to `MAX_IDS_PAGE_SIZE`
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct NoteIdsResponse {
+  /// Ids of the notes in the page, in ascending order
+  #[schema(example = json!(["14322988-32fe-447c-ac38-06fb6c699b4a"]))]
+  ids: Vec<String>,
+  /// Cursor of the next page, absent on the last page
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schema(example = "14322988-32fe-447c-ac38-06fb6c699b4a")]
+  next_cursor: Option<String>,
+}
+
+impl Note {
+  /// Drops the content while keeping its length, for lightweight list responses.
+  fn into_preview(self) -> Self {
+    Self { content: None, ..self }
+  }
+}
+
+impl From<db::Note> for Note {
+  fn from(db_note: db::Note) -> Self {
+    Self {
+      id: db_note.id,
+      title: db_note.title,
+      content_length: db_note.content.len(),
+      reading_time_minutes: reading_time::minutes(&db_note.content),
+      content: Some(db_note.content),
+      created_at: db_note.created_at.to_string(),
+      updated_at: db_note.updated_at,
+      version: db_note.version,
+      expires_at: db_note.expires_at,
+      format: db_note.format.parse::<db::NoteFormat>().unwrap_or_default().into(),
+    }
+  }
+}
+
+#[utoipa::path(
+  responses(
+    (status = 204, description = "The page has no notes and `EMPTY_LIST_NO_CONTENT` is enabled"),
+    (status = 200, description = "List notes, or a bare array of notes with `envelope=false`", body = ListNotesResponse, example = json ! (ListNotesResponse{notes: vec ! [Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: Some(String::from("This is note #1.")), content_length: 16, reading_time_minutes: 1, created_at: String::from("2021-01-01T00:00:00Z"), updated_at: String::from("2021-01-01T00:00:00Z"), version: 1, expires_at: None, format: NoteFormat::Markdown}], total: 1, limit: 50, offset: 0})),
+  ),
+  params(ListNotesQuery)
+)]
+#[get("/notes")]
+pub(super) async fn list_notes(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<ListNotesQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = config.page_size(query.limit);
+  let truncated = matches!(query.limit, Some(requested) if requested > limit);
+  let offset = query.offset.unwrap_or(0).max(0);
+  let (db_notes, total) = if let Some((tags, matching)) = tag_set(&query, &config)? {
+    (note_service.by_tags(&tags, matching, limit, offset).await?, note_service.count_by_tags(&tags, matching).await?)
+  } else {
+    match note_filter(query.q.as_deref(), query.tag.as_deref(), &config)? {
+      Some(filter) => (note_service.search_by(&filter, limit, offset).await?, note_service.count_by(&filter).await?),
+      None => note_service.page(limit, offset).await?,
+    }
+  };
+  let preview_only = query.preview_only.unwrap_or(false);
+  let api_notes: Vec<Note> = db_notes
+    .into_iter()
+    .map(Note::from)
+    .map(|note| if preview_only { note.into_preview() } else { note })
+    .collect();
+
+  let mut response = HttpResponse::Ok();
+  if truncated {
+    response.insert_header((RESULT_TRUNCATED_HEADER, "true"));
+  }
+
+  if api_notes.is_empty() && config.empty_list_no_content {
+    return Ok(response.status(StatusCode::NO_CONTENT).finish());
+  }
+
+  if !query.envelope.unwrap_or(true) {
+    return Ok(response
+      .insert_header((TOTAL_COUNT_HEADER, total.to_string()))
+      .json(api_notes));
+  }
+
+  Ok(response.json(ListNotesResponse { notes: api_notes, total, limit, offset }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Number of notes matching the same filters as the note list", body = CountNotesResponse),
+    (status = 400, description = "Search query is too long", body = ErrorResponse),
+  ),
+  params(NoteFilterQuery)
+)]
+#[get("/notes/count")]
+pub(super) async fn count_notes(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<NoteFilterQuery>) -> Result<HttpResponse, ApiError> {
+  let count = match note_filter(query.q.as_deref(), query.tag.as_deref(), &config)? {
+    Some(filter) => note_service.count_by(&filter).await?,
+    None => note_service.count().await?,
+  };
+
+  Ok(HttpResponse::Ok().json(CountNotesResponse { count }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes whose title starts with the given prefix", body = AutocompleteResponse, example = json ! (AutocompleteResponse{notes: vec ! [NoteTitle{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1")}]})),
+  ),
+  params(AutocompleteQuery)
+)]
+#[get("/notes/autocomplete")]
+pub(super) async fn autocomplete_notes(note_service: Data<Box<dyn NoteService>>, query: Query<AutocompleteQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_AUTOCOMPLETE_LIMIT).clamp(1, MAX_AUTOCOMPLETE_LIMIT);
+  let db_titles = note_service.title_prefix(&query.q, limit).await?;
+  let notes: Vec<NoteTitle> = db_titles.into_iter().map(NoteTitle::from).collect();
+
+  Ok(HttpResponse::Ok().json(AutocompleteResponse { notes }))
+}
+
+/// Trims the query and collapses runs of whitespace, rejecting it when still longer than `max_length` characters.
+pub(super) fn normalize_search_query(q: &str, max_length: usize) -> Result<String, ValidationErrors> {
+  let q = q.split_whitespace().collect::<Vec<_>>().join(" ");
+  if q.chars().count() <= max_length {
+    return Ok(q);
+  }
+
+  let mut error = ValidationError::new("length");
+  error.message = Some(format!("search query must be at most {} characters long", max_length).into());
+  error.add_param("max".into(), &max_length);
+  let mut errors = ValidationErrors::new();
+  errors.add("q", error);
+  Err(errors)
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes whose title or content contains the query, newest first", body = SearchNotesResponse),
+    (status = 400, description = "Search query is too long", body = ErrorResponse),
+  ),
+  params(SearchNotesQuery)
+)]
+#[get("/notes/search")]
+pub(super) async fn search_notes(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<SearchNotesQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = config.search_limit(query.limit);
+  let offset = query.offset.unwrap_or(0).max(0);
+  let q = normalize_search_query(&query.q, config.max_search_query_length)?;
+  // Ask for one extra row to find out whether there are more matches than fit in the page
+  let mut db_notes = match &query.tag {
+    Some(tag) => note_service.search_in_tag(tag, &q, limit + 1, offset).await?,
+    None => note_service.search(&q, limit + 1, offset).await?,
+  };
+  let truncated = db_notes.len() as i64 > limit;
+  db_notes.truncate(limit as usize);
+  let notes: Vec<SearchHit> = db_notes
+    .into_iter()
+    .map(|db_note| {
+      let snippet = match config.search_snippet_length {
+        0 => None,
+        length => snippet::highlight(&db_note.content, &q, length).or_else(|| snippet::highlight(&db_note.title, &q, length)),
+      };
+      SearchHit { note: Note::from(db_note), snippet }
+    })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(SearchNotesResponse { notes, truncated, limit, offset }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "ZIP archive with one Markdown file per note matching the filters", content_type = "application/zip"),
+    (status = 400, description = "Search query is too long", body = ErrorResponse),
+    (status = 413, description = "More notes match than `MAX_EXPORT_NOTES` allows in one export", body = ErrorResponse),
+  ),
+  params(NoteFilterQuery)
+)]
+#[get("/notes/export.zip")]
+pub(super) async fn export_notes_zip(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<NoteFilterQuery>) -> Result<HttpResponse, ApiError> {
+  let filter = note_filter(query.q.as_deref(), query.tag.as_deref(), &config)?;
+  if config.max_export_notes > 0 {
+    let count = match &filter {
+      Some(filter) => note_service.count_by(filter).await?,
+      None => note_service.count().await?,
+    };
+    if count > config.max_export_notes {
+      return Err(ApiError::PayloadTooLarge(format!(
+        "{} notes match, but an export holds at most {}; narrow it down with q or tag",
+        count, config.max_export_notes
+      )));
+    }
+  }
+  let db_notes = match &filter {
+    Some(filter) => note_service.all_by(filter).await?,
+    None => note_service.all().await?,
+  };
+  let db_tags = note_service.all_tags().await?;
+  let archive = export::markdown_zip(&db_notes, &db_tags)?;
+
+  Ok(HttpResponse::Ok()
+    .content_type("application/zip")
+    .insert_header(("Content-Disposition", "attachment; filename=\"notes.zip\""))
+    .body(archive))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "CSV with the columns id, title, content, created_at, updated_at and tags, one row per note created within the range", content_type = "text/csv"),
+    (status = 400, description = "Invalid date range", body = ErrorResponse),
+    (status = 413, description = "More notes were created in the range than `MAX_EXPORT_NOTES` allows in one export", body = ErrorResponse),
+  ),
+  params(ExportCsvQuery)
+)]
+#[get("/notes/export.csv")]
+pub(super) async fn export_notes_csv(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<ExportCsvQuery>) -> Result<HttpResponse, ApiError> {
+  let (from, end) = activity_range(&query.from, &query.to)?;
+  if config.max_export_notes > 0 {
+    let count = note_service.count_created_between(&from, &end).await?;
+    if count > config.max_export_notes {
+      return Err(ApiError::PayloadTooLarge(format!(
+        "{} notes were created in this range, but an export holds at most {}; narrow it down with from and to",
+        count, config.max_export_notes
+      )));
+    }
+  }
+  let db_tags = Rc::new(note_service.tags_created_between(&from, &end).await?);
+  // Rows are read and sent a batch at a time, so a large range never sits in memory at once
+  let rows = stream::try_unfold(Some(0), move |offset| {
+    let (note_service, from, end, db_tags) = (note_service.clone(), from.clone(), end.clone(), db_tags.clone());
+    async move {
+      let offset = match offset {
+        Some(offset) => offset,
+        None => return Ok(None),
+      };
+      let db_notes = note_service.created_between_paged(&from, &end, CSV_EXPORT_BATCH_SIZE, offset).await.map_err(ApiError::from)?;
+      let next = (db_notes.len() as i64 == CSV_EXPORT_BATCH_SIZE).then_some(offset + CSV_EXPORT_BATCH_SIZE);
+
+      Ok::<_, actix_web::Error>(Some((Bytes::from(export::csv_rows(&db_notes, &db_tags)), next)))
+    }
+  });
+  let header = stream::once(future::ready(Ok(Bytes::from_static(export::CSV_HEADER.as_bytes()))));
+
+  Ok(HttpResponse::Ok()
+    .content_type("text/csv; charset=utf-8")
+    .insert_header(("Content-Disposition", "attachment; filename=\"notes.csv\""))
+    .streaming(header.chain(rows)))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Most used words across all notes, stopwords excluded", body = WordFrequencyResponse),
+  ),
+  params(WordFrequencyQuery)
+)]
+#[get("/notes/word-frequency")]
+pub(super) async fn get_word_frequency(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<WordFrequencyQuery>) -> Result<HttpResponse, ApiError> {
+  let top = query.top.unwrap_or(DEFAULT_WORD_FREQUENCY_TOP).clamp(1, MAX_WORD_FREQUENCY_TOP);
+  let db_notes = note_service.all().await?;
+  let words = word_frequency::top_words(db_notes.iter().map(|note| note.content.as_str()), &config.stopwords, top)
+    .into_iter()
+    .map(|(word, count)| WordCount { word, count })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(WordFrequencyResponse { words }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Atom feed of the most recent notes", content_type = "application/atom+xml"),
+  )
+)]
+#[get("/notes/feed.xml")]
+pub(super) async fn notes_feed(note_service: Data<Box<dyn NoteService>>, config: Data<Config>) -> Result<HttpResponse, ApiError> {
+  let db_notes = note_service.recent(config.feed_size).await?;
+
+  Ok(HttpResponse::Ok()
+    .content_type("application/atom+xml")
+    .body(feed::atom(&db_notes)))
+}
+
+/// Parses an inclusive `from..=to` date range into the half-open bounds used to compare `created_at`.
+fn activity_range(from: &str, to: &str) -> Result<(String, String), ValidationErrors> {
+  let parse = |field: &'static str, value: &str| {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+      let mut error = ValidationError::new("date");
+      error.message = Some(format!("{} must be a date formatted as YYYY-MM-DD", field).into());
+      let mut errors = ValidationErrors::new();
+      errors.add(field, error);
+      errors
+    })
+  };
+  let from = parse("from", from)?;
+  let to = parse("to", to)?;
+  if from > to {
+    let mut error = ValidationError::new("range");
+    error.message = Some("from must not be after to".into());
+    let mut errors = ValidationErrors::new();
+    errors.add("from", error);
+    return Err(errors);
+  }
+
+  let end = to + Duration::days(1);
+  Ok((from.format("%Y-%m-%d").to_string(), end.format("%Y-%m-%d").to_string()))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Number of notes created per day, week or month within the range", body = ActivityResponse),
+    (status = 400, description = "Invalid date range or bucket", body = ErrorResponse),
+  ),
+  params(ActivityQuery)
+)]
+#[get("/notes/activity")]
+pub(super) async fn note_activity(note_service: Data<Box<dyn NoteService>>, query: Query<ActivityQuery>) -> Result<HttpResponse, ApiError> {
+  let (from, end) = activity_range(&query.from, &query.to)?;
+  let bucket = query.bucket.unwrap_or(ActivityBucket::Day);
+  let counts = note_service.activity(&from, &end, bucket.into()).await?
+    .into_iter()
+    .map(|count| ActivityCount { bucket: count.bucket, count: count.count })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(ActivityResponse { counts }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Number of notes created on each day of the range, zero for days without notes", body = HeatmapResponse),
+    (status = 400, description = "Invalid date range", body = ErrorResponse),
+  ),
+  params(HeatmapQuery)
+)]
+#[get("/notes/heatmap")]
+pub(super) async fn note_heatmap(note_service: Data<Box<dyn NoteService>>, query: Query<HeatmapQuery>) -> Result<HttpResponse, ApiError> {
+  let (from, end) = activity_range(&query.from, &query.to)?;
+  let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").expect("validated by activity_range");
+  if (parse(&end) - parse(&from)).num_days() > MAX_HEATMAP_DAYS {
+    let mut error = ValidationError::new("range");
+    error.message = Some(format!("the range must span at most {} days", MAX_HEATMAP_DAYS).into());
+    let mut errors = ValidationErrors::new();
+    errors.add("to", error);
+    return Err(errors.into());
+  }
+  let days = note_service.activity_heatmap(&from, &end).await?
+    .into_iter()
+    .map(|(date, count)| HeatmapDay { date, count })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(HeatmapResponse { days }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes created in a calendar month", body = ArchiveResponse),
+    (status = 400, description = "Invalid month or unknown time zone", body = ErrorResponse),
+  ),
+  params(
+    ("year", description = "Year, such as 2023"),
+    ("month", description = "Month from 1 to 12"),
+    ArchiveQuery
+  )
+)]
+#[get("/notes/archive/{year}/{month}")]
+pub(super) async fn note_archive(path: Path<(i32, u32)>, note_service: Data<Box<dyn NoteService>>, query: Query<ArchiveQuery>) -> Result<HttpResponse, ApiError> {
+  let (year, month) = path.into_inner();
+  let tz = time_zone(query.tz.as_deref())?;
+  let (from, to) = month_range(year, month, tz)?;
+  let notes = note_service.created_between(&from, &to).await?.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(ArchiveResponse { year, month, tz: tz.name().to_string(), notes }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Number of notes created on each day of the week and hour of the day", body = PatternsResponse),
+    (status = 400, description = "Unknown time zone", body = ErrorResponse),
+  ),
+  params(PatternsQuery)
+)]
+#[get("/notes/patterns")]
+pub(super) async fn note_patterns(note_service: Data<Box<dyn NoteService>>, query: Query<PatternsQuery>) -> Result<HttpResponse, ApiError> {
+  let tz = time_zone(query.tz.as_deref())?;
+  let patterns = patterns::bucket(&note_service.creation_minutes().await?, tz);
+
+  Ok(HttpResponse::Ok().json(PatternsResponse {
+    tz: tz.name().to_string(),
+    weekdays: patterns::WEEKDAYS.iter().zip(patterns.by_weekday())
+      .map(|(day, count)| WeekdayCount { day: day.to_string(), count })
+      .collect(),
+    hours: patterns.by_hour().into_iter().enumerate()
+      .map(|(hour, count)| HourCount { hour, count })
+      .collect(),
+    grid: patterns.grid.iter().map(|hours| hours.to_vec()).collect(),
+  }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note counts, optionally for a single tag", body = NoteStatsResponse),
+  ),
+  params(NoteStatsQuery)
+)]
+#[get("/notes/stats")]
+pub(super) async fn note_stats(note_service: Data<Box<dyn NoteService>>, query: Query<NoteStatsQuery>) -> Result<HttpResponse, ApiError> {
+  let total = note_service.count().await?;
+  let tagged = match &query.tag {
+    Some(tag) => Some(note_service.count_by_tag(tag).await?),
+    None => None,
+  };
+
+  Ok(HttpResponse::Ok().json(NoteStatsResponse { total, tagged }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "One page of note ids, for clients diffing against a local copy. Follow `nextCursor` until it is absent to get every id.", body = NoteIdsResponse),
+  ),
+  params(NoteIdsQuery)
+)]
+#[get("/notes/ids")]
+pub(super) async fn note_ids(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<NoteIdsQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(config.max_ids_page_size).clamp(1, config.max_ids_page_size);
+  // The cursor is the last id of the previous page. Ids never change, so pages stay stable
+  // while notes are created or deleted in between.
+  let after = query.cursor.as_deref().unwrap_or("");
+  let mut ids = note_service.ids_after(after, limit + 1).await?;
+  let next_cursor = if ids.len() as i64 > limit {
+    ids.truncate(limit as usize);
+    ids.last().cloned()
+  } else {
+    None
+  };
+
+  Ok(HttpResponse::Ok().json(NoteIdsResponse { ids, next_cursor }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note created first", body = GetNoteResponse),
+    (status = 404, description = "There are no notes", body = ErrorResponse),
+  ),
+)]
+#[get("/notes/oldest")]
+pub(super) async fn oldest_note(note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.oldest().await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  Ok(HttpResponse::Ok().json(GetNoteResponse { note: Note::from(db_note) }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note created last", body = GetNoteResponse),
+    (status = 404, description = "There are no notes", body = ErrorResponse),
+  ),
+)]
+#[get("/notes/newest")]
+pub(super) async fn newest_note(note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.newest().await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  Ok(HttpResponse::Ok().json(GetNoteResponse { note: Note::from(db_note) }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Get note", body = GetNoteResponse, example = json ! (GetNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: Some(String::from("This is note #1.")), content_length: 16, reading_time_minutes: 1, created_at: String::from("2021-01-01T00:00:00Z"), updated_at: String::from("2021-01-01T00:00:00Z"), version: 1, expires_at: None, format: NoteFormat::Markdown}})),
+    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+  ),
+  params(
+    ("id", description = "Unique id"),
+    GetNoteQuery
+  ),
+)]
+#[get("/notes/{id}")]
+pub(super) async fn get_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, query: Query<GetNoteQuery>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+  if query.track.unwrap_or(false) {
+    note_service.record_view(&db_note.id, &chrono::offset::Utc::now().naive_utc().to_string()).await?;
+  }
+  let api_note = Note::from(db_note);
+
+  Ok(HttpResponse::Ok().json(GetNoteResponse { note: api_note }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes opened with `GET /notes/{id}?track=true`, most recently viewed first", body = RecentlyViewedResponse),
+  ),
+  params(RecentlyViewedQuery)
+)]
+#[get("/notes/recently-viewed")]
+pub(super) async fn recently_viewed_notes(note_service: Data<Box<dyn NoteService>>, query: Query<RecentlyViewedQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_RECENTLY_VIEWED_LIMIT).clamp(1, MAX_RECENTLY_VIEWED_LIMIT);
+  let notes = note_service.recently_viewed(limit).await?.into_iter().map(ViewedNote::from).collect();
+
+  Ok(HttpResponse::Ok().json(RecentlyViewedResponse { notes }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes changed most often since they were created, by their version", body = MostEditedResponse),
+  ),
+  params(MostEditedQuery)
+)]
+#[get("/notes/most-edited")]
+pub(super) async fn most_edited_notes(note_service: Data<Box<dyn NoteService>>, query: Query<MostEditedQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_MOST_EDITED_LIMIT).clamp(1, MAX_MOST_EDITED_LIMIT);
+  let notes = note_service.most_edited(limit).await?.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(MostEditedResponse { notes }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes updated since they were last viewed, or never viewed at all", body = UnreadResponse),
+  ),
+  params(UnreadQuery)
+)]
+#[get("/notes/unread")]
+pub(super) async fn unread_notes(note_service: Data<Box<dyn NoteService>>, query: Query<UnreadQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_UNREAD_LIMIT).clamp(1, MAX_UNREAD_LIMIT);
+  let notes = note_service.updated_since_viewed(limit).await?.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(UnreadResponse { notes }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note content rendered from Markdown to sanitized HTML", content_type = "text/html"),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/html")]
+pub(super) async fn get_note_html(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  let html = match db_note.format.parse::<db::NoteFormat>().unwrap_or_default() {
+    db::NoteFormat::Markdown => markdown::to_safe_html(&db_note.content),
+    db::NoteFormat::Plain => markdown::plain_to_html(&db_note.content),
+    db::NoteFormat::Html => ammonia::clean(&db_note.content),
+  };
+
+  Ok(HttpResponse::Ok()
+    .content_type("text/html; charset=utf-8")
+    .body(html))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note content with its Markdown or HTML markup removed", content_type = "text/plain"),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/plaintext")]
+pub(super) async fn get_note_plaintext(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  let text = match db_note.format.parse::<db::NoteFormat>().unwrap_or_default() {
+    db::NoteFormat::Markdown => markdown::to_plain_text(&db_note.content),
+    db::NoteFormat::Plain => db_note.content,
+    db::NoteFormat::Html => markdown::html_to_plain_text(&db_note.content),
+  };
+
+  Ok(HttpResponse::Ok()
+    .content_type("text/plain; charset=utf-8")
+    .body(text))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Checksum of the note's title and content, to check a local copy is current without downloading it", body = NoteChecksumResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/checksum")]
+pub(super) async fn get_note_checksum(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  Ok(HttpResponse::Ok().json(NoteChecksumResponse {
+    checksum: checksum::sha256(&db_note.title, &db_note.content),
+    id: db_note.id,
+    algorithm: String::from("sha256"),
+    version: db_note.version,
+  }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Approximate bytes the note's title, content and tags take up in storage", body = NoteSizeResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/size")]
+pub(super) async fn get_note_size(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let size = note_service.storage_size(id.as_str()).await?;
+
+  Ok(HttpResponse::Ok().json(NoteSizeResponse {
+    id: id.as_str().to_string(),
+    title: size.title,
+    content: size.content,
+    tags: size.tags,
+    total: size.total(),
+  }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "The note and its tags as a bundle for `POST /notes/portable`", body = PortableNote),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/portable")]
+pub(super) async fn export_portable_note(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let (db_note, tags) = note_service.get_with_tags(id.as_str()).await?;
+
+  Ok(HttpResponse::Ok().json(PortableNote {
+    schema_version: PORTABLE_SCHEMA_VERSION,
+    title: db_note.title,
+    content: db_note.content,
+    format: db_note.format.parse::<db::NoteFormat>().unwrap_or_default().into(),
+    created_at: db_note.created_at,
+    expires_at: db_note.expires_at,
+    tags,
+  }))
+}
+
+#[utoipa::path(
+  request_body = PortableNote,
+  responses(
+    (status = 200, description = "Note and tags created under a new id in one transaction", body = CreateNoteResponse),
+    (status = 400, description = "Bundle not valid, or written by a newer version of the server", body = ErrorResponse),
+  )
+)]
+#[post("/notes/portable")]
+pub(super) async fn import_portable_note(note_service: Data<Box<dyn NoteService>>, portable: Json<PortableNote>) -> Result<HttpResponse, ApiError> {
+  if !(1..=PORTABLE_SCHEMA_VERSION).contains(&portable.schema_version) {
+    let mut error = ValidationError::new("schema_version");
+    error.message = Some(format!(
+      "bundle schema version {} is not supported, this server reads versions 1 to {}",
+      portable.schema_version, PORTABLE_SCHEMA_VERSION,
+    ).into());
+    let mut errors = ValidationErrors::new();
+    errors.add("schemaVersion", error);
+    return Err(ApiError::ValidationError(errors));
+  }
+  let stored = |field: &'static str, value: &str| timestamp::to_stored(value).ok_or_else(|| {
+    let mut errors = ValidationErrors::new();
+    errors.add(field, ValidationError::new("timestamp"));
+    ApiError::ValidationError(errors)
+  });
+  let new_note = db::NewNote {
+    // Generated by the service
+    id: String::new(),
+    title: portable.title.clone(),
+    content: portable.content.clone(),
+    created_at: stored("createdAt", &portable.created_at)?,
+    expires_at: portable.expires_at.as_deref().map(|expires_at| stored("expiresAt", expires_at)).transpose()?,
+    format: db::NoteFormat::from(portable.format).as_str().to_string(),
+  };
+  let (db_note, tags) = note_service.create_with_tags(&new_note, &NoteTags { tags: portable.tags.clone() }).await?;
+
+  Ok(HttpResponse::Ok().json(CreateNoteResponse { note: Note::from(db_note), tags: Some(tags) }))
+}
+
+/// Documents the title and content limits on note request bodies from the constants `db` validates
+/// them against, so the OpenAPI document can't drift from the enforced limits.
+pub(super) struct NoteLengthLimits;
+
+impl Modify for NoteLengthLimits {
+  fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+    let Some(components) = openapi.components.as_mut() else {
+      return;
+    };
+
+    for name in ["CreateNoteRequest", "UpdateNoteRequest"] {
+      if let Some(RefOr::T(Schema::Object(schema))) = components.schemas.get_mut(name) {
+        set_length_limits(schema, "title", db::TITLE_MIN_LENGTH, db::TITLE_MAX_LENGTH);
+        set_length_limits(schema, "content", db::CONTENT_MIN_LENGTH, db::CONTENT_MAX_LENGTH);
+      }
+    }
+  }
+}
+
+fn set_length_limits(schema: &mut Object, property: &str, min_length: usize, max_length: usize) {
+  if let Some(RefOr::T(Schema::Object(property))) = schema.properties.get_mut(property) {
+    property.min_length = Some(min_length);
+    property.max_length = Some(max_length);
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct TagOverlapQuery {
+  /// Comma-separated tags. Notes with at least one of them are returned.
+  tags: String,
+  /// Maximum number of notes. Clamped to the server's maximum page size.
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct TagOverlapHit {
+  note: Note,
+  /// How many of the requested tags the note carries
+  #[schema(example = 2)]
+  matched_tags: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct TagOverlapResponse {
+  /// Notes carrying the most requested tags first, then oldest first
+  notes: Vec<TagOverlapHit>,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes carrying any of the tags, ranked by how many of them they carry", body = TagOverlapResponse),
+    (status = 400, description = "No tag given", body = ErrorResponse),
+  ),
+  params(TagOverlapQuery)
+)]
+#[get("/notes/by-tags")]
+pub(super) async fn notes_by_tag_overlap(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<TagOverlapQuery>) -> Result<HttpResponse, ApiError> {
+  let mut tags: Vec<String> = Vec::new();
+  for tag in query.tags.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+    if !tags.iter().any(|seen| seen == tag) {
+      tags.push(tag.to_string());
+    }
+  }
+  if tags.is_empty() {
+    let mut error = ValidationError::new("tags");
+    error.message = Some("tags must name at least one tag".into());
+    let mut errors = ValidationErrors::new();
+    errors.add("tags", error);
+    return Err(ApiError::ValidationError(errors));
+  }
+  check_filter_tag_count(&tags, &config)?;
+  let limit = config.page_size(query.limit);
+  let notes = note_service.by_tag_overlap(&tags, limit).await?
+    .into_iter()
+    .map(|(db_note, matched_tags)| TagOverlapHit { note: Note::from(db_note), matched_tags })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(TagOverlapResponse { notes }))
+}
+
+/// Upper bound for the number of related notes.
+const MAX_RELATED_LIMIT: i64 = 20;
+const DEFAULT_RELATED_LIMIT: i64 = 5;
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct RelatedNotesQuery {
+  /// Maximum number of related notes, at most 20
+  limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct RelatedNotesResponse {
+  /// Most related first
+  notes: Vec<Note>,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes sharing tags or significant words with the note, those sharing the most tags first", body = RelatedNotesResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note"),
+    RelatedNotesQuery
+  )
+)]
+#[get("/notes/{id}/related")]
+pub(super) async fn related_notes(id: NoteId, note_service: Data<Box<dyn NoteService>>, query: Query<RelatedNotesQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_RELATED_LIMIT).clamp(1, MAX_RELATED_LIMIT);
+  let notes = note_service.related(id.as_str(), limit).await?.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(RelatedNotesResponse { notes }))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct NoteContextResponse {
+  note: Note,
+  /// Note created right before this one, or null for the first note
+  previous: Option<NoteTitle>,
+  /// Note created right after this one, or null for the last note
+  next: Option<NoteTitle>,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note with the notes created right before and after it", body = NoteContextResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/context")]
+pub(super) async fn note_context(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+  let neighbors = note_service.neighbors(id.as_str()).await?;
+
+  Ok(HttpResponse::Ok().json(NoteContextResponse {
+    note: Note::from(db_note),
+    previous: neighbors.previous.map(NoteTitle::from),
+    next: neighbors.next.map(NoteTitle::from),
+  }))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct CompareNotesResponse {
+  /// Share of distinct words found in both contents, from 0 for none to 1 for the same words
+  #[schema(example = 0.75)]
+  similarity: f64,
+  /// Unified diff from the content of the first note to the second, empty when they are equal
+  #[schema(example = "--- 14322988-32fe-447c-ac38-06fb6c699b4a\n+++ 5e0b1f4c-7d3a-4f6e-9a63-0c2d8e1f7b21\n@@ -1 +1 @@\n-Buy milk\n+Buy oat milk\n")]
+  diff: String,
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "How similar the contents of two notes are, for deciding whether to merge duplicates", body = CompareNotesResponse),
+    (status = 400, description = "Either id has an invalid format, while strict id validation is enabled", body = ErrorResponse),
+    (status = 404, description = "Either note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of the first Note"),
+    ("other_id", description = "Unique storage id of the Note to compare it with")
+  )
+)]
+#[get("/notes/{id}/compare/{other_id}")]
+pub(super) async fn compare_notes(id: NoteId, ids: Path<(String, String)>, note_service: Data<Box<dyn NoteService>>, config: Data<Config>) -> Result<HttpResponse, ApiError> {
+  let (_, other_id) = ids.into_inner();
+  note_id::validate(&config, &other_id)?;
+  let note = note_service.try_get(id.as_str()).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+  let other = note_service.try_get(&other_id).await?.ok_or(ApiError::DbError(DbError::NotFound))?;
+
+  Ok(HttpResponse::Ok().json(CompareNotesResponse {
+    similarity: compare::similarity(&note.content, &other.content),
+    diff: compare::unified_diff(&note.content, &other.content, &note.id, &other.id),
+  }))
+}
+
+#[utoipa::path(
+  request_body = CreateNoteRequest,
+  responses(
+    (status = 201, description = "Note created successfully", body = CreateNoteResponse, example = json ! (CreateNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: Some(String::from("This is note #1.")), content_length: 16, reading_time_minutes: 1, created_at: String::from("2021-01-01T00:00:00Z"), updated_at: String::from("2021-01-01T00:00:00Z"), version: 1, expires_at: None, format: NoteFormat::Markdown}, tags: None})),
+    (status = 400, description = "Note not valid", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("body not valid"), error: String::from("title too long")})),
+    (status = 409, description = "Another note has the same content, while unique content is enforced, or the given id is taken", body = DuplicateContentResponse),
+  )
+)]
+#[post("/notes")]
+pub(super) async fn create_note(note_service: Data<Box<dyn NoteService>>, config: Data<Config>, create_note: Json<CreateNoteRequest>) -> Result<HttpResponse, ApiError> {
+  let expires_at = match &create_note.expires_at {
+    Some(expires_at) => Some(timestamp::to_stored(expires_at).ok_or_else(|| {
+      let mut errors = ValidationErrors::new();
+      errors.add("expiresAt", ValidationError::new("timestamp"));
+      ApiError::ValidationError(errors)
+    })?),
+    None => None,
+  };
+  let id = match &create_note.id {
+    Some(id) => client_note_id(&config, id)?,
+    // Generated by the service, which retries with a fresh id should it be taken
+    None => String::new(),
+  };
+  let new_note = db::NewNote {
+    id,
+    title: create_note.title.clone(),
+    content: create_note.content.clone(),
+    created_at: chrono::offset::Utc::now().naive_utc().to_string(),
+    expires_at,
+    format: db::NoteFormat::from(create_note.format).as_str().to_string(),
+  };
+  let (db_note, tags) = match &create_note.tags {
+    Some(tags) => {
+      let (db_note, tags) = note_service.create_with_tags(&new_note, &NoteTags { tags: tags.clone() }).await?;
+      (db_note, Some(tags))
+    }
+    None => (note_service.create(&new_note).await?, None),
+  };
+  let mut response = HttpResponse::Ok();
+  add_content_warning(&mut response, &db_note.content, &config);
+  let api_note = Note::from(db_note);
+
+  Ok(response.json(CreateNoteResponse { note: api_note, tags }))
+}
+
+/// Checks an id supplied by the client against `Config::note_id_pattern`. A taken id is left to the
+/// unique key on `note.id`, which fails the create with `DbError::IdTaken`.
+fn client_note_id(config: &Config, id: &str) -> Result<String, ApiError> {
+  if config.note_id_pattern.as_ref().map_or(true, |pattern| pattern.is_match(id)) {
+    return Ok(id.to_string());
+  }
+
+  let mut error = ValidationError::new("format");
+  error.message = Some("id has an invalid format".into());
+  let mut errors = ValidationErrors::new();
+  errors.add("id", error);
+  Err(ApiError::ValidationError(errors))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note updated successfully", body = UpdateNoteResponse, example = json ! (UpdateNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: Some(String::from("This is note #1")), content_length: 15, reading_time_minutes: 1, created_at: String::from("2021-01-01T00:00:00Z"), updated_at: String::from("2021-01-01T00:00:00Z"), version: 1, expires_at: None, format: NoteFormat::Markdown}})),
+    (status = 400, description = "Note not valid", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("body not valid"), error: String::from("title too long")})),
+    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+    (status = 409, description = "Note changed since the given version", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("Conflict"), error: String::from("note was modified concurrently")})),
+  ),
+  params(
+    ("id", description = "Unique id"),
+  ),
+)]
+#[put("/notes/{id}")]
+pub(super) async fn put_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, config: Data<Config>, update_note: Json<UpdateNoteRequest>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service
+    .update(
+      id.as_str(),
+      &UpdateNote {
+        title: update_note.title.clone(),
+        content: update_note.content.clone(),
+        updated_at: chrono::offset::Utc::now().naive_utc().to_string(),
+        version: update_note.version,
+      },
+    )
+    .await?;
+  let mut response = HttpResponse::Ok();
+  add_content_warning(&mut response, &db_note.content, &config);
+  let api_note = Note::from(db_note);
+
+  Ok(response.json(UpdateNoteResponse { note: api_note }))
+}
+
+/// Adds a `Warning` header when `content` is longer than `Config::content_warning_length`.
+fn add_content_warning(response: &mut HttpResponseBuilder, content: &str, config: &Config) {
+  let length = content.chars().count();
+  if config.content_warning_length > 0 && length > config.content_warning_length {
+    response.insert_header((
+      WARNING,
+      format!("299 - \"content is {} characters long, over the recommended {}\"", length, config.content_warning_length),
+    ));
+  }
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note deleted successfully, returned with `return=true`", body = DeleteNoteResponse),
+    (status = 204, description = "Note deleted successfully"),
+    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+  ),
+  params(
+    ("id", description = "Unique id"),
+    DeleteNoteQuery
+  ),
+)]
+#[delete("/notes/{id}")]
+pub(super) async fn delete_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, query: Query<DeleteNoteQuery>) -> Result<HttpResponse, ApiError> {
+  let db_note = note_service.delete(id.as_str()).await?;
+
+  if query.return_note.unwrap_or(false) {
+    Ok(HttpResponse::Ok().json(DeleteNoteResponse { note: Note::from(db_note) }))
+  } else {
+    Ok(HttpResponse::NoContent().finish())
+  }
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Note touched successfully", body = TouchNoteResponse),
+    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+  ),
+  params(
+    ("id", description = "Unique id"),
+  ),
+)]
+#[post("/notes/{id}/touch")]
+pub(super) async fn touch_note(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let db_note = note_service.touch(id.as_str(), &updated_at).await?;
+  let api_note = Note::from(db_note);
+
+  Ok(HttpResponse::Ok().json(TouchNoteResponse { note: api_note }))
+}
+
+#[utoipa::path(
+  request_body = AppendNoteRequest,
+  responses(
+    (status = 200, description = "Text appended to the note's content", body = UpdateNoteResponse),
+    (status = 400, description = "Empty text, or the content would exceed its maximum length", body = ErrorResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[post("/notes/{id}/append")]
+pub(super) async fn append_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, append_note: Json<AppendNoteRequest>) -> Result<HttpResponse, ApiError> {
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let db_note = note_service.append(id.as_str(), &append_note.text, &updated_at).await?;
+  let api_note = Note::from(db_note);
+
+  Ok(HttpResponse::Ok().json(UpdateNoteResponse { note: api_note }))
+}
+
+#[utoipa::path(
+  request_body = AppendNoteRequest,
+  responses(
+    (status = 200, description = "Text added to the start of the note's content", body = UpdateNoteResponse),
+    (status = 400, description = "The content would exceed its maximum length", body = ErrorResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+    (status = 409, description = "The note was modified concurrently", body = ErrorResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[post("/notes/{id}/prepend")]
+pub(super) async fn prepend_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, prepend_note: Json<AppendNoteRequest>) -> Result<HttpResponse, ApiError> {
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let db_note = note_service.insert(id.as_str(), 0, &prepend_note.text, &updated_at).await?;
+  let api_note = Note::from(db_note);
+
+  Ok(HttpResponse::Ok().json(UpdateNoteResponse { note: api_note }))
+}
+
+#[utoipa::path(
+  request_body = AppendNoteRequest,
+  responses(
+    (status = 200, description = "Text inserted into the note's content", body = UpdateNoteResponse),
+    (status = 400, description = "Offset out of range, or the content would exceed its maximum length", body = ErrorResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+    (status = 409, description = "The note was modified concurrently", body = ErrorResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note"),
+    InsertNoteQuery
+  )
+)]
+#[post("/notes/{id}/insert")]
+pub(super) async fn insert_note(id: NoteId, note_service: Data<Box<dyn NoteService>>, query: Query<InsertNoteQuery>, insert_note: Json<AppendNoteRequest>) -> Result<HttpResponse, ApiError> {
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let db_note = note_service.insert(id.as_str(), query.offset, &insert_note.text, &updated_at).await?;
+  let api_note = Note::from(db_note);
+
+  Ok(HttpResponse::Ok().json(UpdateNoteResponse { note: api_note }))
+}
+
+#[utoipa::path(
+  request_body = TouchNotesRequest,
+  responses(
+    (status = 200, description = "Notes touched successfully", body = TouchNotesResponse),
+    (status = 404, description = "One of the notes was not found; none were touched", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+  )
+)]
+#[post("/notes/touch")]
+pub(super) async fn touch_notes(note_service: Data<Box<dyn NoteService>>, touch_notes: Json<TouchNotesRequest>) -> Result<HttpResponse, ApiError> {
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let db_notes = note_service.touch_many(&touch_notes.ids, &updated_at).await?;
+  let api_notes: Vec<Note> = db_notes.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(TouchNotesResponse { notes: api_notes }))
+}
+
+#[utoipa::path(
+  request_body = FindReplaceRequest,
+  responses(
+    (status = 200, description = "Every match was replaced in one transaction", body = FindReplaceResponse),
+    (status = 400, description = "Invalid find pattern, or a replaced content is not valid; nothing was changed", body = ErrorResponse),
+    (status = 409, description = "A note changed while replacing; nothing was changed", body = ErrorResponse),
+  )
+)]
+#[post("/notes/find-replace")]
+pub(super) async fn find_replace_notes(note_service: Data<Box<dyn NoteService>>, request: Json<FindReplaceRequest>) -> Result<HttpResponse, ApiError> {
+  let find_replace = FindReplace::new(&request.find, &request.replace, request.regex)?;
+  let updated_at = chrono::offset::Utc::now().naive_utc().to_string();
+  let updates: Vec<(String, UpdateNote)> = note_service.all().await?
+    .into_iter()
+    .filter_map(|note| {
+      let content = find_replace.apply(&note.content)?;
+      Some((note.id, UpdateNote { title: note.title, content, updated_at: updated_at.clone(), version: Some(note.version) }))
+    })
+    .collect();
+  if !request.dry_run && !updates.is_empty() {
+    note_service.update_many(&updates).await?;
+  }
+  let ids: Vec<String> = updates.into_iter().map(|(id, _)| id).collect();
+
+  Ok(HttpResponse::Ok().json(FindReplaceResponse { changed: ids.len(), ids, dry_run: request.dry_run }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Tags of the note, without the note itself", body = NoteTagsResponse),
+    (status = 404, description = "Note not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Unique storage id of Note")
+  )
+)]
+#[get("/notes/{id}/tags")]
+pub(super) async fn get_note_tags(id: NoteId, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let (_, tags) = note_service.get_with_tags(id.as_str()).await?;
+
+  Ok(HttpResponse::Ok().json(NoteTagsResponse { tags }))
+}
+
+#[utoipa::path(
+  request_body(content = Vec<String>, description = "Complete set of tags for the note", example = json!(["todo", "work"])),
+  responses(
+    (status = 200, description = "Tags replaced successfully", body = NoteTagsResponse),
+    (status = 400, description = "A tag is not valid", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("Validation failed"), error: String::from("tag too long")})),
+    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
+  ),
+  params(
+    ("id", description = "Unique id"),
+  ),
+)]
+#[put("/notes/{id}/tags")]
+pub(super) async fn put_note_tags(id: NoteId, note_service: Data<Box<dyn NoteService>>, tags: Json<Vec<String>>) -> Result<HttpResponse, ApiError> {
+  let tags = note_service.replace_tags(id.as_str(), &NoteTags { tags: tags.into_inner() }).await?;
+
+  Ok(HttpResponse::Ok().json(NoteTagsResponse { tags }))
+}
+
+#[utoipa::path(
+  request_body = RenameTagRequest,
+  responses(
+    (status = 200, description = "Tag renamed on every note, merging with the new tag where a note already had it", body = RenameTagResponse),
+    (status = 400, description = "Invalid tag", body = ErrorResponse),
+  )
+)]
+#[post("/tags/rename")]
+pub(super) async fn rename_tag(note_service: Data<Box<dyn NoteService>>, rename: Json<RenameTagRequest>) -> Result<HttpResponse, ApiError> {
+  let note_ids = note_service.rename_tag(&rename.from, &rename.to).await?;
+
+  Ok(HttpResponse::Ok().json(RenameTagResponse { note_ids }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Colors set for tags", body = TagColorsResponse),
+  )
+)]
+#[get("/tags/colors")]
+pub(super) async fn tag_colors(note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let colors = note_service.tag_colors().await?.into_iter().map(|tag_color| (tag_color.tag, tag_color.color)).collect();
+
+  Ok(HttpResponse::Ok().json(TagColorsResponse { colors }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Pairs of tags found on the same notes, with how many notes carry both", body = TagCoOccurrenceResponse),
+  ),
+  params(TagCoOccurrenceQuery)
+)]
+#[get("/tags/co-occurrence")]
+pub(super) async fn tag_co_occurrence(note_service: Data<Box<dyn NoteService>>, query: Query<TagCoOccurrenceQuery>) -> Result<HttpResponse, ApiError> {
+  let limit = query.limit.unwrap_or(DEFAULT_TAG_PAIRS).clamp(1, MAX_TAG_PAIRS);
+  let pairs = note_service.tag_co_occurrence(limit).await?.into_iter().map(TagPair::from).collect();
+
+  Ok(HttpResponse::Ok().json(TagCoOccurrenceResponse { pairs }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "How many notes with the tag were created each month, to see which tags are used more lately", body = TagTrendResponse),
+  ),
+  params(
+    ("tag", description = "Tag name"),
+  ),
+)]
+#[get("/tags/{tag}/trend")]
+pub(super) async fn tag_trend(tag: Path<String>, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  let months = note_service
+    .tag_trend(&tag)
+    .await?
+    .into_iter()
+    .map(|count| ActivityCount { bucket: count.bucket, count: count.count })
+    .collect();
+
+  Ok(HttpResponse::Ok().json(TagTrendResponse { tag: tag.into_inner(), months }))
+}
+
+#[utoipa::path(
+  request_body = SetTagColorRequest,
+  responses(
+    (status = 200, description = "Color set, with the colors of all tags", body = TagColorsResponse),
+    (status = 400, description = "Invalid tag or color", body = ErrorResponse),
+  ),
+  params(
+    ("tag", description = "Tag name, which doesn't have to be on any note yet"),
+  ),
+)]
+#[put("/tags/{tag}/color")]
+pub(super) async fn put_tag_color(tag: Path<String>, note_service: Data<Box<dyn NoteService>>, request: Json<SetTagColorRequest>) -> Result<HttpResponse, ApiError> {
+  note_service.set_tag_color(&tag, &request.color).await?;
+  let colors = note_service.tag_colors().await?.into_iter().map(|tag_color| (tag_color.tag, tag_color.color)).collect();
+
+  Ok(HttpResponse::Ok().json(TagColorsResponse { colors }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Color removed, with the colors of the remaining tags", body = TagColorsResponse),
+    (status = 404, description = "Tag has no color", body = MessageResponse),
+  ),
+  params(
+    ("tag", description = "Tag name"),
+  ),
+)]
+#[delete("/tags/{tag}/color")]
+pub(super) async fn delete_tag_color(tag: Path<String>, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
+  if !note_service.remove_tag_color(&tag).await? {
+    return Err(ApiError::DbError(DbError::NotFound));
+  }
+  let colors = note_service.tag_colors().await?.into_iter().map(|tag_color| (tag_color.tag, tag_color.color)).collect();
+
+  Ok(HttpResponse::Ok().json(TagColorsResponse { colors }))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test;
+  use super::*;
+  use crate::mocks::MockService;
+  use actix_web::App;
+  use mockall::predicate;
+
+  #[actix_web::test]
+  async fn test_list_notes() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_page()
+      .with(predicate::eq(50), predicate::eq(0))
+      .times(1)
+      .returning(|_, _| Ok((vec![db::Note {
+        id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }], 1)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+  }
+
+  #[actix_web::test]
+  async fn test_list_notes_envelope() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_page()
+      .times(2)
+      .returning(|_, _| Ok((vec![db::Note {
+        id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }], 7)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes").to_request();
+    let body: ListNotesResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.notes.len(), 1);
+    assert_eq!(body.total, 7);
+
+    let req = test::TestRequest::get().uri("/notes?envelope=false").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.headers().get(TOTAL_COUNT_HEADER).unwrap(), "7");
+    let body: Vec<Note> = test::read_body_json(resp).await;
+    assert_eq!(body.len(), 1);
+    assert_eq!(body[0].id, "14322988-32fe-447c-ac38-06fb6c699b4a");
+  }
+
+  #[actix_web::test]
+  async fn test_list_notes_clamps_limit() {
+    let mut mock_service = MockService::new();
+    let config = Config::default();
+    let max_page_size = config.max_page_size;
+
+    mock_service.expect_page()
+      .with(predicate::eq(max_page_size), predicate::eq(0))
+      .times(2)
+      .returning(|limit, _| Ok(((0..limit).map(|i| db::Note {
+        id: format!("id-{}", i),
+        title: format!("Note {}", i),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect(), 10000)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes?limit=10000").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(RESULT_TRUNCATED_HEADER).unwrap(), "true");
+
+    let body = test::read_body(resp).await;
+    let returned: ListNotesResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.limit, max_page_size);
+    assert!(returned.notes.len() as i64 <= max_page_size);
+    assert_eq!(returned.total, 10000);
+
+    let req = test::TestRequest::get().uri(&format!("/notes?limit={}", max_page_size)).to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get(RESULT_TRUNCATED_HEADER).is_none());
+  }
+
+  #[actix_web::test]
+  async fn test_filtered_count_matches_list() {
+    let mut mock_service = MockService::new();
+
+    let is_work_filter = |filter: &db::SearchFilter| filter.query == "plan" && filter.tag.as_deref() == Some("work") && filter.sort == SearchSort::Oldest;
+    mock_service.expect_search_by()
+      .withf(move |filter, _, _| is_work_filter(filter))
+      .times(1)
+      .returning(|_, _, _| Ok((1..=2).map(|i| db::Note {
+        id: format!("id-{}", i),
+        title: format!("Plan {}", i),
+        content: String::from("A plan."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+    mock_service.expect_count_by()
+      .withf(move |filter| is_work_filter(filter))
+      .times(2)
+      .returning(|_| Ok(2));
+    mock_service.expect_count().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes?q=%20plan%20&tag=work").to_request();
+    let list: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+
+    let req = test::TestRequest::get().uri("/notes/count?q=plan&tag=work").to_request();
+    let count: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(list["notes"].as_array().unwrap().len(), 2);
+    assert_eq!(list["total"], count["count"]);
+  }
+
+  #[actix_web::test]
+  async fn test_list_notes_by_tag_set() {
+    let mut mock_service = MockService::new();
+
+    let is_tag_set = |tags: &[String]| tags == ["work", "home"];
+    mock_service.expect_by_tags()
+      .withf(move |tags, matching, _, _| is_tag_set(tags) && *matching == db::TagMatch::All)
+      .times(1)
+      .returning(|_, _, _, _| Ok(vec![]));
+    mock_service.expect_by_tags()
+      .withf(move |tags, matching, _, _| is_tag_set(tags) && *matching == db::TagMatch::Any)
+      .times(1)
+      .returning(|_, _, _, _| Ok(vec![]));
+    mock_service.expect_count_by_tags()
+      .times(2)
+      .returning(|_, _| Ok(0));
+    mock_service.expect_search_by().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes?tags=work,%20home").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get().uri("/notes?tags=work,home&match=any").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let req = test::TestRequest::get().uri("/notes?tags=work&tag=home").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_too_many_filter_tags() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_by_tags().times(0);
+    mock_service.expect_by_tag_overlap().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let config = Config { max_filter_tags: 2, ..Config::default() };
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    for uri in ["/notes?tags=a,b,c", "/notes/by-tags?tags=a,b,c"] {
+      let req = test::TestRequest::get().uri(uri).to_request();
+      let resp = test::call_service(&mut app, req).await;
+      assert_eq!(resp.status(), 400, "Expected {} to be rejected", uri);
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_list_notes_empty() {
+    for (empty_list_no_content, status) in [(false, 200), (true, 204)] {
+      let mut mock_service = MockService::new();
+
+      mock_service.expect_page()
+        .times(1)
+        .returning(|_, _| Ok((vec![], 0)));
+
+      let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+      let config = Config { empty_list_no_content, ..Config::default() };
+
+      let mut app = test::init_service(
+        App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+      ).await;
+
+      let req = test::TestRequest::get().uri("/notes").to_request();
+      let resp = test::call_service(&mut app, req).await;
+      assert_eq!(resp.status(), status);
+
+      let body = test::read_body(resp).await;
+      if empty_list_no_content {
+        assert!(body.is_empty());
+      } else {
+        let returned: ListNotesResponse = serde_json::from_slice(&body).unwrap();
+        assert!(returned.notes.is_empty());
+        assert_eq!(returned.total, 0);
+      }
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_list_notes_preview_only() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_page()
+      .times(2)
+      .returning(|_, _| Ok((vec![db::Note {
+        id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"),
+        title: String::from("Note 1"),
+        content: String::from("Caf\u{e9}"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }], 1)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes?preview_only=true").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    let note = &body["notes"][0];
+    assert!(note.get("content").is_none());
+    assert_eq!(note["contentLength"], 5);
+
+    let req = test::TestRequest::get().uri("/notes").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    let note = &body["notes"][0];
+    assert_eq!(note["content"], "Caf\u{e9}");
+    assert_eq!(note["contentLength"], 5);
+  }
+
+  // #[actix_web::test]
+  // async fn test_get_note() {
+
+  // }
+
+  #[actix_web::test]
+  async fn test_create_note() {
+    let mut mock_service = MockService::new();
+
+    let new_note = db::NewNote {
+      id: String::from("new-id"),
+      title: String::from("Note 1"),
+      content: String::from("This is note #1."),
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+    let new_note_test = new_note.clone();
+    mock_service.expect_create()
+      .times(1)
+      .returning(move |_| Ok(db::Note {
+        id: String::from("new-id"),
+        title: new_note_test.title.clone(),
+        content: new_note_test.content.clone(),
+        created_at: new_note_test.created_at.clone(),
+        updated_at: new_note_test.created_at.clone(),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let note = CreateNoteRequest {
+      title: "Note 1".to_string(),
+      content: "This is note #1.".to_string(),
+      tags: None,
+      expires_at: None,
+      format: NoteFormat::Markdown,
+      id: None,
+    };
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(&note)
+      .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+
+    let returned_note: CreateNoteResponse = serde_json::from_slice(&body).unwrap();
+
+    let expected_note = db::Note {
+      id: String::from("new-id"),
+      title: String::from("Note 1"),
+      content: String::from("This is note #1."),
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      updated_at: String::from("2021-01-01T00:00:00Z"),
+      version: 1,
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+    assert_eq!(returned_note.note.id, expected_note.id);
+    assert_eq!(returned_note.note.title, expected_note.title);
+    assert_eq!(returned_note.note.content, Some(expected_note.content));
+    assert_eq!(returned_note.note.created_at, expected_note.created_at);
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_client_id() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get().times(0);
+    mock_service.expect_create()
+      .withf(|note| note.id == "14322988-32fe-447c-ac38-06fb6c699b4a")
+      .times(1)
+      .returning(|note| Ok(db::Note {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        created_at: note.created_at.clone(),
+        updated_at: note.created_at.clone(),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"id": "14322988-32fe-447c-ac38-06fb6c699b4a", "title": "Note 1", "content": "This is note #1."}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    let returned_note: CreateNoteResponse = test::read_body_json(resp).await;
+    assert_eq!(returned_note.note.id, "14322988-32fe-447c-ac38-06fb6c699b4a");
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_rejects_invalid_client_id() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_create().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"id": "../not-a-uuid", "title": "Note 1", "content": "This is note #1."}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 400);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("invalid format"));
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_taken_client_id() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get().times(0);
+    mock_service.expect_create()
+      .times(1)
+      .returning(|_| Err(anyhow::anyhow!(db::DbError::IdTaken)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"id": "14322988-32fe-447c-ac38-06fb6c699b4a", "title": "Note 1", "content": "This is note #1."}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 409);
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("a note with this id already exists"));
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_expiry() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_create()
+      .withf(|note| note.expires_at.as_deref() == Some("2021-01-08 00:00:00"))
+      .times(2)
+      .returning(|note| Ok(db::Note {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        created_at: note.created_at.clone(),
+        updated_at: note.created_at.clone(),
+        version: 1,
+        expires_at: note.expires_at.clone(),
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    for expires_at in [serde_json::json!("2021-01-08T00:00:00Z"), serde_json::json!(1610064000000i64)] {
+      let req = test::TestRequest::post()
+        .uri("/notes")
+        .set_json(serde_json::json!({"title": "Ephemeral", "content": "Gone soon.", "expiresAt": expires_at}))
+        .to_request();
+      let resp = test::call_service(&mut app, req).await;
+      assert!(resp.status().is_success());
+
+      let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+      assert_eq!(body["note"]["expiresAt"], "2021-01-08T00:00:00Z");
+    }
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"title": "Ephemeral", "content": "Gone soon.", "expiresAt": "next week"}))
+      .to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_long_content_warns() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_create()
+      .times(2)
+      .returning(|note| Ok(db::Note {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        created_at: note.created_at.clone(),
+        updated_at: note.created_at.clone(),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let config = Config { content_warning_length: 100, ..Config::default() };
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"title": "Long note", "content": "x".repeat(150)}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get(WARNING).unwrap(), "299 - \"content is 150 characters long, over the recommended 100\"");
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"title": "Short note", "content": "x".repeat(100)}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert!(resp.headers().get(WARNING).is_none());
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_requires_json_content_type() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_create().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .insert_header(("Content-Type", "text/plain"))
+      .set_payload(r#"{"title": "Note 1", "content": "This is note #1."}"#)
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 415);
+
+    let body = test::read_body(resp).await;
+    let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.message, "Unsupported media type");
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_rejects_invalid_utf8() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_create().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .insert_header(("Content-Type", "application/json"))
+      .set_payload(&b"{\"title\": \"Note 1\", \"content\": \"\xff\xfe\"}"[..])
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 400);
+
+    let body = test::read_body(resp).await;
+    let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(error.message, "Request body must be valid UTF-8");
+  }
+
+  #[actix_web::test]
+  async fn test_update_note() {
+    let mut mock_service = MockService::new();
+
+    let note_id = "some-id";
+    let update_request = UpdateNoteRequest {
+      title: "Updated Title".to_string(),
+      content: "Updated content".to_string(),
+      version: Some(1),
+    };
+    let updated_note_test = update_request.clone();
+    let updated_note_test_predicate = update_request.clone();
+    mock_service.expect_update()
+      .with(predicate::eq(note_id), predicate::function(move |note: &db::UpdateNote| {
+        note.title == updated_note_test_predicate.title
+          && note.content == updated_note_test_predicate.content
+          && note.version == updated_note_test_predicate.version
+      }))
+      .times(1)
+      .returning(move |_, _| Ok(db::Note {
+        id: note_id.clone().to_string(),
+        title: updated_note_test.title.clone(),
+        content: updated_note_test.content.clone(),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-02T00:00:00Z"),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::put()
+      .uri(&format!("/notes/{}", note_id))
+      .set_json(&update_request)
+      .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned_note: UpdateNoteResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned_note.note.title, update_request.title);
+    assert_eq!(returned_note.note.content, Some(update_request.content));
+    assert_eq!(returned_note.note.version, 2);
+  }
+
+  #[actix_web::test]
+  async fn test_update_note_deleted_concurrently() {
+    let mut mock_service = MockService::new();
+
+    // The note is deleted between the client's read and its update
+    mock_service.expect_delete()
+      .times(1)
+      .returning(|id| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+    mock_service.expect_update()
+      .times(2)
+      .returning(|_, _| Err(anyhow::anyhow!(db::DbError::NotFound)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::delete().uri("/notes/some-id").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 204);
+
+    // With or without a version the outcome is the same 404
+    for version in [Some(1), None] {
+      let update_request = UpdateNoteRequest {
+        title: "Updated Title".to_string(),
+        content: "Updated content".to_string(),
+        version,
+      };
+      let req = test::TestRequest::put()
+        .uri("/notes/some-id")
+        .set_json(&update_request)
+        .to_request();
+      let resp = test::call_service(&mut app, req).await;
+      assert_eq!(resp.status(), 404);
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_update_note_version_conflict() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_update()
+      .times(1)
+      .returning(|_, _| Err(anyhow::anyhow!(db::DbError::Conflict)));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let update_request = UpdateNoteRequest {
+      title: "Updated Title".to_string(),
+      content: "Updated content".to_string(),
+      version: Some(1),
+    };
+    let req = test::TestRequest::put()
+      .uri("/notes/some-id")
+      .set_json(&update_request)
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 409);
+  }
+
+  #[actix_web::test]
+  async fn test_delete_note() {
+    let mut mock_service = MockService::new();
+
+    let note_id = "some-id";
+    mock_service.expect_delete()
+      .with(predicate::eq(note_id))
+      .times(1)
+      .returning(|_| Ok(db::Note {
+        id: note_id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::delete()
+      .uri(&format!("/notes/{}", note_id))
+      .to_request();
+
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.status(), 204);
+  }
+
+  #[actix_web::test]
+  async fn test_delete_note_returning_note() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_delete()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|id| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::delete().uri("/notes/some-id?return=true").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 200);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["note"]["id"], "some-id");
+    assert_eq!(body["note"]["content"], "This is note #1.");
+  }
+
+  #[actix_web::test]
+  async fn test_delete_note_not_found() {
+      let mut mock_service = MockService::new();
+
+      // Set up the mock to return an error of type db::NotFound
+      mock_service.expect_delete()
+          .times(1)
+          .returning(|_| Err(anyhow::anyhow!(db::DbError::NotFound)));
+
+      let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+      let mut app = test::init_service(
+          App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+      ).await;
+
+      let req = test::TestRequest::delete().uri("/notes/some_id").to_request();
+      let resp = test::call_service(&mut app, req).await;
+
+      assert_eq!(resp.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_touch_note() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_touch()
+      .with(predicate::eq("some-id"), predicate::always())
+      .times(1)
+      .returning(|id, updated_at| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: updated_at.to_string(),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post().uri("/notes/some-id/touch").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned_note: TouchNoteResponse = serde_json::from_slice(&body).unwrap();
+
+    assert!(returned_note.note.updated_at > returned_note.note.created_at);
+    assert_eq!(returned_note.note.content.as_deref(), Some("This is note #1."));
+    assert_eq!(returned_note.note.version, 2);
+  }
+
+  #[actix_web::test]
+  async fn test_touch_notes() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_touch_many()
+      .withf(|ids, _| ids.iter().map(String::as_str).eq(["id-1", "id-2"]))
+      .times(1)
+      .returning(|ids, updated_at| Ok(ids.iter().map(|id| db::Note {
+        id: id.clone(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: updated_at.to_string(),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes/touch")
+      .set_json(&TouchNotesRequest { ids: vec![String::from("id-1"), String::from("id-2")] })
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: TouchNotesResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.notes.len(), 2);
+    assert!(returned.notes.iter().all(|note| note.updated_at > note.created_at && note.content.as_deref() == Some("This is note #1.")));
+  }
+
+  #[actix_web::test]
+  async fn test_autocomplete_notes() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_title_prefix()
+      .with(predicate::eq("Sho"), predicate::eq(MAX_AUTOCOMPLETE_LIMIT))
+      .times(1)
+      .returning(|_, _| Ok(vec![db::NoteTitle {
+        id: String::from("some-id"),
+        title: String::from("Shopping list"),
+      }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/autocomplete?q=Sho&limit=1000").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: AutocompleteResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.notes.len(), 1);
+    assert_eq!(returned.notes[0].title, "Shopping list");
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_is_capped() {
+    let mut mock_service = MockService::new();
+    let config = Config {
+      max_search_results: 3,
+      ..Config::default()
+    };
+
+    mock_service.expect_search()
+      .with(predicate::eq("note"), predicate::eq(4), predicate::eq(0))
+      .times(1)
+      .returning(|_, limit, _| Ok((0..limit).map(|i| db::Note {
+        id: format!("id-{}", i),
+        title: format!("Note {}", i),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=note&limit=50").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: SearchNotesResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.notes.len(), 3);
+    assert_eq!(returned.limit, 3);
+    assert!(returned.truncated);
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_not_truncated() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_search()
+      .times(1)
+      .returning(|_, _, _| Ok(vec![]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=nothing").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    let body = test::read_body(resp).await;
+    let returned: SearchNotesResponse = serde_json::from_slice(&body).unwrap();
+
+    assert!(returned.notes.is_empty());
+    assert!(!returned.truncated);
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_in_tag() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_search_in_tag()
+      .with(predicate::eq("work"), predicate::eq("plan"), predicate::always(), predicate::eq(0))
+      .times(1)
+      .returning(|_, _, _, _| Ok(vec![]));
+    mock_service.expect_search().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=plan&tag=work").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_snippets() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_search()
+      .times(1)
+      .returning(|_, _, _| Ok(vec![db::Note {
+        id: String::from("id-1"),
+        title: String::from("Groceries"),
+        content: String::from("Buy milk and bread."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=Milk").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(body["notes"][0]["id"], "id-1");
+    assert_eq!(body["notes"][0]["snippet"], "Buy **milk** and bread.");
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_tags() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_get_with_tags()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|id| Ok((db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01 00:00:00"),
+        updated_at: String::from("2021-01-01 00:00:00"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }, vec![String::from("todo"), String::from("work")])));
+    mock_service.expect_get_with_tags()
+      .with(predicate::eq("missing"))
+      .times(1)
+      .returning(|_| Err(DbError::NotFound.into()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/tags").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(body, serde_json::json!({"tags": ["todo", "work"]}));
+
+    let req = test::TestRequest::get().uri("/notes/missing/tags").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_put_note_tags() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_replace_tags()
+      .withf(|id, tags| id == "some-id" && tags.tags == [String::from("x")])
+      .times(1)
+      .returning(|_, tags| Ok(tags.tags.clone()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::put()
+      .uri("/notes/some-id/tags")
+      .set_json(vec!["x"])
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: NoteTagsResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.tags, vec!["x"]);
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_zip() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_count()
+      .times(1)
+      .returning(|| Ok(3));
+    mock_service.expect_all()
+      .times(1)
+      .returning(|| Ok(["Groceries", "Groceries", "Plans: 2024/Q1"].iter().enumerate().map(|(i, title)| db::Note {
+        id: format!("id-{}", i),
+        title: title.to_string(),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+    mock_service.expect_all_tags()
+      .times(1)
+      .returning(|| Ok(vec![db::NoteTag { note_id: String::from("id-0"), tag: String::from("food") }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.zip").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/zip");
+
+    let body = test::read_body(resp).await;
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    let names: Vec<String> = archive.file_names().map(String::from).collect();
+
+    assert_eq!(archive.len(), 3);
+    assert!(names.contains(&String::from("groceries.md")));
+    assert!(names.contains(&String::from("groceries-2.md")));
+    assert!(names.contains(&String::from("plans-2024-q1.md")));
+
+    let mut first = String::new();
+    std::io::Read::read_to_string(&mut archive.by_name("groceries.md").unwrap(), &mut first).unwrap();
+    assert!(first.starts_with("---\nid: id-0\n"));
+    assert!(first.contains("tags: [\"food\"]"));
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_csv() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_created_between_paged()
+      .with(predicate::eq("2021-01-01"), predicate::eq("2021-02-01"), predicate::eq(CSV_EXPORT_BATCH_SIZE), predicate::eq(0))
+      .times(1)
+      .returning(|_, _, _, _| Ok(vec![db::Note {
+        id: String::from("id-0"),
+        title: String::from("Groceries"),
+        content: String::from("Milk, eggs\nand bread"),
+        created_at: String::from("2021-01-05 00:00:00"),
+        updated_at: String::from("2021-01-06 00:00:00"),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+    mock_service.expect_count_created_between()
+      .times(1)
+      .returning(|_, _| Ok(1));
+    mock_service.expect_tags_created_between()
+      .with(predicate::eq("2021-01-01"), predicate::eq("2021-02-01"))
+      .times(1)
+      .returning(|_, _| Ok(vec![db::NoteTag { note_id: String::from("id-0"), tag: String::from("food") }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.csv?from=2021-01-01&to=2021-01-31").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+
+    let body = test::read_body(resp).await;
+    assert_eq!(
+      std::str::from_utf8(&body).unwrap(),
+      "id,title,content,created_at,updated_at,tags\r\n\
+       id-0,Groceries,\"Milk, eggs\nand bread\",2021-01-05 00:00:00,2021-01-06 00:00:00,food\r\n",
+    );
+
+    let req = test::TestRequest::get().uri("/notes/export.csv?from=2021-02-01&to=2021-01-31").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_csv_in_batches() {
+    let mut mock_service = MockService::new();
+    let note = |index: i64| db::Note {
+      id: format!("id-{}", index),
+      title: String::from("Note"),
+      content: String::from("content"),
+      created_at: String::from("2021-01-05 00:00:00"),
+      updated_at: String::from("2021-01-06 00:00:00"),
+      version: 1,
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+
+    mock_service.expect_count_created_between()
+      .times(1)
+      .returning(|_, _| Ok(CSV_EXPORT_BATCH_SIZE + 1));
+    mock_service.expect_tags_created_between()
+      .times(1)
+      .returning(|_, _| Ok(vec![]));
+    mock_service.expect_created_between_paged()
+      .with(predicate::always(), predicate::always(), predicate::eq(CSV_EXPORT_BATCH_SIZE), predicate::eq(0))
+      .times(1)
+      .returning(move |_, _, limit, _| Ok((0..limit).map(note).collect()));
+    mock_service.expect_created_between_paged()
+      .with(predicate::always(), predicate::always(), predicate::eq(CSV_EXPORT_BATCH_SIZE), predicate::eq(CSV_EXPORT_BATCH_SIZE))
+      .times(1)
+      .returning(move |_, _, _, offset| Ok(vec![note(offset)]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.csv?from=2021-01-01&to=2021-01-31").to_request();
+    let body = test::read_body(test::call_service(&mut app, req).await).await;
+    let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+    assert_eq!(lines.len() as i64, 1 + CSV_EXPORT_BATCH_SIZE + 1);
+    assert!(lines[lines.len() - 1].starts_with(&format!("id-{},", CSV_EXPORT_BATCH_SIZE)));
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_csv_over_cap() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_count_created_between()
+      .times(1)
+      .returning(|_, _| Ok(3));
+    mock_service.expect_created_between_paged().times(0);
+    mock_service.expect_tags_created_between().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let config = Config { max_export_notes: 2, ..Config::default() };
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.csv?from=2021-01-01&to=2021-01-31").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 413);
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_zip_over_cap() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_count()
+      .times(2)
+      .returning(|| Ok(3));
+    mock_service.expect_all()
+      .times(1)
+      .returning(|| Ok(vec![]));
+    mock_service.expect_all_tags()
+      .times(1)
+      .returning(|| Ok(vec![]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    for (max_export_notes, status) in [(2, 413), (3, 200)] {
+      let config = Config { max_export_notes, ..Config::default() };
+      let mut app = test::init_service(
+        App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+      ).await;
+
+      let req = test::TestRequest::get().uri("/notes/export.zip").to_request();
+      assert_eq!(test::call_service(&mut app, req).await.status(), status);
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_export_notes_zip_with_tag() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_all()
+      .times(0);
+    mock_service.expect_count_by()
+      .times(1)
+      .returning(|_| Ok(1));
+    mock_service.expect_all_by()
+      .withf(|filter| filter.query.is_empty() && filter.tag.as_deref() == Some("work"))
+      .times(1)
+      .returning(|_| Ok(vec![db::Note {
+        id: String::from("id-1"),
+        title: String::from("Standup"),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+    mock_service.expect_all_tags()
+      .times(1)
+      .returning(|| Ok(vec![
+        db::NoteTag { note_id: String::from("id-1"), tag: String::from("work") },
+        db::NoteTag { note_id: String::from("id-2"), tag: String::from("home") },
+      ]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.zip?tag=work").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let archive = zip::ZipArchive::new(std::io::Cursor::new(body.to_vec())).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+
+    assert_eq!(names, vec!["standup.md"]);
+  }
+
+  #[actix_web::test]
+  async fn test_word_frequency() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_all()
+      .times(1)
+      .returning(|| Ok(["Buy milk and bread", "The milk is sour. Milk!"].iter().enumerate().map(|(i, content)| db::Note {
+        id: format!("id-{}", i),
+        title: format!("Note {}", i),
+        content: content.to_string(),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/word-frequency?top=2").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: WordFrequencyResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.words.len(), 2);
+    assert_eq!(returned.words[0].word, "milk");
+    assert_eq!(returned.words[0].count, 3);
+  }
+
+  #[actix_web::test]
+  async fn test_notes_feed() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_recent()
+      .with(predicate::eq(Config::default().feed_size))
+      .times(1)
+      .returning(|_| Ok(vec![db::Note {
+        id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"),
+        title: String::from("Fish & <chips>"),
+        content: String::from("Buy some."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-02T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/feed.xml").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/atom+xml");
+
+    let body = test::read_body(resp).await;
+    let mut reader = quick_xml::Reader::from_reader(body.as_ref());
+    let mut titles = Vec::new();
+    let mut in_title = false;
+    loop {
+      match reader.read_event().unwrap() {
+        quick_xml::events::Event::Start(tag) => in_title = tag.name().as_ref() == b"title",
+        quick_xml::events::Event::Text(text) if in_title => titles.push(text.unescape().unwrap().into_owned()),
+        quick_xml::events::Event::End(_) => in_title = false,
+        quick_xml::events::Event::Eof => break,
+        _ => {}
+      }
+    }
+
+    assert_eq!(titles, vec!["Notes", "Fish & <chips>"]);
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_collapses_whitespace() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_search()
+      .with(predicate::eq("shopping list"), predicate::always(), predicate::always())
+      .times(1)
+      .returning(|_, _, _| Ok(vec![]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=%20%20shopping%20%09%20list%20").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+  }
+
+  #[actix_web::test]
+  async fn test_search_notes_rejects_long_query() {
+    let mock_service = MockService::new();
+    let config = Config {
+      max_search_query_length: 5,
+      ..Config::default()
+    };
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/search?q=too%20long").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_tags() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_create().times(0);
+    mock_service.expect_create_with_tags()
+      .withf(|_, tags| tags.tags == [String::from("work"), String::from("ideas")])
+      .times(1)
+      .returning(|note, tags| {
+        let mut tags = tags.tags.clone();
+        tags.sort();
+        Ok((db::Note {
+          id: note.id.clone(),
+          title: note.title.clone(),
+          content: note.content.clone(),
+          created_at: note.created_at.clone(),
+          updated_at: note.created_at.clone(),
+          version: 1,
+          expires_at: None,
+          format: String::from("markdown"),
+        }, tags))
+      });
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"title": "Note 1", "content": "This is note #1.", "tags": ["work", "ideas"]}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: CreateNoteResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.note.title, "Note 1");
+    assert_eq!(returned.tags, Some(vec![String::from("ideas"), String::from("work")]));
+  }
+
+  #[actix_web::test]
+  async fn test_note_activity() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_activity()
+      .with(predicate::eq("2021-01-01"), predicate::eq("2021-01-08"), predicate::eq(db::ActivityBucket::Day))
+      .times(1)
+      .returning(|_, _, _| Ok(vec![
+        db::ActivityCount { bucket: String::from("2021-01-01"), count: 2 },
+        db::ActivityCount { bucket: String::from("2021-01-03"), count: 1 },
+      ]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/activity?from=2021-01-01&to=2021-01-07&bucket=day").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: ActivityResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.counts.len(), 2);
+    assert_eq!(returned.counts[0].bucket, "2021-01-01");
+    assert_eq!(returned.counts[0].count, 2);
+  }
+
+  #[actix_web::test]
+  async fn test_note_activity_rejects_invalid_range() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_activity().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    for uri in [
+      "/notes/activity?from=2021-01-07&to=2021-01-01",
+      "/notes/activity?from=yesterday&to=2021-01-01",
+      "/notes/activity?from=2021-01-01&to=2021-01-07&bucket=year",
+    ] {
+      let req = test::TestRequest::get().uri(uri).to_request();
+      let resp = test::call_service(&mut app, req).await;
+
+      assert_eq!(resp.status(), 400, "{}", uri);
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_html() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|id| Ok(Some(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("# Title\n\nSome *text*."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      })));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/html").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/html; charset=utf-8");
+
+    let body = test::read_body(resp).await;
+    let html = std::str::from_utf8(&body).unwrap();
+
+    assert!(html.contains("<h1>Title</h1>"));
+    assert!(html.contains("<em>text</em>"));
+  }
+
+  #[actix_web::test]
+  async fn test_notes_by_tag_overlap() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_by_tag_overlap()
+      .withf(|tags, _| tags == [String::from("work"), String::from("home")])
+      .times(1)
+      .returning(|_, _| Ok(["both", "work-only"].iter().zip([2, 1]).map(|(id, matched)| (db::Note {
+        id: id.to_string(),
+        title: id.to_string(),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }, matched)).collect()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/by-tags?tags=work,home,work").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let returned: TagOverlapResponse = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+
+    let ranked: Vec<(&str, i64)> = returned.notes.iter().map(|hit| (hit.note.id.as_str(), hit.matched_tags)).collect();
+    assert_eq!(ranked, vec![("both", 2), ("work-only", 1)]);
+
+    let req = test::TestRequest::get().uri("/notes/by-tags?tags=,").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_tracks_views() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get()
+      .times(2)
+      .returning(|id| Ok(Some(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      })));
+    mock_service.expect_record_view()
+      .withf(|id, _| id == "some-id")
+      .times(1)
+      .returning(|_, _| Ok(()));
+    mock_service.expect_recently_viewed()
+      .with(predicate::eq(MAX_RECENTLY_VIEWED_LIMIT))
+      .times(1)
+      .returning(|_| Ok(vec![db::ViewedNote {
+        id: String::from("some-id"),
+        title: String::from("Note 1"),
+        view_count: 1,
+        last_viewed_at: String::from("2021-01-01 12:00:00"),
+      }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id").to_request();
+    assert!(test::call_service(&mut app, req).await.status().is_success());
+    let req = test::TestRequest::get().uri("/notes/some-id?track=true").to_request();
+    assert!(test::call_service(&mut app, req).await.status().is_success());
+
+    let req = test::TestRequest::get().uri("/notes/recently-viewed?limit=500").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    let returned: RecentlyViewedResponse = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+
+    assert_eq!(returned.notes.len(), 1);
+    assert_eq!(returned.notes[0].view_count, 1);
+  }
+
+  #[actix_web::test]
+  async fn test_most_edited_notes() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_most_edited()
+      .with(predicate::eq(DEFAULT_MOST_EDITED_LIMIT))
+      .times(1)
+      .returning(|_| Ok(vec![
+        db::Note {
+          id: String::from("edited-often"),
+          title: String::from("Note 1"),
+          content: String::from("This is note #1."),
+          created_at: String::from("2021-01-01T00:00:00Z"),
+          updated_at: String::from("2021-01-03T00:00:00Z"),
+          version: 5,
+          expires_at: None,
+          format: String::from("markdown"),
+        },
+        db::Note {
+          id: String::from("edited-once"),
+          title: String::from("Note 2"),
+          content: String::from("This is note #2."),
+          created_at: String::from("2021-01-01T00:00:00Z"),
+          updated_at: String::from("2021-01-02T00:00:00Z"),
+          version: 2,
+          expires_at: None,
+          format: String::from("markdown"),
+        },
+      ]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/most-edited").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+
+    let returned: MostEditedResponse = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    let ids: Vec<&str> = returned.notes.iter().map(|note| note.id.as_str()).collect();
+    assert_eq!(ids, vec!["edited-often", "edited-once"]);
+  }
+
+  #[actix_web::test]
+  async fn test_unread_notes() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_updated_since_viewed()
+      .with(predicate::eq(MAX_UNREAD_LIMIT))
+      .times(1)
+      .returning(|_| Ok(vec![db::Note {
+        id: String::from("edited-since-viewed"),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-03T00:00:00Z"),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/unread?limit=1000").to_request();
+    let returned: UnreadResponse = test::call_and_read_body_json(&mut app, req).await;
+    let ids: Vec<&str> = returned.notes.iter().map(|note| note.id.as_str()).collect();
+    assert_eq!(ids, vec!["edited-since-viewed"]);
+  }
+
+  #[actix_web::test]
+  async fn test_portable_note_round_trip() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_get_with_tags()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|id| Ok((db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2021-01-01 00:00:00"),
+        updated_at: String::from("2021-01-02 00:00:00"),
+        version: 3,
+        expires_at: None,
+        format: String::from("plain"),
+      }, vec![String::from("ideas"), String::from("work")])));
+    mock_service.expect_create_with_tags()
+      .withf(|note, tags| note.id != "some-id"
+        && note.title == "Note 1"
+        && note.content == "This is note #1."
+        && note.created_at == "2021-01-01 00:00:00"
+        && note.format == "plain"
+        && tags.tags == [String::from("ideas"), String::from("work")])
+      .times(1)
+      .returning(|note, tags| Ok((db::Note {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        created_at: note.created_at.clone(),
+        updated_at: note.created_at.clone(),
+        version: 1,
+        expires_at: None,
+        format: note.format.clone(),
+      }, tags.tags.clone())));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/portable").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let bundle: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert!(bundle.get("id").is_none());
+    assert_eq!(bundle["schemaVersion"], PORTABLE_SCHEMA_VERSION);
+
+    let req = test::TestRequest::post().uri("/notes/portable").set_json(&bundle).to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success());
+    let returned: CreateNoteResponse = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+
+    assert_ne!(returned.note.id, "some-id");
+    assert_eq!(returned.tags, Some(vec![String::from("ideas"), String::from("work")]));
+  }
+
+  #[actix_web::test]
+  async fn test_import_portable_note_unsupported_schema_version() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_create_with_tags().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes/portable")
+      .set_json(serde_json::json!({
+        "schemaVersion": PORTABLE_SCHEMA_VERSION + 1,
+        "title": "Note 1",
+        "content": "This is note #1.",
+        "createdAt": "2021-01-01T00:00:00Z",
+      }))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 400);
+
+    let body: ErrorResponse = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert!(body.error.contains("bundle schema version 2 is not supported"), "{}", body.error);
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_plaintext() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|id| Ok(Some(db::Note {
+        id: id.to_string(),
+        title: String::from("Note 1"),
+        content: String::from("Some **bold** text."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      })));
+    mock_service.expect_try_get()
+      .with(predicate::eq("missing"))
+      .times(1)
+      .returning(|_| Ok(None));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/plaintext").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "text/plain; charset=utf-8");
+    assert_eq!(test::read_body(resp).await, "Some bold text.");
+
+    let req = test::TestRequest::get().uri("/notes/missing/plaintext").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_checksum() {
+    let mut mock_service = MockService::new();
+    let mut contents = vec!["This is note #1, edited.", "This is note #1."];
+
+    mock_service.expect_try_get()
+      .with(predicate::eq("some-id"))
+      .times(2)
+      .returning(move |id| {
+        let version = 3 - contents.len() as i64;
+        Ok(Some(db::Note {
+          id: id.to_string(),
+          title: String::from("Note 1"),
+          content: String::from(contents.pop().unwrap()),
+          created_at: String::from("2021-01-01T00:00:00Z"),
+          updated_at: String::from("2021-01-01T00:00:00Z"),
+          version,
+          expires_at: None,
+          format: String::from("markdown"),
+        }))
+      });
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/checksum").to_request();
+    let before: NoteChecksumResponse = test::call_and_read_body_json(&mut app, req).await;
+    // printf 'Note 1\nThis is note #1.' | sha256sum
+    assert_eq!(before.checksum, "ec944fb8447cf093c5d5d59fe036d80e1a570e92df2f3d245e5cc734ef94139b");
+    assert_eq!(before.algorithm, "sha256");
+
+    let req = test::TestRequest::get().uri("/notes/some-id/checksum").to_request();
+    let after: NoteChecksumResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_ne!(after.checksum, before.checksum);
+    assert_eq!(after.version, 2);
+  }
+
+  #[actix_web::test]
+  async fn test_get_note_size() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_storage_size()
+      .with(predicate::eq("some-id"))
+      .times(1)
+      .returning(|_| Ok(db::NoteSize { title: 6, content: 16, tags: 8 }));
+    mock_service.expect_storage_size()
+      .with(predicate::eq("missing"))
+      .times(1)
+      .returning(|_| Err(DbError::NotFound.into()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/size").to_request();
+    let size: NoteSizeResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(size.id, "some-id");
+    assert_eq!((size.title, size.content, size.tags), (6, 16, 8));
+    assert_eq!(size.total, 30);
+
+    let req = test::TestRequest::get().uri("/notes/missing/size").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+  }
+
+  fn compared_note(id: &str, content: &str) -> db::Note {
+    db::Note {
+      id: String::from(id),
+      title: String::from("Title"),
+      content: String::from(content),
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      updated_at: String::from("2021-01-01T00:00:00Z"),
+      version: 1,
+      expires_at: None,
+      format: String::from("markdown"),
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_compare_notes() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .returning(|id| Ok(match id {
+        "a" => Some(compared_note("a", "Buy milk and eggs\n")),
+        "b" => Some(compared_note("b", "Buy milk and eggs\n")),
+        "c" => Some(compared_note("c", "Call the plumber\n")),
+        _ => None,
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/a/compare/b").to_request();
+    let body: CompareNotesResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.similarity, 1.0);
+    assert_eq!(body.diff, "");
+
+    let req = test::TestRequest::get().uri("/notes/a/compare/c").to_request();
+    let body: CompareNotesResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert!(body.similarity < 0.01);
+    assert!(body.diff.contains("-Buy milk and eggs\n+Call the plumber\n"));
+
+    let req = test::TestRequest::get().uri("/notes/a/compare/missing").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_compare_notes_with_invalid_other_id() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let strict_config = Config { strict_id_validation: true, ..Config::default() };
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(strict_config)))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/14322988-32fe-447c-ac38-06fb6c699b4a/compare/not-a-uuid").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[test]
+  fn test_documented_lengths_match_validation() {
+    use utoipa::OpenApi;
+    use validator::Validate;
+
+    #[derive(OpenApi)]
+    #[openapi(components(schemas(CreateNoteRequest, UpdateNoteRequest)), modifiers(&NoteLengthLimits))]
+    struct Doc;
+
+    let doc = serde_json::to_value(Doc::openapi()).unwrap();
+    for name in ["CreateNoteRequest", "UpdateNoteRequest"] {
+      let properties = &doc["components"]["schemas"][name]["properties"];
+      assert_eq!(properties["title"]["maxLength"], db::TITLE_MAX_LENGTH, "{}", name);
+      assert_eq!(properties["content"]["maxLength"], db::CONTENT_MAX_LENGTH, "{}", name);
+    }
+
+    let note = |title: String, content: String| db::NewNote {
+      id: String::from("new-id"),
+      title,
+      content,
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      expires_at: None,
+      format: String::from("markdown"),
+    };
+    assert!(note("t".repeat(db::TITLE_MAX_LENGTH), "c".repeat(db::CONTENT_MAX_LENGTH)).validate().is_ok());
+    assert!(note("t".repeat(db::TITLE_MAX_LENGTH + 1), String::from("c")).validate().is_err());
+    assert!(note(String::from("t"), "c".repeat(db::CONTENT_MAX_LENGTH + 1)).validate().is_err());
+  }
+
+  #[actix_web::test]
+  async fn test_note_ids() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_ids_after()
+      .times(1)
+      .returning(|_, _| Ok(vec![String::from("a"), String::from("b")]));
+    mock_service.expect_get().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/ids").to_request();
+    let body: NoteIdsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.ids, vec!["a", "b"]);
+    assert_eq!(body.next_cursor, None);
+  }
+
+  #[actix_web::test]
+  async fn test_note_ids_across_pages() {
+    let all_ids: Vec<String> = (0..2500).map(|i| format!("{:05}", i)).collect();
+    let stored = all_ids.clone();
+    let mut mock_service = MockService::new();
+    mock_service.expect_ids_after()
+      .returning(move |after: &str, limit: i64| Ok(stored.iter().filter(|id| id.as_str() > after).take(limit as usize).cloned().collect()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let config = Config { max_ids_page_size: 1000, ..Config::default() };
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+
+    let mut collected = Vec::new();
+    let mut pages = 0;
+    let mut uri = String::from("/notes/ids?limit=5000");
+    loop {
+      let req = test::TestRequest::get().uri(&uri).to_request();
+      let body: NoteIdsResponse = test::call_and_read_body_json(&mut app, req).await;
+      pages += 1;
+      assert!(body.ids.len() <= 1000);
+      collected.extend(body.ids);
+      match body.next_cursor {
+        Some(cursor) => {
+          assert_eq!(collected.last(), Some(&cursor));
+          uri = format!("/notes/ids?cursor={}", cursor);
+        }
+        None => break,
+      }
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(collected, all_ids);
+
+    let req = test::TestRequest::get().uri("/notes/ids?cursor=00999&limit=2").to_request();
+    let body: NoteIdsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.ids, vec!["01000", "01001"]);
+    assert_eq!(body.next_cursor.as_deref(), Some("01001"));
+  }
+
+  fn find_replace_note(id: &str, content: &str) -> db::Note {
+    db::Note {
+      id: String::from(id),
+      title: String::from("Title"),
+      content: String::from(content),
+      created_at: String::from("2021-01-01T00:00:00Z"),
+      updated_at: String::from("2021-01-01T00:00:00Z"),
+      version: 3,
+      expires_at: None,
+      format: String::from("markdown"),
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_find_replace_notes() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_all()
+      .times(1)
+      .returning(|| Ok(vec![
+        find_replace_note("a", "teh cat and teh dog"),
+        find_replace_note("b", "nothing here"),
+        find_replace_note("c", "teh end"),
+      ]));
+    mock_service.expect_update_many()
+      .withf(|updates| {
+        updates.len() == 2
+          && updates[0].0 == "a" && updates[0].1.content == "the cat and the dog"
+          && updates[1].0 == "c" && updates[1].1.content == "the end"
+          && updates.iter().all(|(_, note)| note.version == Some(3))
+      })
+      .times(1)
+      .returning(|_| Ok(vec![]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes/find-replace")
+      .set_json(serde_json::json!({"find": "teh", "replace": "the"}))
+      .to_request();
+    let body: FindReplaceResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.changed, 2);
+    assert_eq!(body.ids, vec!["a", "c"]);
+    assert!(!body.dry_run);
+  }
+
+  #[actix_web::test]
+  async fn test_find_replace_notes_dry_run() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_all()
+      .times(1)
+      .returning(|| Ok(vec![find_replace_note("a", "teh cat"), find_replace_note("b", "the dog")]));
+    mock_service.expect_update_many().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes/find-replace")
+      .set_json(serde_json::json!({"find": "teh", "replace": "the", "dryRun": true}))
+      .to_request();
+    let body: FindReplaceResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body.changed, 1);
+    assert_eq!(body.ids, vec!["a"]);
+    assert!(body.dry_run);
+  }
+
+  #[actix_web::test]
+  async fn test_oldest_and_newest_notes() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_oldest()
+      .times(1)
+      .returning(|| Ok(Some(db::Note {
+        id: String::from("first"),
+        title: String::from("First"),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      })));
+    mock_service.expect_newest()
+      .times(1)
+      .returning(|| Ok(None));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/oldest").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body["note"]["id"], "first");
+
+    let req = test::TestRequest::get().uri("/notes/newest").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_note_context_of_first_note() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .with(predicate::eq("first"))
+      .times(1)
+      .returning(|id| Ok(Some(db::Note {
+        id: String::from(id),
+        title: String::from("First"),
+        content: String::from("content"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      })));
+    mock_service.expect_neighbors()
+      .with(predicate::eq("first"))
+      .times(1)
+      .returning(|_| Ok(db::NoteNeighbors {
+        previous: None,
+        next: Some(db::NoteTitle { id: String::from("second"), title: String::from("Second") }),
+      }));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/first/context").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(body["note"]["id"], "first");
+    assert!(body["previous"].is_null());
+    assert_eq!(body["next"]["id"], "second");
+  }
+
+  #[actix_web::test]
+  async fn test_malformed_id_with_strict_validation() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .with(predicate::eq("not-a-uuid"))
+      .times(1)
+      .returning(|_| Ok(None));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let strict_config = Config { strict_id_validation: true, ..Config::default() };
+
+    let mut strict_app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(strict_config)))
+    ).await;
+    let req = test::TestRequest::get().uri("/notes/not-a-uuid").to_request();
+    assert_eq!(test::call_service(&mut strict_app, req).await.status(), 400);
+
+    let mut lenient_app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+    let req = test::TestRequest::get().uri("/notes/not-a-uuid").to_request();
+    assert_eq!(test::call_service(&mut lenient_app, req).await.status(), 404);
+  }
+
+  #[actix_web::test]
+  async fn test_strict_validation_accepts_ids_matching_the_pattern() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .with(predicate::eq("note-1"))
+      .times(1)
+      .returning(|_| Ok(None));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let config = Config {
+      strict_id_validation: true,
+      note_id_pattern: Some(regex::Regex::new("^(?:note-[0-9]+)$").unwrap()),
+      ..Config::default()
+    };
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(config)))
+    ).await;
+    let req = test::TestRequest::get().uri("/notes/note-1").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 404);
+    let req = test::TestRequest::get().uri("/notes/other").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_plain_note_html_is_not_markdown() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_try_get()
+      .times(1)
+      .returning(|id| Ok(Some(db::Note {
+        id: String::from(id),
+        title: String::from("Plain"),
+        content: String::from("# Not a heading <b>"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("plain"),
+      })));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/some-id/html").to_request();
+    let body = test::call_and_read_body(&mut app, req).await;
+
+    assert_eq!(body, "<pre># Not a heading &lt;b&gt;</pre>");
+  }
+
+  #[actix_web::test]
+  async fn test_create_note_with_unknown_format() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_create().times(0);
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/notes")
+      .set_json(serde_json::json!({"title": "Note 1", "content": "Content", "format": "rtf"}))
+      .to_request();
+
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_get_missing_note_html() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_try_get()
+      .times(1)
+      .returning(|_| Ok(None));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/missing/html").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 404);
   }
-}
 
-#[utoipa::path(
-  responses(
-    (status = 200, description = "List notes", body = ListNotesResponse, example = json ! (ListNotesResponse{notes: vec ! [Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: String::from("This is note #1."), created_at: String::from("2021-01-01T00:00:00Z")}]})),
-  )
-)]
-#[get("/notes")]
-pub(super) async fn list_notes(note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
-  let db_notes = note_service.all().await?;
-  let api_notes: Vec<Note> = db_notes.into_iter().map(Note::from).collect();
+  #[actix_web::test]
+  async fn test_note_heatmap() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_activity_heatmap()
+      .with(predicate::eq("2021-01-01"), predicate::eq("2021-01-03"))
+      .times(1)
+      .returning(|_, _| Ok(vec![(String::from("2021-01-01"), 2), (String::from("2021-01-02"), 0)]));
 
-  Ok(HttpResponse::Ok().json(ListNotesResponse { notes: api_notes }))
-}
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
-#[utoipa::path(
-  responses(
-    (status = 200, description = "Get note", body = GetNoteResponse, example = json ! (GetNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: String::from("This is note #1."), created_at: String::from("2021-01-01T00:00:00Z")}})),
-    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
-  ),
-  params(
-    ("id", description = "Unique id")
-  ),
-)]
-#[get("/notes/{id}")]
-pub(super) async fn get_note(id: Path<String>, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
-  let db_note = note_service.get(id.as_str()).await?;
-  let api_note = Note::from(db_note);
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
 
-  Ok(HttpResponse::Ok().json(GetNoteResponse { note: api_note }))
-}
+    let req = test::TestRequest::get().uri("/notes/heatmap?from=2021-01-01&to=2021-01-02").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body["days"][1]["date"], "2021-01-02");
+    assert_eq!(body["days"][1]["count"], 0);
 
-#[utoipa::path(
-  request_body = CreateNoteRequest,
-  responses(
-    (status = 201, description = "Note created successfully", body = CreateNoteResponse, example = json ! (CreateNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: String::from("This is note #1."), created_at: String::from("2021-01-01T00:00:00Z")}})),
-    (status = 400, description = "Note not valid", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("body not valid"), error: String::from("title too long")})),
-  )
-)]
-#[post("/notes")]
-pub(super) async fn create_note(note_service: Data<Box<dyn NoteService>>, create_note: Json<CreateNoteRequest>) -> Result<HttpResponse, ApiError> {
-  let new_note = db::NewNote {
-    id: Uuid::new_v4().to_string(),
-    title: create_note.title.clone(),
-    content: create_note.content.clone(),
-    created_at: chrono::offset::Utc::now().naive_utc().to_string(),
-  };
-  let db_note = note_service.create(&new_note).await?;
-  let api_note = Note::from(db_note);
+    let req = test::TestRequest::get().uri("/notes/heatmap?from=2021-01-01&to=2022-12-31").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
 
-  Ok(HttpResponse::Ok().json(CreateNoteResponse { note: api_note }))
-}
+  #[actix_web::test]
+  async fn test_note_archive() {
+    let mut mock_service = MockService::new();
 
-#[utoipa::path(
-  responses(
-    (status = 200, description = "Note updated successfully", body = UpdateNoteResponse, example = json ! (UpdateNoteResponse{note: Note{id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"), title: String::from("Note 1"), content: String::from("This is note #1"), created_at: String::from("2021-01-01T00:00:00Z")}})),
-    (status = 400, description = "Note not valid", body = ErrorResponse, example = json ! (ErrorResponse{message: String::from("body not valid"), error: String::from("title too long")})),
-    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
-  ),
-  params(
-    ("id", description = "Unique id"),
-  ),
-)]
-#[put("/notes/{id}")]
-pub(super) async fn put_note(id: Path<String>, note_service: Data<Box<dyn NoteService>>, update_note: Json<UpdateNoteRequest>) -> Result<HttpResponse, ApiError> {
-  let db_note = note_service
-    .update(
-      id.as_str(),
-      &UpdateNote {
-        title: update_note.title.clone(),
-        content: update_note.content.clone(),
-      },
-    )
-    .await?;
-  let api_note = Note::from(db_note);
+    mock_service.expect_created_between()
+      .with(predicate::eq("2023-11-30 15:00:00"), predicate::eq("2023-12-31 15:00:00"))
+      .times(1)
+      .returning(|_, _| Ok(vec![db::Note {
+        id: String::from("december"),
+        title: String::from("Note 1"),
+        content: String::from("This is note #1."),
+        created_at: String::from("2023-11-30 16:00:00"),
+        updated_at: String::from("2023-11-30 16:00:00"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+    mock_service.expect_created_between()
+      .with(predicate::eq("2023-12-01 00:00:00"), predicate::eq("2024-01-01 00:00:00"))
+      .times(1)
+      .returning(|_, _| Ok(vec![]));
 
-  Ok(HttpResponse::Ok().json(UpdateNoteResponse { note: api_note }))
-}
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
-#[utoipa::path(
-  responses(
-    (status = 204, description = "Note deleted successfully"),
-    (status = 404, description = "Note not found by id", body = ErrorResponse, example = json ! (MessageResponse{message: String::from("note not found")})),
-  ),
-  params(
-    ("id", description = "Unique id"),
-  ),
-)]
-#[delete("/notes/{id}")]
-pub(super) async fn delete_note(id: Path<String>, note_service: Data<Box<dyn NoteService>>) -> Result<HttpResponse, ApiError> {
-  note_service.delete(id.as_str()).await?;
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
 
-  Ok(HttpResponse::NoContent().finish())
-}
+    let req = test::TestRequest::get().uri("/notes/archive/2023/12?tz=Asia/Tokyo").to_request();
+    let returned: ArchiveResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(returned.tz, "Asia/Tokyo");
+    assert_eq!(returned.notes.len(), 1);
+    assert_eq!(returned.notes[0].id, "december");
 
-#[cfg(test)]
-mod tests {
-  use actix_web::test;
-  use super::*;
-  use anyhow::Result;
-  use async_trait::async_trait;
-  use actix_web::App;
-  use mockall::predicate;
+    let req = test::TestRequest::get().uri("/notes/archive/2023/12").to_request();
+    let returned: ArchiveResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert!(returned.notes.is_empty());
 
-  mock! {
-    Service {}
-    #[async_trait]
-    impl service::NoteService for Service {
-      async fn all(&self) -> Result<Vec<db::Note>>;
-      async fn get(&self, id: &str) -> Result<db::Note>;
-      async fn create(&self, note: &db::NewNote) -> Result<db::Note>;
-      async fn update(&self, id: &str, note: &db::UpdateNote) -> Result<db::Note>;
-      async fn delete(&self, id: &str) -> Result<db::Note>;
+    for uri in ["/notes/archive/2023/13", "/notes/archive/2023/0", "/notes/archive/2023/12?tz=Mars/Olympus"] {
+      let req = test::TestRequest::get().uri(uri).to_request();
+      assert_eq!(test::call_service(&mut app, req).await.status(), 400, "Expected {} to be rejected", uri);
     }
   }
 
   #[actix_web::test]
-  async fn test_list_notes() {
+  async fn test_note_patterns() {
     let mut mock_service = MockService::new();
+    // Monday 09:15 and Sunday 23:59 UTC
+    mock_service.expect_creation_minutes()
+      .times(2)
+      .returning(|| Ok(vec![
+        db::ActivityCount { bucket: String::from("2021-01-04 09:15"), count: 2 },
+        db::ActivityCount { bucket: String::from("2021-01-10 23:59"), count: 1 },
+      ]));
 
-    mock_service.expect_all()
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/patterns").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body["tz"], "UTC");
+    assert_eq!(body["weekdays"][0], serde_json::json!({"day": "monday", "count": 2}));
+    assert_eq!(body["weekdays"][6]["count"], 1);
+    assert_eq!(body["hours"][9]["count"], 2);
+    assert_eq!(body["grid"][6][23], 1);
+
+    let req = test::TestRequest::get().uri("/notes/patterns?tz=Asia/Tokyo").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body["tz"], "Asia/Tokyo");
+    assert_eq!(body["weekdays"][0]["count"], 3);
+    assert_eq!(body["hours"][18]["count"], 2);
+    assert_eq!(body["hours"][8]["count"], 1);
+
+    let req = test::TestRequest::get().uri("/notes/patterns?tz=Mars/Olympus").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 400);
+  }
+
+  #[actix_web::test]
+  async fn test_note_stats_by_tag() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_count()
       .times(1)
-      .returning(|| Ok(vec![db::Note {
-        id: String::from("14322988-32fe-447c-ac38-06fb6c699b4a"),
-        title: String::from("Note 1"),
-        content: String::from("This is note #1."),
-        created_at: String::from("2021-01-01T00:00:00Z"),
-      }]));
+      .returning(|| Ok(10));
+    mock_service.expect_count_by_tag()
+      .with(predicate::eq("work"))
+      .times(1)
+      .returning(|_| Ok(4));
 
     let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
     let mut app = test::init_service(
-      App::new().configure(configure(note_service_data.clone()))
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
     ).await;
 
-    let req = test::TestRequest::get().uri("/notes").to_request();
+    let req = test::TestRequest::get().uri("/notes/stats?tag=work").to_request();
     let resp = test::call_service(&mut app, req).await;
 
     assert!(resp.status().is_success());
-  }
 
-  // #[actix_web::test]
-  // async fn test_get_note() {
+    let body = test::read_body(resp).await;
+    let returned: NoteStatsResponse = serde_json::from_slice(&body).unwrap();
 
-  // }
+    assert_eq!(returned.total, 10);
+    assert_eq!(returned.tagged, Some(4));
+  }
 
   #[actix_web::test]
-  async fn test_create_note() {
+  async fn test_append_note() {
     let mut mock_service = MockService::new();
 
-    let new_note = db::NewNote {
-      id: String::from("new-id"),
-      title: String::from("Note 1"),
-      content: String::from("This is note #1."),
-      created_at: String::from("2021-01-01T00:00:00Z"),
-    };
-    let new_note_test = new_note.clone();
-    mock_service.expect_create()
+    mock_service.expect_append()
+      .withf(|id, text, _| id == "some-id" && text == " more")
       .times(1)
-      .returning(move |_| Ok(db::Note {
-        id: String::from("new-id"),
-        title: new_note_test.title.clone(),
-        content: new_note_test.content.clone(),
-        created_at: new_note_test.created_at.clone(),
+      .returning(|id, text, updated_at| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Journal"),
+        content: format!("content{}", text),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: updated_at.to_string(),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
       }));
+    mock_service.expect_append()
+      .withf(|_, text, _| text.len() == db::CONTENT_MAX_LENGTH)
+      .times(1)
+      .returning(|_, _, _| {
+        let mut error = validator::ValidationError::new("length");
+        error.add_param("max".into(), &db::CONTENT_MAX_LENGTH);
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("content", error);
+        Err(errors.into())
+      });
 
     let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
     let mut app = test::init_service(
-      App::new().configure(configure(note_service_data.clone()))
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
     ).await;
 
-    let note = CreateNoteRequest {
-      title: "Note 1".to_string(),
-      content: "This is note #1.".to_string(),
-    };
-
     let req = test::TestRequest::post()
-      .uri("/notes")
-      .set_json(&note)
+      .uri("/notes/some-id/append")
+      .set_json(serde_json::json!({"text": " more"}))
       .to_request();
-
     let resp = test::call_service(&mut app, req).await;
 
     assert!(resp.status().is_success());
 
     let body = test::read_body(resp).await;
+    let returned: UpdateNoteResponse = serde_json::from_slice(&body).unwrap();
 
-    let returned_note: CreateNoteResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(returned.note.content, Some(String::from("content more")));
 
-    let expected_note = db::Note {
-      id: String::from("new-id"),
-      title: String::from("Note 1"),
-      content: String::from("This is note #1."),
-      created_at: String::from("2021-01-01T00:00:00Z"),
-    };
-    assert_eq!(returned_note.note.id, expected_note.id);
-    assert_eq!(returned_note.note.title, expected_note.title);
-    assert_eq!(returned_note.note.content, expected_note.content);
-    assert_eq!(returned_note.note.created_at, expected_note.created_at);
+    let req = test::TestRequest::post()
+      .uri("/notes/some-id/append")
+      .set_json(serde_json::json!({"text": "x".repeat(db::CONTENT_MAX_LENGTH)}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 400);
   }
 
   #[actix_web::test]
-  async fn test_update_note() {
+  async fn test_prepend_and_insert_note() {
     let mut mock_service = MockService::new();
 
-    let note_id = "some-id";
-    let update_request = UpdateNoteRequest {
-      title: "Updated Title".to_string(),
-      content: "Updated content".to_string(),
-    };
-    let updated_note = db::UpdateNote {
-      title: update_request.title.clone(),
-      content: update_request.content.clone(),
-    };
-    let updated_note_test = updated_note.clone();
-    let updated_note_test_predicate = updated_note.clone();
-    mock_service.expect_update()
-      .with(predicate::eq(note_id), predicate::eq(updated_note_test_predicate))
+    mock_service.expect_insert()
+      .withf(|id, offset, text, _| id == "some-id" && *offset == 0 && text == "Top: ")
       .times(1)
-      .returning(move |_, _| Ok(db::Note {
-        id: note_id.clone().to_string(),
-        title: updated_note_test.title.clone(),
-        content: updated_note_test.content.clone(),
+      .returning(|id, _, _, updated_at| Ok(db::Note {
+        id: id.to_string(),
+        title: String::from("Journal"),
+        content: String::from("Top: content"),
         created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: updated_at.to_string(),
+        version: 2,
+        expires_at: None,
+        format: String::from("markdown"),
       }));
+    mock_service.expect_insert()
+      .withf(|_, offset, _, _| *offset == 3)
+      .times(1)
+      .returning(|_, _, _, _| {
+        let mut errors = validator::ValidationErrors::new();
+        errors.add("offset", validator::ValidationError::new("range"));
+        Err(errors.into())
+      });
 
     let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
     let mut app = test::init_service(
-      App::new().configure(configure(note_service_data.clone()))
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
     ).await;
 
-    let req = test::TestRequest::put()
-      .uri(&format!("/notes/{}", note_id))
-      .set_json(&update_request)
+    let req = test::TestRequest::post()
+      .uri("/notes/some-id/prepend")
+      .set_json(serde_json::json!({"text": "Top: "}))
       .to_request();
-
     let resp = test::call_service(&mut app, req).await;
 
     assert!(resp.status().is_success());
 
     let body = test::read_body(resp).await;
-    let returned_note: UpdateNoteResponse = serde_json::from_slice(&body).unwrap();
+    let returned: UpdateNoteResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(returned.note.content, Some(String::from("Top: content")));
 
-    assert_eq!(returned_note.note.title, update_request.title);
-    assert_eq!(returned_note.note.content, update_request.content);
+    let req = test::TestRequest::post()
+      .uri("/notes/some-id/insert?offset=3")
+      .set_json(serde_json::json!({"text": "x"}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert_eq!(resp.status(), 400);
   }
 
   #[actix_web::test]
-  async fn test_delete_note() {
+  async fn test_rename_tag() {
     let mut mock_service = MockService::new();
 
-    let note_id = "some-id";
-    mock_service.expect_delete()
-      .with(predicate::eq(note_id))
+    mock_service.expect_rename_tag()
+      .with(predicate::eq("js"), predicate::eq("javascript"))
       .times(1)
-      .returning(|_| Ok(db::Note {
-        id: note_id.to_string(),
-        title: String::from("Note 1"),
-        content: String::from("This is note #1."),
-        created_at: String::from("2021-01-01T00:00:00Z"),
-      }));
+      .returning(|_, _| Ok(vec![String::from("id-1"), String::from("id-2")]));
 
     let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
     let mut app = test::init_service(
-      App::new().configure(configure(note_service_data.clone()))
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
     ).await;
 
-    let req = test::TestRequest::delete()
-      .uri(&format!("/notes/{}", note_id))
+    let req = test::TestRequest::post()
+      .uri("/tags/rename")
+      .set_json(serde_json::json!({"from": "js", "to": "javascript"}))
       .to_request();
-
     let resp = test::call_service(&mut app, req).await;
 
     assert!(resp.status().is_success());
-    assert_eq!(resp.status(), 204);
+
+    let body = test::read_body(resp).await;
+    let returned: RenameTagResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.note_ids, vec!["id-1", "id-2"]);
   }
 
   #[actix_web::test]
-  async fn test_delete_note_not_found() {
-      let mut mock_service = MockService::new();
+  async fn test_tag_trend() {
+    let mut mock_service = MockService::new();
 
-      // Set up the mock to return an error of type db::NotFound
-      mock_service.expect_delete()
-          .times(1)
-          .returning(|_| Err(anyhow::anyhow!(db::DbError::NotFound)));
+    mock_service.expect_tag_trend()
+      .with(predicate::eq("rust"))
+      .times(1)
+      .returning(|_| Ok(vec![
+        db::ActivityCount { bucket: String::from("2023-11"), count: 1 },
+        db::ActivityCount { bucket: String::from("2023-12"), count: 2 },
+      ]));
 
-      let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
 
-      let mut app = test::init_service(
-          App::new().configure(configure(note_service_data.clone()))
-      ).await;
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
 
-      let req = test::TestRequest::delete().uri("/notes/some_id").to_request();
-      let resp = test::call_service(&mut app, req).await;
+    let req = test::TestRequest::get().uri("/tags/rust/trend").to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(body, serde_json::json!({
+      "tag": "rust",
+      "months": [{"bucket": "2023-11", "count": 1}, {"bucket": "2023-12", "count": 2}],
+    }));
+  }
 
-      assert_eq!(resp.status(), 404);
+  #[actix_web::test]
+  async fn test_tag_co_occurrence() {
+    let mut mock_service = MockService::new();
+
+    mock_service.expect_tag_co_occurrence()
+      .with(predicate::eq(MAX_TAG_PAIRS))
+      .times(1)
+      .returning(|_| Ok(vec![
+        db::TagPair { tag: String::from("rust"), other_tag: String::from("web"), count: 3 },
+        db::TagPair { tag: String::from("rust"), other_tag: String::from("todo"), count: 1 },
+      ]));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/tags/co-occurrence?limit=1000").to_request();
+    let returned: TagCoOccurrenceResponse = test::call_and_read_body_json(&mut app, req).await;
+
+    assert_eq!(returned.pairs.len(), 2);
+    assert_eq!((returned.pairs[0].tag.as_str(), returned.pairs[0].other_tag.as_str(), returned.pairs[0].count), ("rust", "web", 3));
+  }
+
+  #[actix_web::test]
+  async fn test_tag_colors() {
+    let mut mock_service = MockService::new();
+    let stored = std::sync::Arc::new(std::sync::Mutex::new(Vec::<db::TagColor>::new()));
+
+    let set = stored.clone();
+    mock_service.expect_set_tag_color()
+      .with(predicate::eq("work"), predicate::eq("#1e90ff"))
+      .times(1)
+      .returning(move |tag, color| {
+        set.lock().unwrap().push(db::TagColor { tag: tag.to_string(), color: color.to_string() });
+        Ok(())
+      });
+    let removed = stored.clone();
+    mock_service.expect_remove_tag_color()
+      .with(predicate::eq("work"))
+      .times(2)
+      .returning(move |tag| {
+        let mut colors = removed.lock().unwrap();
+        let before = colors.len();
+        colors.retain(|tag_color| tag_color.tag != tag);
+        Ok(colors.len() < before)
+      });
+    let listed = stored.clone();
+    mock_service.expect_tag_colors()
+      .times(4)
+      .returning(move || Ok(listed.lock().unwrap().clone()));
+
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new().configure(configure(note_service_data.clone(), Data::new(Config::default())))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/tags/colors").to_request();
+    let returned: TagColorsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert!(returned.colors.is_empty());
+
+    let req = test::TestRequest::put()
+      .uri("/tags/work/color")
+      .set_json(serde_json::json!({"color": "#1e90ff"}))
+      .to_request();
+    let returned: TagColorsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(returned.colors.get("work").map(String::as_str), Some("#1e90ff"));
+
+    let req = test::TestRequest::get().uri("/tags/colors").to_request();
+    let returned: TagColorsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert_eq!(returned.colors, BTreeMap::from([(String::from("work"), String::from("#1e90ff"))]));
+
+    let req = test::TestRequest::delete().uri("/tags/work/color").to_request();
+    let returned: TagColorsResponse = test::call_and_read_body_json(&mut app, req).await;
+    assert!(returned.colors.is_empty());
+
+    let req = test::TestRequest::delete().uri("/tags/work/color").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), 404);
   }
+
 }
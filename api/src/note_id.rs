@@ -0,0 +1,49 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest};
+use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+use crate::config::Config;
+use crate::error::ApiError;
+
+/// The `{id}` segment of a note route. With `Config::strict_id_validation` ids no note can have are
+/// rejected with 400 before the database is queried.
+pub(crate) struct NoteId(String);
+
+impl NoteId {
+  pub(crate) fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl FromRequest for NoteId {
+  type Error = ApiError;
+  type Future = Ready<Result<Self, Self::Error>>;
+
+  fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+    let id = req.match_info().get("id").unwrap_or_default().to_string();
+    let result = match req.app_data::<Data<Config>>() {
+      Some(config) => validate(config, &id),
+      None => Ok(()),
+    };
+
+    ready(result.map(|()| NoteId(id)))
+  }
+}
+
+/// With `Config::strict_id_validation`, rejects `id` unless it is a generated UUID or matches
+/// `Config::note_id_pattern`, the two ways a note gets its id.
+pub(crate) fn validate(config: &Config, id: &str) -> Result<(), ApiError> {
+  if !config.strict_id_validation || Uuid::parse_str(id).is_ok() || config.note_id_pattern.as_ref().map_or(true, |pattern| pattern.is_match(id)) {
+    return Ok(());
+  }
+
+  let mut error = ValidationError::new("format");
+  error.message = Some("note id has an invalid format".into());
+  let mut errors = ValidationErrors::new();
+  errors.add("id", error);
+  Err(ApiError::ValidationError(errors))
+}
@@ -0,0 +1,73 @@
+use chrono::{Datelike, NaiveDateTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+
+pub(crate) const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+/// How many notes were created on each day of the week and hour of the day, in one time zone.
+#[derive(Debug, PartialEq)]
+pub(crate) struct WritingPatterns {
+  /// Indexed by day of the week, Monday first, then by hour of the day
+  pub(crate) grid: [[i64; 24]; 7],
+}
+
+impl WritingPatterns {
+  pub(crate) fn by_weekday(&self) -> [i64; 7] {
+    self.grid.map(|hours| hours.iter().sum())
+  }
+
+  pub(crate) fn by_hour(&self) -> [i64; 24] {
+    let mut hours = [0; 24];
+    for day in &self.grid {
+      for (hour, count) in day.iter().enumerate() {
+        hours[hour] += count;
+      }
+    }
+    hours
+  }
+}
+
+/// Buckets per-minute UTC creation counts, as returned by `NoteService::creation_minutes`, by local
+/// day of the week and hour in `tz`. Minutes are fine enough for zones offset by a half or quarter hour.
+pub(crate) fn bucket(minutes: &[db::ActivityCount], tz: Tz) -> WritingPatterns {
+  let mut grid = [[0; 24]; 7];
+  for minute in minutes {
+    let utc = match NaiveDateTime::parse_from_str(&minute.bucket, "%Y-%m-%d %H:%M") {
+      Ok(utc) => utc,
+      Err(_) => continue,
+    };
+    let local = tz.from_utc_datetime(&utc);
+    grid[local.weekday().num_days_from_monday() as usize][local.hour() as usize] += minute.count;
+  }
+
+  WritingPatterns { grid }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn minute(bucket: &str, count: i64) -> db::ActivityCount {
+    db::ActivityCount { bucket: String::from(bucket), count }
+  }
+
+  #[test]
+  fn test_bucket_in_utc() {
+    // 2021-01-04 was a Monday
+    let patterns = bucket(&[minute("2021-01-04 09:15", 2), minute("2021-01-10 23:59", 1)], Tz::UTC);
+
+    assert_eq!(patterns.grid[0][9], 2);
+    assert_eq!(patterns.grid[6][23], 1);
+    assert_eq!(patterns.by_weekday(), [2, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(patterns.by_hour()[9], 2);
+    assert_eq!(patterns.by_hour().iter().sum::<i64>(), 3);
+  }
+
+  #[test]
+  fn test_bucket_in_time_zone() {
+    // Sunday 23:59 UTC is Monday 08:59 in Tokyo; Monday 09:15 UTC is Monday 14:45 in Kolkata
+    let minutes = [minute("2021-01-10 23:59", 1), minute("2021-01-04 09:15", 1)];
+
+    assert_eq!(bucket(&minutes[..1], Tz::Asia__Tokyo).grid[0][8], 1);
+    assert_eq!(bucket(&minutes[1..], Tz::Asia__Kolkata).grid[0][14], 1);
+  }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::Error;
+
+use crate::error::ApiError;
+
+/// Groups of routes sharing a request budget, so heavy endpoints can't use up the budget of cheap ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum RouteClass {
+  Crud,
+  Search,
+  Export,
+  Stats,
+}
+
+impl RouteClass {
+  pub(crate) fn of(path: &str) -> Self {
+    match path {
+      "/notes/search" | "/notes/autocomplete" => RouteClass::Search,
+      "/notes/export.zip" | "/notes/export.csv" | "/notes/feed.xml" => RouteClass::Export,
+      "/notes/stats" | "/notes/activity" | "/notes/patterns" | "/notes/word-frequency" => RouteClass::Stats,
+      _ if path.starts_with("/saved-searches/") && path.ends_with("/results") => RouteClass::Search,
+      _ => RouteClass::Crud,
+    }
+  }
+}
+
+struct Window {
+  started: Instant,
+  count: u32,
+}
+
+/// Fixed-window request counter per client and route class.
+pub(crate) struct RateLimiter {
+  window: Duration,
+  limits: HashMap<RouteClass, u32>,
+  windows: Mutex<HashMap<(RouteClass, String), Window>>,
+}
+
+impl RateLimiter {
+  pub(crate) fn new(window: Duration) -> Self {
+    RateLimiter { window, limits: HashMap::new(), windows: Mutex::new(HashMap::new()) }
+  }
+
+  /// Allows `limit` requests per window for `class`. Zero leaves the class unlimited.
+  pub(crate) fn with_limit(mut self, class: RouteClass, limit: u32) -> Self {
+    if limit > 0 {
+      self.limits.insert(class, limit);
+    }
+    self
+  }
+
+  /// Counts a request from `client` and returns whether it is within the limit of `class`.
+  pub(crate) fn try_acquire(&self, class: RouteClass, client: &str) -> bool {
+    let Some(&limit) = self.limits.get(&class) else {
+      return true;
+    };
+
+    let now = Instant::now();
+    let mut windows = self.windows.lock().unwrap();
+    windows.retain(|_, window| now.duration_since(window.started) < self.window);
+    let window = windows.entry((class, client.to_string())).or_insert(Window { started: now, count: 0 });
+    if window.count >= limit {
+      return false;
+    }
+    window.count += 1;
+    true
+  }
+}
+
+/// `wrap_fn` middleware answering 429 when a client exceeds the limit of the requested route's class.
+pub(super) fn limit_rate<S, B>(req: ServiceRequest, srv: &S, limiter: &RateLimiter) -> impl Future<Output = Result<ServiceResponse<EitherBody<B>>, Error>>
+where
+  S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+  let class = RouteClass::of(req.path());
+  // The socket's address, not Forwarded or X-Forwarded-For, which a client could change on every request
+  let client = req.peer_addr().map(|addr| addr.ip().to_string()).unwrap_or_else(|| String::from("unknown"));
+  let response = if limiter.try_acquire(class, &client) { Ok(srv.call(req)) } else { Err(req) };
+
+  async move {
+    match response {
+      Ok(response) => response.await.map(ServiceResponse::map_into_left_body),
+      Err(req) => Ok(req.error_response(ApiError::TooManyRequests).map_into_right_body()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+  use actix_web::{test, web, App, HttpResponse};
+  use super::*;
+
+  #[test]
+  fn test_route_classes() {
+    assert_eq!(RouteClass::of("/notes/export.zip"), RouteClass::Export);
+    assert_eq!(RouteClass::of("/notes/export.csv"), RouteClass::Export);
+    assert_eq!(RouteClass::of("/notes/search"), RouteClass::Search);
+    assert_eq!(RouteClass::of("/saved-searches/1/results"), RouteClass::Search);
+    assert_eq!(RouteClass::of("/notes/stats"), RouteClass::Stats);
+    assert_eq!(RouteClass::of("/notes/some-id"), RouteClass::Crud);
+  }
+
+  #[actix_web::test]
+  async fn test_export_limit_does_not_affect_crud() {
+    let limiter = Arc::new(
+      RateLimiter::new(Duration::from_secs(60))
+        .with_limit(RouteClass::Export, 1)
+        .with_limit(RouteClass::Crud, 10)
+    );
+    let mut app = test::init_service(
+      App::new()
+        .wrap_fn(move |req, srv| limit_rate(req, srv, &limiter))
+        .route("/notes/export.zip", web::get().to(HttpResponse::Ok))
+        .route("/notes/{id}", web::get().to(HttpResponse::Ok))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/notes/export.zip").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 200);
+
+    let req = test::TestRequest::get().uri("/notes/export.zip").to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 429);
+
+    for _ in 0..3 {
+      let req = test::TestRequest::get().uri("/notes/some-id").to_request();
+      assert_eq!(test::call_service(&mut app, req).await.status(), 200);
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_forwarded_headers_do_not_reset_the_limit() {
+    let limiter = Arc::new(RateLimiter::new(Duration::from_secs(60)).with_limit(RouteClass::Crud, 1));
+    let mut app = test::init_service(
+      App::new()
+        .wrap_fn(move |req, srv| limit_rate(req, srv, &limiter))
+        .route("/notes/{id}", web::get().to(HttpResponse::Ok))
+    ).await;
+    let peer = "203.0.113.7:50000".parse().unwrap();
+
+    let req = test::TestRequest::get().uri("/notes/some-id").peer_addr(peer).insert_header(("X-Forwarded-For", "198.51.100.1")).to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 200);
+
+    let req = test::TestRequest::get().uri("/notes/some-id").peer_addr(peer).insert_header(("X-Forwarded-For", "198.51.100.2")).to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 429);
+
+    let other_peer = "203.0.113.8:50000".parse().unwrap();
+    let req = test::TestRequest::get().uri("/notes/some-id").peer_addr(other_peer).to_request();
+    assert_eq!(test::call_service(&mut app, req).await.status(), 200);
+  }
+}
@@ -0,0 +1,41 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Reading speed assumed unless configured otherwise.
+pub(crate) const DEFAULT_WORDS_PER_MINUTE: usize = 200;
+
+/// Words per minute used for `Note::reading_time_minutes`. Set once on startup from `Config::reading_words_per_minute`.
+static WORDS_PER_MINUTE: AtomicUsize = AtomicUsize::new(DEFAULT_WORDS_PER_MINUTE);
+
+pub(crate) fn set_words_per_minute(words_per_minute: usize) {
+  WORDS_PER_MINUTE.store(words_per_minute.max(1), Ordering::Relaxed);
+}
+
+/// Minutes it takes to read `content` at the configured speed, rounded up. Empty content takes none.
+pub(crate) fn minutes(content: &str) -> usize {
+  minutes_at(content, WORDS_PER_MINUTE.load(Ordering::Relaxed))
+}
+
+fn minutes_at(content: &str, words_per_minute: usize) -> usize {
+  content.split_whitespace().count().div_ceil(words_per_minute)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_short_content() {
+    assert_eq!(minutes_at("", 200), 0);
+    assert_eq!(minutes_at("   ", 200), 0);
+    assert_eq!(minutes_at("One short note.", 200), 1);
+  }
+
+  #[test]
+  fn test_long_content() {
+    let content = "word ".repeat(1001);
+
+    assert_eq!(minutes_at(&content, 200), 6);
+    assert_eq!(minutes_at(&content, 1001), 1);
+    assert_eq!(minutes_at(&"word ".repeat(1000), 200), 5);
+  }
+}
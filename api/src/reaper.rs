@@ -0,0 +1,27 @@
+use std::time::Duration;
+
+use actix_web::web::Data;
+use anyhow::Result;
+use service::NoteService;
+
+/// Deletes expired notes every `interval` for as long as the server runs.
+pub(crate) fn spawn(note_service: Data<Box<dyn NoteService>>, interval: Duration) {
+  actix_web::rt::spawn(async move {
+    let mut ticks = actix_web::rt::time::interval(interval);
+    loop {
+      ticks.tick().await;
+      match reap(note_service.as_ref().as_ref()).await {
+        Ok(0) => {}
+        Ok(deleted) => log::info!("Deleted {} expired notes", deleted),
+        Err(e) => log::error!("Failed to delete expired notes: {:?}", e),
+      }
+    }
+  });
+}
+
+/// Deletes the notes that expired by now and returns how many there were.
+pub(crate) async fn reap(note_service: &dyn NoteService) -> Result<usize> {
+  let now = chrono::offset::Utc::now().naive_utc().to_string();
+
+  Ok(note_service.delete_expired(&now).await?.len())
+}
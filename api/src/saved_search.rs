@@ -0,0 +1,311 @@
+use actix_web::{get, post, HttpResponse, web::{Data, Json, Path, Query, ServiceConfig}};
+use db::{NoteTags, SavedSearchRepository, SearchFilter, SearchSort};
+use serde::{Deserialize, Serialize};
+use service::NoteService;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError, ValidationErrors};
+use crate::config::Config;
+use crate::error::ApiError;
+use crate::domain::{ErrorResponse, MessageResponse};
+use crate::note::{normalize_search_query, Note};
+use crate::timestamp;
+
+/// Longest accepted saved search name, in characters.
+const NAME_MAX_LENGTH: usize = 50;
+
+pub(super) fn configure(saved_search_repository: Data<Box<dyn SavedSearchRepository>>) -> impl FnOnce(&mut ServiceConfig) {
+  |config: &mut ServiceConfig| {
+    config
+      .app_data(saved_search_repository)
+      .service(list_saved_searches)
+      .service(create_saved_search)
+      .service(saved_search_results);
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum SavedSearchSort {
+  /// Newest notes first
+  #[default]
+  Newest,
+  /// Oldest notes first
+  Oldest,
+  /// Alphabetically by title
+  Title,
+}
+
+impl From<SavedSearchSort> for SearchSort {
+  fn from(sort: SavedSearchSort) -> Self {
+    match sort {
+      SavedSearchSort::Newest => SearchSort::Newest,
+      SavedSearchSort::Oldest => SearchSort::Oldest,
+      SavedSearchSort::Title => SearchSort::Title,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SavedSearch {
+  #[schema(example = 1)]
+  id: i64,
+  #[schema(example = "Open work items")]
+  name: String,
+  /// Text the notes' title or content must contain
+  #[schema(example = "todo")]
+  query: String,
+  /// Tag the notes must carry, if any
+  #[schema(example = "work")]
+  tag: Option<String>,
+  /// newest, oldest or title
+  #[schema(example = "newest")]
+  sort: String,
+  #[schema(example = "2021-01-01T00:00:00Z")]
+  #[serde(serialize_with = "timestamp::serialize", deserialize_with = "timestamp::deserialize")]
+  created_at: String,
+}
+
+impl From<db::SavedSearch> for SavedSearch {
+  fn from(db_search: db::SavedSearch) -> Self {
+    Self {
+      id: db_search.id,
+      name: db_search.name,
+      query: db_search.query,
+      tag: db_search.tag,
+      sort: db_search.sort,
+      created_at: db_search.created_at,
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct CreateSavedSearchRequest {
+  #[schema(example = "Open work items")]
+  name: String,
+  #[schema(example = "todo")]
+  query: String,
+  #[schema(example = "work")]
+  tag: Option<String>,
+  #[serde(default)]
+  sort: SavedSearchSort,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SavedSearchResponse {
+  search: SavedSearch,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct ListSavedSearchesResponse {
+  searches: Vec<SavedSearch>,
+}
+
+#[derive(Serialize, Deserialize, Clone, IntoParams)]
+pub(super) struct SavedSearchResultsQuery {
+  /// Maximum number of notes to return. Clamped to the server's maximum number of search results.
+  limit: Option<i64>,
+  /// Number of matching notes to skip
+  offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub(super) struct SavedSearchResultsResponse {
+  notes: Vec<Note>,
+  limit: i64,
+  offset: i64,
+}
+
+fn validate_name(name: &str) -> Result<String, ValidationErrors> {
+  let name = name.trim();
+  if (1..=NAME_MAX_LENGTH).contains(&name.chars().count()) {
+    return Ok(name.to_string());
+  }
+
+  let mut error = ValidationError::new("length");
+  error.message = Some(format!("name must be between 1 and {} characters long", NAME_MAX_LENGTH).into());
+  let mut errors = ValidationErrors::new();
+  errors.add("name", error);
+  Err(errors)
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "All saved searches, by name", body = ListSavedSearchesResponse),
+  )
+)]
+#[get("/saved-searches")]
+pub(super) async fn list_saved_searches(saved_search_repository: Data<Box<dyn SavedSearchRepository>>) -> Result<HttpResponse, ApiError> {
+  let searches = saved_search_repository.all().await?.into_iter().map(SavedSearch::from).collect();
+
+  Ok(HttpResponse::Ok().json(ListSavedSearchesResponse { searches }))
+}
+
+#[utoipa::path(
+  request_body = CreateSavedSearchRequest,
+  responses(
+    (status = 200, description = "Saved search created", body = SavedSearchResponse),
+    (status = 400, description = "Invalid name, query or tag", body = ErrorResponse),
+  )
+)]
+#[post("/saved-searches")]
+pub(super) async fn create_saved_search(saved_search_repository: Data<Box<dyn SavedSearchRepository>>, config: Data<Config>, request: Json<CreateSavedSearchRequest>) -> Result<HttpResponse, ApiError> {
+  let name = validate_name(&request.name)?;
+  let query = normalize_search_query(&request.query, config.max_search_query_length)?;
+  if let Some(tag) = &request.tag {
+    NoteTags { tags: vec![tag.clone()] }.validate()?;
+  }
+
+  let db_search = saved_search_repository.create(&db::NewSavedSearch {
+    name,
+    query,
+    tag: request.tag.clone(),
+    sort: SearchSort::from(request.sort).as_str().to_string(),
+    created_at: chrono::offset::Utc::now().naive_utc().to_string(),
+  }).await?;
+
+  Ok(HttpResponse::Ok().json(SavedSearchResponse { search: SavedSearch::from(db_search) }))
+}
+
+#[utoipa::path(
+  responses(
+    (status = 200, description = "Notes matching the saved search", body = SavedSearchResultsResponse),
+    (status = 404, description = "Saved search not found by id", body = MessageResponse),
+  ),
+  params(
+    ("id", description = "Id of the saved search"),
+    SavedSearchResultsQuery
+  )
+)]
+#[get("/saved-searches/{id}/results")]
+pub(super) async fn saved_search_results(id: Path<i64>, saved_search_repository: Data<Box<dyn SavedSearchRepository>>, note_service: Data<Box<dyn NoteService>>, config: Data<Config>, query: Query<SavedSearchResultsQuery>) -> Result<HttpResponse, ApiError> {
+  let saved_search = saved_search_repository.get(*id).await?;
+  let filter = SearchFilter {
+    query: saved_search.query,
+    tag: saved_search.tag,
+    sort: saved_search.sort.parse()?,
+  };
+  let limit = config.search_limit(query.limit);
+  let offset = query.offset.unwrap_or(0).max(0);
+  let notes = note_service.search_by(&filter, limit, offset).await?.into_iter().map(Note::from).collect();
+
+  Ok(HttpResponse::Ok().json(SavedSearchResultsResponse { notes, limit, offset }))
+}
+
+#[cfg(test)]
+mod tests {
+  use actix_web::test;
+  use super::*;
+  use crate::mocks::MockService;
+  use anyhow::Result;
+  use async_trait::async_trait;
+  use actix_web::App;
+  use mockall::{mock, predicate};
+
+  mock! {
+    SavedSearches {}
+    #[async_trait]
+    impl db::SavedSearchRepository for SavedSearches {
+      async fn create(&self, search: &db::NewSavedSearch) -> Result<db::SavedSearch>;
+      async fn all(&self) -> Result<Vec<db::SavedSearch>>;
+      async fn get(&self, id: i64) -> Result<db::SavedSearch>;
+    }
+  }
+
+  fn saved_search(search: &db::NewSavedSearch) -> db::SavedSearch {
+    db::SavedSearch {
+      id: 1,
+      name: search.name.clone(),
+      query: search.query.clone(),
+      tag: search.tag.clone(),
+      sort: search.sort.clone(),
+      created_at: search.created_at.clone(),
+    }
+  }
+
+  #[actix_web::test]
+  async fn test_create_saved_search() {
+    let mut mock_searches = MockSavedSearches::new();
+
+    mock_searches.expect_create()
+      .withf(|search| search.name == "Work todos" && search.query == "todo list" && search.sort == "title")
+      .times(1)
+      .returning(|search| Ok(saved_search(search)));
+
+    let searches_data = Data::new(Box::new(mock_searches) as Box<dyn SavedSearchRepository>);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(Config::default()))
+        .configure(configure(searches_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::post()
+      .uri("/saved-searches")
+      .set_json(serde_json::json!({"name": " Work todos ", "query": "todo   list", "tag": "work", "sort": "title"}))
+      .to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: SavedSearchResponse = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned.search.id, 1);
+    assert_eq!(returned.search.tag, Some(String::from("work")));
+  }
+
+  #[actix_web::test]
+  async fn test_saved_search_results() {
+    let mut mock_searches = MockSavedSearches::new();
+    let mut mock_service = MockService::new();
+
+    mock_searches.expect_get()
+      .with(predicate::eq(1))
+      .times(1)
+      .returning(|_| Ok(db::SavedSearch {
+        id: 1,
+        name: String::from("Work todos"),
+        query: String::from("todo"),
+        tag: Some(String::from("work")),
+        sort: String::from("oldest"),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+      }));
+    mock_service.expect_search_by()
+      .withf(|filter, _, offset| {
+        filter.query == "todo" && filter.tag.as_deref() == Some("work") && filter.sort == SearchSort::Oldest && *offset == 0
+      })
+      .times(1)
+      .returning(|_, _, _| Ok(vec![db::Note {
+        id: String::from("some-id"),
+        title: String::from("Release todo"),
+        content: String::from("Ship it."),
+        created_at: String::from("2021-01-01T00:00:00Z"),
+        updated_at: String::from("2021-01-01T00:00:00Z"),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }]));
+
+    let searches_data = Data::new(Box::new(mock_searches) as Box<dyn SavedSearchRepository>);
+    let note_service_data = Data::new(Box::new(mock_service) as Box<dyn NoteService>);
+
+    let mut app = test::init_service(
+      App::new()
+        .app_data(Data::new(Config::default()))
+        .app_data(note_service_data.clone())
+        .configure(configure(searches_data.clone()))
+    ).await;
+
+    let req = test::TestRequest::get().uri("/saved-searches/1/results").to_request();
+    let resp = test::call_service(&mut app, req).await;
+
+    assert!(resp.status().is_success());
+
+    let body = test::read_body(resp).await;
+    let returned: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(returned["notes"].as_array().unwrap().len(), 1);
+    assert_eq!(returned["notes"][0]["id"], "some-id");
+  }
+}
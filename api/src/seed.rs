@@ -0,0 +1,83 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use service::NoteService;
+
+/// A note in the seed file. Missing ids and creation times are generated.
+#[derive(Deserialize)]
+struct SeedNote {
+  id: Option<String>,
+  title: String,
+  content: String,
+  created_at: Option<String>,
+}
+
+/// Loads the notes in the JSON array at `path`, unless the database already contains notes.
+/// Returns the number of notes created.
+pub(crate) async fn seed(note_service: &dyn NoteService, path: &str) -> Result<usize> {
+  if note_service.exists_any().await? {
+    return Ok(0);
+  }
+
+  let file = fs::read_to_string(path).with_context(|| format!("Failed to read seed file {}", path))?;
+  let seed_notes: Vec<SeedNote> = serde_json::from_str(&file).with_context(|| format!("Invalid seed file {}", path))?;
+  let now = chrono::offset::Utc::now().naive_utc().to_string();
+  let new_notes: Vec<db::NewNote> = seed_notes
+    .into_iter()
+    .map(|note| db::NewNote {
+      // Left empty so the service generates it, retrying should it be taken
+      id: note.id.unwrap_or_default(),
+      title: note.title,
+      content: note.content,
+      created_at: note.created_at.unwrap_or_else(|| now.clone()),
+      expires_at: None,
+      format: db::NoteFormat::Markdown.as_str().to_string(),
+    })
+    .collect();
+
+  Ok(note_service.create_many(&new_notes).await?.len())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mocks::MockService;
+  use uuid::Uuid;
+
+  fn seed_file() -> String {
+    let path = std::env::temp_dir().join(format!("seed-{}.json", Uuid::new_v4()));
+    fs::write(&path, r#"[{"title": "Welcome", "content": "First note."}, {"id": "fixed-id", "title": "Second", "content": "Second note."}]"#).unwrap();
+    path.to_string_lossy().into_owned()
+  }
+
+  #[actix_web::test]
+  async fn test_seed_empty_database() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_exists_any().times(1).returning(|| Ok(false));
+    mock_service.expect_create_many()
+      .withf(|notes| notes.len() == 2 && notes[0].id.is_empty() && notes[1].id == "fixed-id" && notes[0].title == "Welcome")
+      .times(1)
+      .returning(|notes| Ok(notes.iter().map(|note| db::Note {
+        id: note.id.clone(),
+        title: note.title.clone(),
+        content: note.content.clone(),
+        created_at: note.created_at.clone(),
+        updated_at: note.created_at.clone(),
+        version: 1,
+        expires_at: None,
+        format: String::from("markdown"),
+      }).collect()));
+
+    assert_eq!(seed(&mock_service, &seed_file()).await.unwrap(), 2);
+  }
+
+  #[actix_web::test]
+  async fn test_seed_is_skipped_when_notes_exist() {
+    let mut mock_service = MockService::new();
+    mock_service.expect_exists_any().times(1).returning(|| Ok(true));
+    mock_service.expect_create_many().times(0);
+
+    assert_eq!(seed(&mock_service, &seed_file()).await.unwrap(), 0);
+  }
+}
@@ -0,0 +1,63 @@
+/// Marks placed around the matched text in a snippet.
+const MARK: &str = "**";
+
+/// Cuts about `length` characters of `text` around the first case-insensitive match of `query`, with
+/// the match wrapped in `**` and `…` where text was left out. `None` when `text` doesn't contain `query`.
+/// Case is folded for ASCII only, like SQLite's `LIKE`. Cuts fall between characters rather than at word
+/// boundaries, so a long unbroken token such as a base64 blob is still cut to size.
+pub(crate) fn highlight(text: &str, query: &str, length: usize) -> Option<String> {
+  let chars: Vec<char> = text.chars().collect();
+  let needle: Vec<char> = query.chars().collect();
+  if needle.is_empty() || needle.len() > chars.len() {
+    return None;
+  }
+  let start = chars
+    .windows(needle.len())
+    .position(|window| window.iter().zip(&needle).all(|(a, b)| a.eq_ignore_ascii_case(b)))?;
+  let end = start + needle.len();
+
+  let context = length.saturating_sub(needle.len()) / 2;
+  let from = start.saturating_sub(context);
+  let to = (end + context).min(chars.len());
+  let mut snippet = String::new();
+  if from > 0 {
+    snippet.push('…');
+  }
+  snippet.extend(&chars[from..start]);
+  snippet.push_str(MARK);
+  snippet.extend(&chars[start..end]);
+  snippet.push_str(MARK);
+  snippet.extend(&chars[end..to]);
+  if to < chars.len() {
+    snippet.push('…');
+  }
+
+  Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_highlight() {
+    let text = "Remember to buy milk and bread on the way home from work.";
+
+    assert_eq!(highlight(text, "MILK", 20).unwrap(), "… to buy **milk** and bre…");
+    assert_eq!(highlight(text, "remember", 20).unwrap(), "**Remember** to bu…");
+    assert_eq!(highlight(text, "work.", 200).unwrap(), "Remember to buy milk and bread on the way home from **work.**");
+    assert!(highlight(text, "cheese", 20).is_none());
+  }
+
+  #[test]
+  fn test_highlight_giant_token() {
+    let blob = format!("{}needle{}", "QUJD".repeat(12_500), "QUJD".repeat(12_500));
+
+    let snippet = highlight(&blob, "needle", 20).unwrap();
+    assert_eq!(snippet, "…UJDQUJD**needle**QUJDQUJ…");
+
+    let wide = format!("{}ü{}", "é".repeat(25_000), "é".repeat(25_000));
+    assert_eq!(highlight(&wide, "Ü", 9), None);
+    assert_eq!(highlight(&wide, "ü", 9).unwrap(), "…éééé**ü**éééé…");
+  }
+}
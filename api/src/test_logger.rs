@@ -0,0 +1,45 @@
+//! Process-wide logger for tests that assert on log output. `log` accepts a single logger per process, so every
+//! test module installs this one through `init` instead of its own.
+
+use std::sync::{Mutex, Once};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+static LINES: Mutex<Vec<(Level, String)>> = Mutex::new(Vec::new());
+
+static INIT: Once = Once::new();
+
+struct CaptureLogger;
+
+impl Log for CaptureLogger {
+  fn enabled(&self, metadata: &Metadata) -> bool {
+    metadata.level() <= Level::Info
+  }
+
+  fn log(&self, record: &Record) {
+    if self.enabled(record.metadata()) {
+      LINES.lock().unwrap().push((record.level(), record.args().to_string()));
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+static LOGGER: CaptureLogger = CaptureLogger;
+
+/// Installs the capture logger, once per test binary.
+pub(crate) fn init() {
+  INIT.call_once(|| {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+  });
+}
+
+/// Messages captured so far at `level` or more severe, across all tests.
+pub(crate) fn lines(level: Level) -> Vec<String> {
+  LINES.lock().unwrap()
+    .iter()
+    .filter(|(logged_level, _)| *logged_level <= level)
+    .map(|(_, line)| line.clone())
+    .collect()
+}
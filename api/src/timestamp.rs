@@ -0,0 +1,128 @@
+use std::cell::Cell;
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// How `created_at` and `updated_at` are written in responses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum TimestampFormat {
+  /// `2021-01-01T00:00:00Z`
+  #[default]
+  Rfc3339,
+  /// Milliseconds since the Unix epoch, as a JSON number
+  EpochMillis,
+}
+
+impl FromStr for TimestampFormat {
+  type Err = anyhow::Error;
+
+  fn from_str(value: &str) -> anyhow::Result<Self> {
+    match value {
+      "rfc3339" => Ok(TimestampFormat::Rfc3339),
+      "epoch-millis" => Ok(TimestampFormat::EpochMillis),
+      _ => Err(anyhow!("unknown timestamp format: {}", value)),
+    }
+  }
+}
+
+thread_local! {
+  // Responses are serialized on the worker thread handling the request, so each worker sets this once on startup.
+  static FORMAT: Cell<TimestampFormat> = Cell::new(TimestampFormat::default());
+}
+
+/// Sets the timestamp format used by responses serialized on the current thread.
+pub(crate) fn set_format(format: TimestampFormat) {
+  FORMAT.with(|current| current.set(format));
+}
+
+/// Reads timestamps as stored, either RFC 3339 or chrono's naive `2021-01-01 00:00:00.123` in UTC.
+fn parse(stored: &str) -> Option<DateTime<Utc>> {
+  DateTime::parse_from_rfc3339(stored)
+    .map(|timestamp| timestamp.with_timezone(&Utc))
+    .ok()
+    .or_else(|| NaiveDateTime::parse_from_str(stored, "%Y-%m-%d %H:%M:%S%.f").ok().map(|naive| Utc.from_utc_datetime(&naive)))
+}
+
+/// Converts a timestamp received from a client into the naive UTC format notes are stored in,
+/// so it compares correctly with stored timestamps.
+pub(crate) fn to_stored(value: &str) -> Option<String> {
+  parse(value).map(|timestamp| timestamp.naive_utc().to_string())
+}
+
+/// `serialize_with` helper writing a stored timestamp in the configured format.
+/// Values that can't be parsed are written unchanged.
+pub(crate) fn serialize<S: Serializer>(stored: &str, serializer: S) -> Result<S::Ok, S::Error> {
+  match (FORMAT.with(Cell::get), parse(stored)) {
+    (TimestampFormat::EpochMillis, Some(timestamp)) => serializer.serialize_i64(timestamp.timestamp_millis()),
+    (TimestampFormat::Rfc3339, Some(timestamp)) => serializer.serialize_str(&timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    (_, None) => serializer.serialize_str(stored),
+  }
+}
+
+/// `deserialize_with` helper accepting either format. Epoch milliseconds are normalized to RFC 3339.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Raw {
+    Millis(i64),
+    Text(String),
+  }
+
+  match Raw::deserialize(deserializer)? {
+    Raw::Text(text) => Ok(text),
+    Raw::Millis(millis) => Utc.timestamp_millis_opt(millis)
+      .single()
+      .map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+      .ok_or_else(|| serde::de::Error::custom(format!("timestamp out of range: {}", millis))),
+  }
+}
+
+/// `serialize_with` helper for optional timestamps.
+pub(crate) fn serialize_option<S: Serializer>(stored: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+  match stored {
+    Some(stored) => serialize(stored, serializer),
+    None => serializer.serialize_none(),
+  }
+}
+
+/// `deserialize_with` helper for optional timestamps.
+pub(crate) fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+  #[derive(Deserialize)]
+  struct Timestamp(#[serde(deserialize_with = "deserialize")] String);
+
+  Ok(Option::<Timestamp>::deserialize(deserializer)?.map(|Timestamp(value)| value))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde::Serialize;
+
+  #[derive(Serialize, Deserialize, Debug, PartialEq)]
+  struct Stamped {
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    created_at: String,
+  }
+
+  #[test]
+  fn test_rfc3339() {
+    set_format(TimestampFormat::Rfc3339);
+    let stamped = Stamped { created_at: String::from("2021-01-01 00:00:00.250") };
+
+    assert_eq!(serde_json::to_value(&stamped).unwrap()["created_at"], "2021-01-01T00:00:00.250Z");
+  }
+
+  #[test]
+  fn test_epoch_millis_round_trip() {
+    set_format(TimestampFormat::EpochMillis);
+    let stamped = Stamped { created_at: String::from("2021-01-01T00:00:00.250Z") };
+
+    let json = serde_json::to_string(&stamped).unwrap();
+    assert_eq!(json, r#"{"created_at":1609459200250}"#);
+
+    let parsed: Stamped = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, stamped);
+  }
+}
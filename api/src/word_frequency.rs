@@ -0,0 +1,48 @@
+use std::collections::{HashMap, HashSet};
+
+/// Words ignored by `GET /notes/word-frequency` unless `STOPWORDS` is set.
+pub(crate) const DEFAULT_STOPWORDS: &[&str] = &[
+  "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "i", "in",
+  "is", "it", "its", "me", "my", "not", "of", "on", "or", "so", "that", "the", "this", "to", "was",
+  "we", "were", "will", "with", "you", "your",
+];
+
+/// Counts lowercased words across `contents` and returns the `top` most frequent ones,
+/// most frequent first, ties broken alphabetically.
+pub(crate) fn top_words<'a>(contents: impl IntoIterator<Item = &'a str>, stopwords: &HashSet<String>, top: usize) -> Vec<(String, usize)> {
+  let mut counts: HashMap<String, usize> = HashMap::new();
+  for content in contents {
+    for word in content.split(|c: char| !c.is_alphanumeric() && c != '\'') {
+      let word = word.trim_matches('\'').to_lowercase();
+      if word.is_empty() || stopwords.contains(&word) {
+        continue;
+      }
+      *counts.entry(word).or_default() += 1;
+    }
+  }
+
+  let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+  counts.sort_by(|(a_word, a_count), (b_word, b_count)| b_count.cmp(a_count).then_with(|| a_word.cmp(b_word)));
+  counts.truncate(top);
+
+  counts
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_top_words() {
+    let stopwords = DEFAULT_STOPWORDS.iter().map(|word| word.to_string()).collect();
+    let contents = ["Buy milk and bread.", "MILK is out; don't forget the milk!", "bread"];
+
+    let words = top_words(contents, &stopwords, 3);
+
+    assert_eq!(words, vec![
+      (String::from("milk"), 3),
+      (String::from("bread"), 2),
+      (String::from("buy"), 1),
+    ]);
+  }
+}
@@ -0,0 +1,4 @@
+ALTER TABLE note ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+ALTER TABLE note ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+UPDATE note SET updated_at = created_at WHERE updated_at = '';
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS audit_log
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    operation  TEXT NOT NULL,
+    note_id    TEXT NOT NULL,
+    user_id    TEXT,
+    created_at TEXT NOT NULL
+);
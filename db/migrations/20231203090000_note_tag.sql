@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS note_tag
+(
+    note_id TEXT NOT NULL,
+    tag     TEXT NOT NULL,
+    PRIMARY KEY (note_id, tag)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS saved_search
+(
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    name       TEXT NOT NULL,
+    query      TEXT NOT NULL,
+    tag        TEXT,
+    sort       TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE note ADD COLUMN expires_at TEXT;
+
+CREATE INDEX IF NOT EXISTS note_expires_at ON note (expires_at);
@@ -0,0 +1 @@
+ALTER TABLE note ADD COLUMN format TEXT NOT NULL DEFAULT 'markdown';
@@ -0,0 +1,4 @@
+-- note.id was never declared unique, so SQLite rejects foreign keys that reference it and accepts a
+-- taken id twice. Keep the first row of each id before enforcing uniqueness.
+DELETE FROM note WHERE rowid NOT IN (SELECT MIN(rowid) FROM note GROUP BY id);
+CREATE UNIQUE INDEX IF NOT EXISTS note_id ON note (id);
@@ -0,0 +1,11 @@
+-- Tags of deleted notes were left behind. Rebuild note_tag with a foreign key so they are removed
+-- together with their note, dropping the rows that are already orphaned.
+CREATE TABLE note_tag_new
+(
+    note_id TEXT NOT NULL REFERENCES note (id) ON DELETE CASCADE,
+    tag     TEXT NOT NULL,
+    PRIMARY KEY (note_id, tag)
+);
+INSERT INTO note_tag_new (note_id, tag) SELECT note_id, tag FROM note_tag WHERE note_id IN (SELECT id FROM note);
+DROP TABLE note_tag;
+ALTER TABLE note_tag_new RENAME TO note_tag;
@@ -0,0 +1,9 @@
+-- How often and when a note was last opened with GET /notes/{id}?track=true. Kept apart from note
+-- so reading a note never bumps its version or updated_at.
+CREATE TABLE IF NOT EXISTS note_view
+(
+    note_id        TEXT    NOT NULL PRIMARY KEY REFERENCES note (id) ON DELETE CASCADE,
+    view_count     INTEGER NOT NULL,
+    last_viewed_at TEXT    NOT NULL
+);
+CREATE INDEX IF NOT EXISTS note_view_last_viewed_at ON note_view (last_viewed_at);
@@ -0,0 +1,3 @@
+-- Groups the mutations of one editing session, as named by the X-Session-Id header.
+ALTER TABLE audit_log ADD COLUMN session_id TEXT;
+CREATE INDEX IF NOT EXISTS audit_log_session_id ON audit_log (session_id);
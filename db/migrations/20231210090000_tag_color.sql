@@ -0,0 +1,6 @@
+-- Display color per tag name, independent of which notes carry the tag
+CREATE TABLE IF NOT EXISTS tag_color
+(
+    tag   TEXT NOT NULL PRIMARY KEY,
+    color TEXT NOT NULL
+);
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePool;
+
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub operation: String,
+    pub note_id: String,
+    pub user_id: Option<String>,
+    pub created_at: String,
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NewAuditEntry {
+    pub operation: String,
+    pub note_id: String,
+    pub user_id: Option<String>,
+    pub created_at: String,
+    /// Editing session the mutation was made in, if the client named one.
+    pub session_id: Option<String>,
+}
+
+#[async_trait]
+pub trait AuditRepository: Sync + Send {
+    async fn record(&self, entry: &NewAuditEntry) -> Result<AuditEntry>;
+    async fn recent(&self, limit: i64) -> Result<Vec<AuditEntry>>;
+    async fn by_session(&self, session_id: &str) -> Result<Vec<AuditEntry>>;
+}
+
+#[derive(Clone)]
+pub struct SqliteAuditRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteAuditRepository {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url).await?;
+
+        Ok(SqliteAuditRepository { pool })
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        SqliteAuditRepository { pool }
+    }
+}
+
+#[async_trait]
+impl AuditRepository for SqliteAuditRepository {
+    async fn record(&self, entry: &NewAuditEntry) -> Result<AuditEntry> {
+        let recorded = sqlx::query_as!(
+            AuditEntry,
+            "INSERT INTO audit_log (operation, note_id, user_id, created_at, session_id) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            entry.operation, entry.note_id, entry.user_id, entry.created_at, entry.session_id
+        )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(recorded)
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<AuditEntry>> {
+        let entries = sqlx::query_as!(AuditEntry, "SELECT * FROM audit_log ORDER BY id DESC LIMIT ?", limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(entries)
+    }
+
+    async fn by_session(&self, session_id: &str) -> Result<Vec<AuditEntry>> {
+        let entries = sqlx::query_as!(AuditEntry, "SELECT * FROM audit_log WHERE session_id = ? ORDER BY id", session_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MIGRATOR;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    fn entry(note_id: &str, session_id: Option<&str>) -> NewAuditEntry {
+        NewAuditEntry {
+            operation: String::from("update"),
+            note_id: String::from(note_id),
+            user_id: None,
+            created_at: String::from("2021-01-01 00:00:00"),
+            session_id: session_id.map(String::from),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_by_session() {
+        let pool = SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        let repository = SqliteAuditRepository { pool };
+        repository.record(&entry("1", Some("session-a"))).await.unwrap();
+        repository.record(&entry("2", Some("session-b"))).await.unwrap();
+        repository.record(&entry("3", None)).await.unwrap();
+        repository.record(&entry("4", Some("session-a"))).await.unwrap();
+
+        let changes = repository.by_session("session-a").await.unwrap();
+
+        let note_ids: Vec<&str> = changes.iter().map(|entry| entry.note_id.as_str()).collect();
+        assert_eq!(note_ids, vec!["1", "4"]);
+        assert!(repository.by_session("unknown").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+
+/// Writes a consistent copy of the database at `database_url` to a new, timestamped file in `dir`
+/// using `VACUUM INTO`, and returns its path. The directory is created when missing.
+pub async fn backup(database_url: &str, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create backup directory {}", dir.display()))?;
+    let timestamp = chrono::offset::Utc::now().format("%Y%m%d-%H%M%S%.3f");
+    let path = dir.join(format!("notes-{}.db", timestamp));
+
+    let mut connection = SqliteConnectOptions::from_str(database_url)?.connect().await?;
+    sqlx::query("VACUUM INTO ?")
+        .bind(path.to_string_lossy().as_ref())
+        .execute(&mut connection)
+        .await
+        .with_context(|| format!("failed to back up the database to {}", path.display()))?;
+    connection.close().await?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{NewNote, NoteRepository, SqliteNoteRepository, MIGRATOR};
+
+    #[tokio::test]
+    async fn test_backup_can_be_opened_and_queried() {
+        let dir = std::env::temp_dir().join(format!("backup-test-{}-{}", std::process::id(), chrono::offset::Utc::now().timestamp_millis()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let database_url = format!("sqlite://{}?mode=rwc", dir.join("notes.db").display());
+        let repository = SqliteNoteRepository::new(&database_url).await.unwrap();
+        MIGRATOR.run(&repository.pool).await.unwrap();
+        repository.create(&NewNote {
+            id: String::from("1"),
+            title: String::from("Backed up"),
+            content: String::from("Survives a redeploy."),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await.unwrap();
+
+        let path = backup(&database_url, &dir.join("backups")).await.unwrap();
+
+        let copy = SqliteNoteRepository::new(&format!("sqlite://{}", path.display())).await.unwrap();
+        assert_eq!(copy.get("1").await.unwrap().title, "Backed up");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
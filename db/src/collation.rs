@@ -0,0 +1,81 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+use icu_collator::{Collator, CollatorOptions};
+use icu_locid::Locale;
+use sqlx::sqlite::SqliteConnectOptions;
+
+/// Name of the SQLite collation that orders titles by the configured locale, as used in
+/// `ORDER BY title COLLATE title_locale`.
+pub const TITLE_COLLATION: &str = "title_locale";
+
+/// Collator behind `TITLE_COLLATION`. Falls back to the root locale until a locale is set on startup.
+static TITLE_COLLATOR: RwLock<Option<Arc<Collator>>> = RwLock::new(None);
+
+/// Sorts titles by the rules of `locale`, a BCP 47 tag such as `en`, `de` or `sv`.
+pub fn set_title_locale(locale: &str) -> Result<()> {
+    let collator = collator(locale)?;
+    *TITLE_COLLATOR.write().unwrap_or_else(|error| error.into_inner()) = Some(Arc::new(collator));
+
+    Ok(())
+}
+
+/// Options for a connection to `database_url` that has the title collation registered.
+pub(crate) fn connect_options(database_url: &str) -> Result<SqliteConnectOptions> {
+    Ok(SqliteConnectOptions::from_str(database_url)?.collation(TITLE_COLLATION, compare_titles))
+}
+
+fn collator(locale: &str) -> Result<Collator> {
+    let locale = Locale::from_str(locale).map_err(|error| anyhow!("invalid locale {:?}: {}", locale, error))?;
+
+    Collator::try_new(&(&locale).into(), CollatorOptions::new())
+        .map_err(|error| anyhow!("no collation data for locale {}: {}", locale, error))
+}
+
+fn compare_titles(left: &str, right: &str) -> Ordering {
+    let current = TITLE_COLLATOR.read().unwrap_or_else(|error| error.into_inner()).clone();
+    let collator = match current {
+        Some(collator) => collator,
+        None => {
+            let collator = Arc::new(collator("und").expect("root collation data is compiled in"));
+            *TITLE_COLLATOR.write().unwrap_or_else(|error| error.into_inner()) = Some(collator.clone());
+            collator
+        }
+    };
+
+    collator.compare(left, right)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted(locale: &str, titles: &[&str]) -> Vec<String> {
+        let collator = collator(locale).unwrap();
+        let mut titles: Vec<String> = titles.iter().map(|title| title.to_string()).collect();
+        titles.sort_by(|left, right| collator.compare(left, right));
+        titles
+    }
+
+    #[test]
+    fn test_accented_titles_sort_next_to_unaccented() {
+        assert_eq!(
+            sorted("en", &["Zebra", "\u{e9}cole", "Eagle", "apple", "ecology"]),
+            vec!["apple", "Eagle", "\u{e9}cole", "ecology", "Zebra"]
+        );
+    }
+
+    #[test]
+    fn test_sort_follows_locale() {
+        // German sorts ö with o; Swedish sorts it as its own letter after z
+        assert_eq!(sorted("de", &["zebra", "\u{f6}l", "ost"]), vec!["\u{f6}l", "ost", "zebra"]);
+        assert_eq!(sorted("sv", &["zebra", "\u{f6}l", "ost"]), vec!["ost", "zebra", "\u{f6}l"]);
+    }
+
+    #[test]
+    fn test_invalid_locale() {
+        assert!(set_title_locale("not a locale!").is_err());
+    }
+}
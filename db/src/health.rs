@@ -0,0 +1,138 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqlitePool;
+
+/// Migrations shipped with this crate, used to tell whether the database schema is current.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HealthStatus {
+    pub backend: String,
+    pub applied_migrations: Vec<i64>,
+    pub pending_migrations: Vec<i64>,
+    pub note_count: i64,
+}
+
+/// Space taken up by the note database, as reported by `HealthRepository::storage`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StorageFootprint {
+    /// Size of the whole database file, including tags, audit entries and free pages
+    pub database_bytes: i64,
+    /// Notes stored, including expired ones the reaper hasn't deleted yet
+    pub note_count: i64,
+    /// UTF-8 bytes of all note content together
+    pub content_bytes: i64,
+}
+
+#[async_trait]
+pub trait HealthRepository: Sync + Send {
+    async fn status(&self) -> Result<HealthStatus>;
+    /// Applies pending migrations and returns the versions that were applied.
+    async fn migrate(&self) -> Result<Vec<i64>>;
+    async fn storage(&self) -> Result<StorageFootprint>;
+}
+
+#[derive(Clone)]
+pub struct SqliteHealthRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteHealthRepository {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url).await?;
+
+        Ok(SqliteHealthRepository { pool })
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        SqliteHealthRepository { pool }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for SqliteHealthRepository {
+    async fn status(&self) -> Result<HealthStatus> {
+        // The bookkeeping table only exists once `sqlx migrate run` has been executed at least once,
+        // so a failing query means nothing has been applied yet.
+        let applied_migrations: Vec<i64> = sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1 ORDER BY version")
+            .fetch_all(&self.pool)
+            .await
+            .unwrap_or_default();
+        let pending_migrations = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .filter(|version| !applied_migrations.contains(version))
+            .collect();
+        let note_count = if applied_migrations.is_empty() {
+            0
+        } else {
+            sqlx::query_scalar!("SELECT COUNT(*) AS \"count!: i64\" FROM note")
+                .fetch_one(&self.pool)
+                .await?
+        };
+
+        Ok(HealthStatus {
+            backend: String::from("sqlite"),
+            applied_migrations,
+            pending_migrations,
+            note_count,
+        })
+    }
+
+    async fn migrate(&self) -> Result<Vec<i64>> {
+        let pending_migrations = self.status().await?.pending_migrations;
+        MIGRATOR.run(&self.pool).await?;
+
+        Ok(pending_migrations)
+    }
+
+    async fn storage(&self) -> Result<StorageFootprint> {
+        let storage = sqlx::query!(
+            "SELECT (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()) AS \"database_bytes!: i64\", COUNT(*) AS \"note_count!: i64\", COALESCE(SUM(length(CAST(content AS BLOB))), 0) AS \"content_bytes!: i64\" FROM note"
+        )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(StorageFootprint {
+            database_bytes: storage.database_bytes,
+            note_count: storage.note_count,
+            content_bytes: storage.content_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_storage() {
+        // A single, never recycled connection keeps the in-memory database alive for the whole test.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        for (id, content) in [("1", "This is note #1."), ("2", "café")] {
+            sqlx::query("INSERT INTO note (id, title, content, created_at, updated_at) VALUES ($1, 'Title', $2, '2021-01-01 00:00:00', '2021-01-01 00:00:00')")
+                .bind(id)
+                .bind(content)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let repository = SqliteHealthRepository { pool };
+
+        let storage = repository.storage().await.unwrap();
+        assert!(storage.database_bytes > 0);
+        assert_eq!(storage.note_count, 2);
+        assert_eq!(storage.content_bytes, 16 + 5);
+    }
+}
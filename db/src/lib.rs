@@ -1,101 +1,667 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use sqlx::FromRow;
-use sqlx::sqlite::SqlitePool;
+use sqlx::{FromRow, QueryBuilder, Row, Sqlite};
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use unicode_segmentation::UnicodeSegmentation;
+use validator::{ValidationError, ValidationErrors};
 use validator_derive::Validate;
 
+pub use audit::{AuditEntry, AuditRepository, NewAuditEntry, SqliteAuditRepository};
+pub use backup::backup;
+pub use collation::set_title_locale;
+pub use health::{HealthRepository, HealthStatus, SqliteHealthRepository, StorageFootprint, MIGRATOR};
+pub use raw::{RawColumn, RawValue};
+pub use redact::set_redact_content;
+pub use replicated::ReplicatedNoteRepository;
+pub use retry::connect_with_retry;
+pub use saved_search::{NewSavedSearch, SavedSearch, SavedSearchRepository, SqliteSavedSearchRepository};
+pub use self_test::self_test;
+pub use slow_query::SlowQueryLoggingRepository;
+
+mod audit;
+mod backup;
+mod collation;
+mod health;
+#[cfg(test)]
+mod mocks;
+mod raw;
+mod redact;
+mod replicated;
+mod retry;
+mod saved_search;
+mod self_test;
+mod slow_query;
+
 #[derive(Debug, thiserror::Error)]
 pub enum DbError {
     #[error("NotFound")]
     NotFound,
 
+    #[error("Conflict")]
+    Conflict,
+
+    #[error("IdTaken")]
+    IdTaken,
+
     #[error(transparent)]
     SqlxError(#[from] sqlx::Error)
 }
 
-#[derive(Debug, FromRow, PartialEq, Eq)]
+#[derive(FromRow, PartialEq, Eq)]
 pub struct Note {
     pub id: String,
     pub title: String,
     pub content: String,
     pub created_at: String,
+    pub updated_at: String,
+    pub version: i64,
+    /// Expired notes are hidden from reads and removed by `delete_expired`.
+    pub expires_at: Option<String>,
+    /// Name of the `NoteFormat` of the content.
+    pub format: String,
+}
+
+/// Lightweight projection of a note used by autocomplete.
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct NoteTitle {
+    pub id: String,
+    pub title: String,
+}
+
+/// A note opened with view tracking, as returned by `NoteRepository::recently_viewed`.
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct ViewedNote {
+    pub id: String,
+    pub title: String,
+    pub view_count: i64,
+    pub last_viewed_at: String,
+}
+
+/// The notes created right before and right after a note, as returned by `NoteRepository::neighbors`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NoteNeighbors {
+    pub previous: Option<NoteTitle>,
+    pub next: Option<NoteTitle>,
+}
+
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct NoteTag {
+    pub note_id: String,
+    pub tag: String,
+}
+
+/// Display color set for a tag name, as a `#rrggbb` hex string.
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct TagColor {
+    pub tag: String,
+    pub color: String,
+}
+
+/// Two tags and the number of notes carrying both, as returned by `NoteRepository::tag_co_occurrence`.
+/// `tag` sorts before `other_tag`.
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct TagPair {
+    pub tag: String,
+    pub other_tag: String,
+    pub count: i64,
+}
+
+/// Size of the time buckets notes are counted in by `NoteRepository::activity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivityBucket {
+    Day,
+    Week,
+    Month,
+}
+
+impl ActivityBucket {
+    /// `strftime` format naming the bucket a timestamp falls into.
+    fn format(self) -> &'static str {
+        match self {
+            ActivityBucket::Day => "%Y-%m-%d",
+            ActivityBucket::Week => "%Y-W%W",
+            ActivityBucket::Month => "%Y-%m",
+        }
+    }
+}
+
+/// Text, tag and order of a `NoteRepository::search_by` search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchFilter {
+    pub query: String,
+    pub tag: Option<String>,
+    pub sort: SearchSort,
+}
+
+/// Order of the notes returned by `NoteRepository::search_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSort {
+    Newest,
+    Oldest,
+    Title,
+}
+
+impl SearchSort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SearchSort::Newest => "newest",
+            SearchSort::Oldest => "oldest",
+            SearchSort::Title => "title",
+        }
+    }
+}
+
+impl FromStr for SearchSort {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "newest" => Ok(SearchSort::Newest),
+            "oldest" => Ok(SearchSort::Oldest),
+            "title" => Ok(SearchSort::Title),
+            _ => Err(anyhow::anyhow!("unknown sort: {}", value)),
+        }
+    }
+}
+
+/// How note content is written, and so how it is rendered and exported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteFormat {
+    #[default]
+    Markdown,
+    Plain,
+    Html,
+}
+
+impl NoteFormat {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteFormat::Markdown => "markdown",
+            NoteFormat::Plain => "plain",
+            NoteFormat::Html => "html",
+        }
+    }
+}
+
+impl FromStr for NoteFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "markdown" => Ok(NoteFormat::Markdown),
+            "plain" => Ok(NoteFormat::Plain),
+            "html" => Ok(NoteFormat::Html),
+            _ => Err(anyhow::anyhow!("unknown note format: {}", value)),
+        }
+    }
+}
+
+/// Whether `NoteRepository::by_tags` wants notes carrying every given tag or at least one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagMatch {
+    All,
+    Any,
+}
+
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct ActivityCount {
+    pub bucket: String,
+    pub count: i64,
+}
+
+/// Bytes a note's text takes up in storage, as returned by `NoteRepository::storage_size`.
+/// Ids, timestamps and database overhead aren't counted.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct NoteSize {
+    pub title: i64,
+    pub content: i64,
+    /// All of the note's tags together
+    pub tags: i64,
+}
+
+impl NoteSize {
+    pub fn total(&self) -> i64 {
+        self.title + self.content + self.tags
+    }
+}
+
+/// Rows removed by `NoteRepository::repair_orphans`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct RepairReport {
+    /// Tags whose note no longer exists
+    pub orphaned_tags: u64,
 }
 
 #[async_trait]
 pub trait NoteRepository {
     async fn all(&self) -> Result<Vec<Note>>;
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn count(&self) -> Result<i64>;
     async fn get(&self, id: &str) -> Result<Note>;
     async fn create(&self, note: &NewNote) -> Result<Note>;
     async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note>;
     async fn delete(&self, id: &str) -> Result<Note>;
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note>;
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>>;
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>>;
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn tags(&self, id: &str) -> Result<Vec<String>>;
+    async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>>;
+    async fn all_tags(&self) -> Result<Vec<NoteTag>>;
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>>;
+    async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)>;
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>>;
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>>;
+    async fn count_by_tag(&self, tag: &str) -> Result<i64>;
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note>;
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>>;
+    async fn try_get(&self, id: &str) -> Result<Option<Note>>;
+    async fn exists_any(&self) -> Result<bool>;
+    async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>>;
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>>;
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64>;
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>>;
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64>;
+    async fn oldest(&self) -> Result<Option<Note>>;
+    async fn newest(&self) -> Result<Option<Note>>;
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors>;
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>>;
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)>;
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>>;
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>>;
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>>;
+    async fn repair_orphans(&self) -> Result<RepairReport>;
+    async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>>;
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note>;
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>>;
+    async fn id_with_content(&self, content: &str) -> Result<Option<String>>;
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()>;
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>>;
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>>;
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()>;
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool>;
+    async fn tag_colors(&self) -> Result<Vec<TagColor>>;
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>>;
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>>;
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>>;
+    async fn storage_size(&self, id: &str) -> Result<NoteSize>;
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>>;
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>>;
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64>;
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>>;
+    async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>>;
+    async fn get_for_update(&self, id: &str) -> Result<Note>;
+    async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>>;
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
 }
 
-#[derive(Validate, Clone, PartialEq, Debug)]
+pub const TITLE_MIN_LENGTH: usize = 1;
+pub const TITLE_MAX_LENGTH: usize = 50;
+
+/// Checks the title length in grapheme clusters rather than bytes or chars, so a title
+/// like a single family emoji counts as one character, as a user would see it.
+fn validate_title_length(title: &str) -> Result<(), ValidationError> {
+    let length = title.graphemes(true).count();
+    if (TITLE_MIN_LENGTH..=TITLE_MAX_LENGTH).contains(&length) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("length");
+        error.add_param("min".into(), &TITLE_MIN_LENGTH);
+        error.add_param("max".into(), &TITLE_MAX_LENGTH);
+        error.add_param("value".into(), &length);
+        Err(error)
+    }
+}
+
+/// Derives a title from the first non-blank line of `content`, without leading Markdown heading
+/// marks and cut to `TITLE_MAX_LENGTH` graphemes. Returns `None` when the content is blank.
+pub fn title_from_content(content: &str) -> Option<String> {
+    content.lines()
+        .map(|line| line.trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.graphemes(true).take(TITLE_MAX_LENGTH).collect::<String>().trim_end().to_string())
+}
+
+pub const CONTENT_MIN_LENGTH: usize = 1;
+/// Longest note content in characters, enforced on `NewNote`, `UpdateNote` and appends.
+pub const CONTENT_MAX_LENGTH: usize = 200;
+
+fn validate_content_length(content: &str) -> Result<(), ValidationError> {
+    let length = content.chars().count();
+    if (CONTENT_MIN_LENGTH..=CONTENT_MAX_LENGTH).contains(&length) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("length");
+        error.add_param("min".into(), &CONTENT_MIN_LENGTH);
+        error.add_param("max".into(), &CONTENT_MAX_LENGTH);
+        error.add_param("value".into(), &length);
+        Err(error)
+    }
+}
+
+#[derive(Validate, Clone, PartialEq)]
 pub struct NewNote {
     pub id: String,
-    #[validate(length(min = 1, max = 50))]
+    #[validate(custom = "validate_title_length")]
     pub title: String,
-    #[validate(length(min = 1, max = 200))]
+    #[validate(custom = "validate_content_length")]
     pub content: String,
     pub created_at: String,
+    /// UTC timestamp in the same format as `created_at` after which the note is gone.
+    pub expires_at: Option<String>,
+    #[validate(custom = "validate_format")]
+    pub format: String,
+}
+
+fn validate_format(format: &str) -> Result<(), ValidationError> {
+    match NoteFormat::from_str(format) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            let mut error = ValidationError::new("format");
+            error.add_param("value".into(), &format);
+            Err(error)
+        }
+    }
+}
+
+pub const TAG_MAX_LENGTH: usize = 30;
+
+/// Tags are short labels; commas are reserved as the separator in tag filters.
+fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    for tag in tags {
+        let length = tag.graphemes(true).count();
+        if length == 0 || length > TAG_MAX_LENGTH || tag.contains(',') || tag.trim() != tag {
+            let mut error = ValidationError::new("tag");
+            error.add_param("value".into(), tag);
+            error.add_param("max".into(), &TAG_MAX_LENGTH);
+            return Err(error);
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Validate, Clone, PartialEq, Debug)]
+pub struct NoteTags {
+    #[validate(custom = "validate_tags")]
+    pub tags: Vec<String>,
+}
+
+/// One write in a `NoteRepository::transaction`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum NoteWrite {
+    Create(NewNote),
+    Update { id: String, note: UpdateNote },
+    Delete { id: String },
+    ReplaceTags { id: String, tags: Vec<String> },
+}
+
+#[derive(Validate, Clone, PartialEq)]
 pub struct UpdateNote {
-    #[validate(length(min = 1, max = 50))]
+    #[validate(custom = "validate_title_length")]
     pub title: String,
-    #[validate(length(min = 1, max = 200))]
+    #[validate(custom = "validate_content_length")]
     pub content: String,
+    pub updated_at: String,
+    /// Version the client last read. When set, the update only applies if the stored
+    /// note still has this version.
+    pub version: Option<i64>,
 }
 
+/// Writers allowed at once unless configured otherwise. SQLite serializes writes anyway,
+/// so queueing them here avoids "database is locked" errors under load.
+pub const DEFAULT_MAX_CONCURRENT_WRITES: usize = 1;
+
+#[derive(Clone)]
 pub struct SqliteNoteRepository {
     pool: SqlitePool,
+    write_permits: Arc<Semaphore>,
 }
 
 impl SqliteNoteRepository {
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = SqlitePool::connect(database_url).await?;
+        let pool = SqlitePool::connect_with(collation::connect_options(database_url)?).await?;
 
-        Ok(SqliteNoteRepository { pool })
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Like `new`, but opens `min_connections` connections up front and checks each with a test query,
+    /// so the first requests don't wait for connections to be established.
+    pub async fn new_with_min_connections(database_url: &str, min_connections: u32) -> Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .min_connections(min_connections)
+            .max_connections(min_connections.max(10))
+            .connect_with(collation::connect_options(database_url)?)
+            .await?;
+        // Holding every connection until all are open forces the pool to create new ones
+        let mut connections = Vec::with_capacity(min_connections as usize);
+        for _ in 0..min_connections {
+            let mut connection = pool.acquire().await?;
+            sqlx::query("SELECT 1").execute(&mut *connection).await?;
+            connections.push(connection);
+        }
+
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Opens a repository backed by one connection, opened on first use and shared by every operation.
+    /// Meant for embedded, single-user setups where a connection pool is wasted.
+    pub fn single_connection(database_url: &str) -> Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_lazy_with(collation::connect_options(database_url)?);
+
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        SqliteNoteRepository { pool, write_permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_WRITES)) }
+    }
+
+    /// The pool behind this repository, for building other repositories on the same connections.
+    pub fn pool(&self) -> SqlitePool {
+        self.pool.clone()
+    }
+
+    /// Limits how many write operations run at once. Further writes wait for a free slot; reads are never limited.
+    pub fn with_max_concurrent_writes(mut self, max_concurrent_writes: usize) -> Self {
+        self.write_permits = Arc::new(Semaphore::new(max_concurrent_writes.max(1)));
+        self
+    }
+
+    async fn write_permit(&self) -> Result<SemaphorePermit<'_>> {
+        Ok(self.write_permits.acquire().await?)
     }
 }
 
 #[async_trait]
 impl NoteRepository for SqliteNoteRepository {
     async fn all(&self) -> Result<Vec<Note>> {
-        let notes = sqlx::query_as!(Note, "SELECT * FROM note")
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT ? OFFSET ?", limit, offset)
             .fetch_all(&self.pool)
             .await?;
 
         Ok(notes)
     }
 
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        // The window function sees every row before LIMIT applies, so each row carries the full total
+        let rows = sqlx::query!(
+            "SELECT id, title, content, created_at, updated_at, version, expires_at, format, COUNT(*) OVER () AS \"total!: i64\" FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT ? OFFSET ?",
+            limit, offset
+        )
+            .fetch_all(&self.pool)
+            .await?;
+        let total = match rows.first() {
+            Some(row) => row.total,
+            // A page past the end has no row to read the total from
+            None if offset > 0 => self.count().await?,
+            None => 0,
+        };
+        let notes = rows.into_iter()
+            .map(|row| Note {
+                id: row.id,
+                title: row.title,
+                content: row.content,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+                version: row.version,
+                expires_at: row.expires_at,
+                format: row.format,
+            })
+            .collect();
+
+        Ok((notes, total))
+    }
+
+    async fn count(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) AS \"count!: i64\" FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM note) AS \"exists!: bool\"")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(exists)
+    }
+
     async fn get(&self, id: &str) -> Result<Note> {
-        let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE id = ?", id)
+        let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))", id)
             .fetch_one(&self.pool)
             .await?;
 
         Ok(note)
     }
 
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))", id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(note)
+    }
+
     async fn create(&self, note: &NewNote) -> Result<Note> {
+        let _permit = self.write_permit().await?;
+
         let new_note = sqlx::query_as!(
             Note,
-            "INSERT INTO note (id, title, content, created_at) VALUES ($1, $2, $3, $4) RETURNING *",
-            note.id, note.title, note.content, note.created_at
+            "INSERT INTO note (id, title, content, created_at, updated_at, expires_at, format) VALUES ($1, $2, $3, $4, $4, $5, $6) RETURNING *",
+            note.id, note.title, note.content, note.created_at, note.expires_at, note.format
         )
             .fetch_one(&self.pool)
-            .await?;
+            .await
+            .map_err(conflict_on_taken_id)?;
 
         Ok(new_note)
     }
 
+    async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+
+        let new_note = sqlx::query_as!(
+            Note,
+            "INSERT INTO note (id, title, content, created_at, updated_at, expires_at, format) VALUES ($1, $2, $3, $4, $4, $5, $6) RETURNING *",
+            note.id, note.title, note.content, note.created_at, note.expires_at, note.format
+        )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(conflict_on_taken_id)?;
+        for tag in tags {
+            sqlx::query!("INSERT INTO note_tag (note_id, tag) VALUES (?, ?)", new_note.id, tag)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let tags = sqlx::query_scalar!("SELECT tag FROM note_tag WHERE note_id = ? ORDER BY tag", new_note.id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((new_note, tags))
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+
+        let mut created = Vec::with_capacity(notes.len());
+        for note in notes {
+            let new_note = sqlx::query_as!(
+                Note,
+                "INSERT INTO note (id, title, content, created_at, updated_at, expires_at, format) VALUES ($1, $2, $3, $4, $4, $5, $6) RETURNING *",
+                note.id, note.title, note.content, note.created_at, note.expires_at, note.format
+            )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(conflict_on_taken_id)?;
+            created.push(new_note);
+        }
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
     async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
-        let updated_note = sqlx::query_as!(
+        let _permit = self.write_permit().await?;
+
+        // A writer whose clock lags the last one still moves updated_at forward, by a millisecond, so
+        // clients syncing on updated_at never miss a change
+        let result = sqlx::query_as!(
             Note,
-            "UPDATE note SET title = $1, content = $2 WHERE id = $3 RETURNING *",
-            note.title, note.content, id
+            "UPDATE note SET title = $1, content = $2, updated_at = CASE WHEN $3 > updated_at THEN $3 ELSE strftime('%Y-%m-%d %H:%M:%f', updated_at, '+0.001 seconds') END, version = version + 1 WHERE id = $4 AND ($5 IS NULL OR version = $5) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *",
+            note.title, note.content, note.updated_at, id, note.version
         )
+            .fetch_one(&self.pool)
+            .await;
+
+        match result {
+            Ok(updated_note) => Ok(updated_note),
+            Err(sqlx::Error::RowNotFound) => {
+                // The note is either gone (never existed or deleted since the client read it)
+                // or still there with a newer version than the one the client expected.
+                let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))) AS \"exists!: bool\"", id)
+                    .fetch_one(&self.pool)
+                    .await?;
+
+                if exists {
+                    Err(DbError::Conflict.into())
+                } else {
+                    Err(DbError::NotFound.into())
+                }
+            }
+            Err(e) => Err(DbError::SqlxError(e).into()),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<Note> {
+        let _permit = self.write_permit().await?;
+
+        let deleted_note = sqlx::query_as!(Note, "DELETE FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *", id)
             .fetch_one(&self.pool)
             .await
             .map_err(|e| {
@@ -105,11 +671,39 @@ impl NoteRepository for SqliteNoteRepository {
                 }
             })?;
 
-        Ok(updated_note)
+        Ok(deleted_note)
     }
 
-    async fn delete(&self, id: &str) -> Result<Note> {
-        let deleted_note = sqlx::query_as!(Note, "DELETE FROM note WHERE id = ? RETURNING *", id)
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        let _permit = self.write_permit().await?;
+
+        let ids = sqlx::query_scalar!("DELETE FROM note WHERE expires_at <= ? RETURNING id", now)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(ids)
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        let _permit = self.write_permit().await?;
+
+        // Tags now cascade with their note, but databases written before that may still hold orphans
+        let orphaned_tags = sqlx::query!("DELETE FROM note_tag WHERE note_id NOT IN (SELECT id FROM note)")
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+        Ok(RepairReport { orphaned_tags })
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        let _permit = self.write_permit().await?;
+
+        let touched_note = sqlx::query_as!(
+            Note,
+            "UPDATE note SET updated_at = CASE WHEN $1 > updated_at THEN $1 ELSE strftime('%Y-%m-%d %H:%M:%f', updated_at, '+0.001 seconds') END, version = version + 1 WHERE id = $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *",
+            updated_at, id
+        )
             .fetch_one(&self.pool)
             .await
             .map_err(|e| {
@@ -119,6 +713,1745 @@ impl NoteRepository for SqliteNoteRepository {
                 }
             })?;
 
-        Ok(deleted_note)
+        Ok(touched_note)
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        let _permit = self.write_permit().await?;
+
+        // Only for notes imported from elsewhere, so neither updated_at nor version change
+        let note = sqlx::query_as!(Note, "UPDATE note SET created_at = $1 WHERE id = $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *", created_at, id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                match e {
+                    sqlx::Error::RowNotFound => DbError::NotFound,
+                    _ => DbError::SqlxError(e),
+                }
+            })?;
+
+        Ok(note)
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        let _permit = self.write_permit().await?;
+
+        // The length check is part of the UPDATE so a concurrent append can't push the content over the limit
+        let max_length = CONTENT_MAX_LENGTH as i64;
+        let result = sqlx::query_as!(
+            Note,
+            "UPDATE note SET content = content || $1, updated_at = CASE WHEN $2 > updated_at THEN $2 ELSE strftime('%Y-%m-%d %H:%M:%f', updated_at, '+0.001 seconds') END, version = version + 1 WHERE id = $3 AND length(content || $1) <= $4 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *",
+            text, updated_at, id, max_length
+        )
+            .fetch_one(&self.pool)
+            .await;
+
+        match result {
+            Ok(appended_note) => Ok(appended_note),
+            Err(sqlx::Error::RowNotFound) => {
+                let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))) AS \"exists!: bool\"", id)
+                    .fetch_one(&self.pool)
+                    .await?;
+
+                if exists {
+                    let mut error = ValidationError::new("length");
+                    error.add_param("max".into(), &CONTENT_MAX_LENGTH);
+                    let mut errors = ValidationErrors::new();
+                    errors.add("content", error);
+                    Err(errors.into())
+                } else {
+                    Err(DbError::NotFound.into())
+                }
+            }
+            Err(e) => Err(DbError::SqlxError(e).into()),
+        }
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+        let mut touched_notes = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let touched_note = sqlx::query_as!(
+                Note,
+                "UPDATE note SET updated_at = CASE WHEN $1 > updated_at THEN $1 ELSE strftime('%Y-%m-%d %H:%M:%f', updated_at, '+0.001 seconds') END, version = version + 1 WHERE id = $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *",
+                updated_at, id
+            )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(|e| {
+                    match e {
+                        sqlx::Error::RowNotFound => DbError::NotFound,
+                        _ => DbError::SqlxError(e),
+                    }
+                })?;
+            touched_notes.push(touched_note);
+        }
+
+        tx.commit().await?;
+
+        Ok(touched_notes)
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        let pattern = escape_like(prefix);
+        let titles = sqlx::query_as!(
+            NoteTitle,
+            "SELECT id, title FROM note WHERE title LIKE $1 || '%' ESCAPE '\\' AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY title COLLATE title_locale LIMIT $2",
+            pattern, limit
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(titles)
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let pattern = escape_like(query);
+        let notes = sqlx::query_as!(
+            Note,
+            "SELECT * FROM note WHERE (title LIKE '%' || $1 || '%' ESCAPE '\\' OR content LIKE '%' || $1 || '%' ESCAPE '\\') AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at DESC, id LIMIT $2 OFFSET $3",
+            pattern, limit, offset
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let pattern = escape_like(&filter.query);
+        let tag = filter.tag.as_deref();
+        // ORDER BY can't be bound as a parameter, so each sort order gets its own checked query
+        let notes = match filter.sort {
+            SearchSort::Newest => sqlx::query_as!(
+                Note,
+                "SELECT * FROM note WHERE (title LIKE '%' || $1 || '%' ESCAPE '\\' OR content LIKE '%' || $1 || '%' ESCAPE '\\') AND ($2 IS NULL OR EXISTS(SELECT 1 FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag = $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at DESC, id LIMIT $3 OFFSET $4",
+                pattern, tag, limit, offset
+            )
+                .fetch_all(&self.pool)
+                .await?,
+            SearchSort::Oldest => sqlx::query_as!(
+                Note,
+                "SELECT * FROM note WHERE (title LIKE '%' || $1 || '%' ESCAPE '\\' OR content LIKE '%' || $1 || '%' ESCAPE '\\') AND ($2 IS NULL OR EXISTS(SELECT 1 FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag = $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT $3 OFFSET $4",
+                pattern, tag, limit, offset
+            )
+                .fetch_all(&self.pool)
+                .await?,
+            SearchSort::Title => sqlx::query_as!(
+                Note,
+                "SELECT * FROM note WHERE (title LIKE '%' || $1 || '%' ESCAPE '\\' OR content LIKE '%' || $1 || '%' ESCAPE '\\') AND ($2 IS NULL OR EXISTS(SELECT 1 FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag = $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY title COLLATE title_locale, id LIMIT $3 OFFSET $4",
+                pattern, tag, limit, offset
+            )
+                .fetch_all(&self.pool)
+                .await?,
+        };
+
+        Ok(notes)
+    }
+
+    async fn tags(&self, id: &str) -> Result<Vec<String>> {
+        let tags = sqlx::query_scalar!("SELECT tag FROM note_tag WHERE note_id = ? ORDER BY tag", id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tags)
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+
+        let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))) AS \"exists!: bool\"", id)
+            .fetch_one(&mut *tx)
+            .await?;
+        if !exists {
+            return Err(DbError::NotFound.into());
+        }
+
+        sqlx::query!("DELETE FROM note_tag WHERE note_id = ?", id)
+            .execute(&mut *tx)
+            .await?;
+        for tag in tags {
+            sqlx::query!("INSERT INTO note_tag (note_id, tag) VALUES (?, ?)", id, tag)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        let tags = sqlx::query_scalar!("SELECT tag FROM note_tag WHERE note_id = ? ORDER BY tag", id)
+            .fetch_all(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(tags)
+    }
+
+    async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+
+        // Returning early drops `tx`, which rolls back every write made so far
+        let mut notes = Vec::with_capacity(writes.len());
+        for write in writes {
+            let note = match write {
+                NoteWrite::Create(note) => sqlx::query_as!(
+                    Note,
+                    "INSERT INTO note (id, title, content, created_at, updated_at, expires_at, format) VALUES ($1, $2, $3, $4, $4, $5, $6) RETURNING *",
+                    note.id, note.title, note.content, note.created_at, note.expires_at, note.format
+                )
+                    .fetch_one(&mut *tx)
+                    .await
+                    .map_err(conflict_on_taken_id)?,
+                NoteWrite::Update { id, note } => {
+                    let updated = sqlx::query_as!(
+                        Note,
+                        "UPDATE note SET title = $1, content = $2, updated_at = CASE WHEN $3 > updated_at THEN $3 ELSE strftime('%Y-%m-%d %H:%M:%f', updated_at, '+0.001 seconds') END, version = version + 1 WHERE id = $4 AND ($5 IS NULL OR version = $5) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *",
+                        note.title, note.content, note.updated_at, id, note.version
+                    )
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                    match updated {
+                        Some(updated) => updated,
+                        None => {
+                            let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))) AS \"exists!: bool\"", id)
+                                .fetch_one(&mut *tx)
+                                .await?;
+                            return Err(if exists { DbError::Conflict } else { DbError::NotFound }.into());
+                        }
+                    }
+                }
+                NoteWrite::Delete { id } => sqlx::query_as!(Note, "DELETE FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) RETURNING *", id)
+                    .fetch_optional(&mut *tx)
+                    .await?
+                    .ok_or(DbError::NotFound)?,
+                NoteWrite::ReplaceTags { id, tags } => {
+                    let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE id = ? AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))", id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                        .ok_or(DbError::NotFound)?;
+                    sqlx::query!("DELETE FROM note_tag WHERE note_id = ?", id)
+                        .execute(&mut *tx)
+                        .await?;
+                    for tag in tags {
+                        sqlx::query!("INSERT INTO note_tag (note_id, tag) VALUES (?, ?)", id, tag)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    note
+                }
+            };
+            notes.push(note);
+        }
+
+        tx.commit().await?;
+
+        Ok(notes)
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let _permit = self.write_permit().await?;
+        let mut tx = self.pool.begin().await?;
+
+        let note_ids = sqlx::query_scalar!("SELECT note_id FROM note_tag WHERE tag = ? ORDER BY note_id", from)
+            .fetch_all(&mut *tx)
+            .await?;
+        if from != to {
+            // Notes that already carry `to` keep a single entry for it
+            sqlx::query!("INSERT OR IGNORE INTO note_tag (note_id, tag) SELECT note_id, $2 FROM note_tag WHERE tag = $1", from, to)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query!("DELETE FROM note_tag WHERE tag = ?", from)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(note_ids)
+    }
+
+    async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>> {
+        let note_ids = sqlx::query_scalar!("SELECT note_id FROM note_tag WHERE tag = ? ORDER BY note_id", tag)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(note_ids)
+    }
+
+    // A single database has no replica to lag behind, so reads ahead of a write are plain reads
+    async fn get_for_update(&self, id: &str) -> Result<Note> {
+        self.get(id).await
+    }
+
+    async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>> {
+        self.ids_with_tag(tag).await
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        let tags = sqlx::query_as!(NoteTag, "SELECT * FROM note_tag ORDER BY note_id, tag")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tags)
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        let _permit = self.write_permit().await?;
+
+        sqlx::query!("INSERT INTO note_view (note_id, view_count, last_viewed_at) VALUES ($1, 1, $2) ON CONFLICT (note_id) DO UPDATE SET view_count = view_count + 1, last_viewed_at = excluded.last_viewed_at", id, viewed_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        let notes = sqlx::query_as!(
+            ViewedNote,
+            "SELECT note.id, note.title, note_view.view_count, note_view.last_viewed_at FROM note_view JOIN note ON note.id = note_view.note_id WHERE (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY note_view.last_viewed_at DESC, note.id LIMIT $1",
+            limit
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        let _permit = self.write_permit().await?;
+
+        sqlx::query!("INSERT INTO tag_color (tag, color) VALUES ($1, $2) ON CONFLICT (tag) DO UPDATE SET color = excluded.color", tag, color)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        let _permit = self.write_permit().await?;
+
+        let result = sqlx::query!("DELETE FROM tag_color WHERE tag = $1", tag)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        let colors = sqlx::query_as!(TagColor, "SELECT * FROM tag_color ORDER BY tag")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(colors)
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        let pairs = sqlx::query_as!(
+            TagPair,
+            "SELECT first.tag AS tag, second.tag AS other_tag, COUNT(*) AS \"count!: i64\" FROM note_tag AS first JOIN note_tag AS second ON second.note_id = first.note_id AND second.tag > first.tag JOIN note ON note.id = first.note_id WHERE (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) GROUP BY first.tag, second.tag ORDER BY 3 DESC, 1, 2 LIMIT $1",
+            limit
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(pairs)
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        // Cast to BLOB so length() counts bytes rather than characters
+        let size = sqlx::query!(
+            "SELECT length(CAST(title AS BLOB)) AS \"title!: i64\", length(CAST(content AS BLOB)) AS \"content!: i64\", (SELECT COALESCE(SUM(length(CAST(tag AS BLOB))), 0) FROM note_tag WHERE note_tag.note_id = note.id) AS \"tags!: i64\" FROM note WHERE id = $1 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))",
+            id
+        )
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+
+        Ok(NoteSize { title: size.title, content: size.content, tags: size.tags })
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        // Notes never viewed compare against '' and always count as changed
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE updated_at > COALESCE((SELECT last_viewed_at FROM note_view WHERE note_view.note_id = note.id), '') AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY updated_at DESC, id LIMIT ?", limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        let format = ActivityBucket::Month.format();
+        let counts = sqlx::query_as!(
+            ActivityCount,
+            "SELECT strftime($1, note.created_at) AS \"bucket!: String\", COUNT(*) AS \"count!: i64\" FROM note_tag JOIN note ON note.id = note_tag.note_id WHERE note_tag.tag = $2 AND (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) GROUP BY 1 ORDER BY 1",
+            format, tag
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(counts)
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE created_at >= $1 AND created_at < $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id", from, to)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let notes = sqlx::query_as!(
+            Note,
+            "SELECT * FROM note WHERE created_at >= $1 AND created_at < $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT $3 OFFSET $4",
+            from,
+            to,
+            limit,
+            offset
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) AS \"count!: i64\" FROM note WHERE created_at >= $1 AND created_at < $2 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))", from, to)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        let tags = sqlx::query_as!(
+            NoteTag,
+            "SELECT note_tag.note_id, note_tag.tag FROM note_tag JOIN note ON note.id = note_tag.note_id WHERE note.created_at >= $1 AND note.created_at < $2 AND (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY note_tag.note_id, note_tag.tag",
+            from,
+            to
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tags)
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        // Every update, append and touch bumps the version, so it counts the edits since creation
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY version DESC, updated_at DESC, id LIMIT ?", limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        let source = self.try_get(id).await?.ok_or(DbError::NotFound)?;
+        let source_words = significant_words(&source);
+        let mut query = related_candidates_query(&source.id, &source_words);
+        let rows = query.build()
+            .fetch_all(&self.pool)
+            .await?;
+        let candidates = rows.iter()
+            .map(|row| Ok((Note::from_row(row)?, row.try_get("shared_tags")?)))
+            .collect::<Result<Vec<(Note, i64)>>>()?;
+
+        Ok(rank_related(&source_words, candidates, limit))
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        let note = self.try_get(id).await?.ok_or(DbError::NotFound)?;
+        // Ties on created_at are broken by id, the same order as the note list
+        let previous = sqlx::query_as!(
+            NoteTitle,
+            "SELECT id, title FROM note WHERE (created_at < $1 OR (created_at = $1 AND id < $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at DESC, id DESC LIMIT 1",
+            note.created_at, note.id
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+        let next = sqlx::query_as!(
+            NoteTitle,
+            "SELECT id, title FROM note WHERE (created_at > $1 OR (created_at = $1 AND id > $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT 1",
+            note.created_at, note.id
+        )
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(NoteNeighbors { previous, next })
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        let notes = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at DESC, id LIMIT ?", limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(note)
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        let note = sqlx::query_as!(Note, "SELECT * FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at DESC, id LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(note)
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        let ids = sqlx::query_scalar!("SELECT id FROM note WHERE id > $1 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY id LIMIT $2", after, limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(ids)
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        let counts = sqlx::query_as!(
+            ActivityCount,
+            "SELECT strftime('%Y-%m-%d %H:%M', created_at) AS \"bucket!: String\", COUNT(*) AS \"count!: i64\" FROM note WHERE (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) GROUP BY 1 ORDER BY 1"
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(counts)
+    }
+
+    async fn id_with_content(&self, content: &str) -> Result<Option<String>> {
+        let id = sqlx::query_scalar!("SELECT id FROM note WHERE content = $1 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) ORDER BY created_at, id LIMIT 1", content)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        // Expired notes are included on purpose, they are often what a data issue is about
+        let row = sqlx::query("SELECT * FROM note WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or(DbError::NotFound)?;
+
+        raw::columns(&row)
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        let format = bucket.format();
+        let counts = sqlx::query_as!(
+            ActivityCount,
+            "SELECT strftime($1, created_at) AS \"bucket!: String\", COUNT(*) AS \"count!: i64\" FROM note WHERE created_at >= $2 AND created_at < $3 AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now')) GROUP BY 1 ORDER BY 1",
+            format, from, to
+        )
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(counts)
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        let counts: HashMap<String, i64> = self.activity(from, to, ActivityBucket::Day).await?
+            .into_iter()
+            .map(|count| (count.bucket, count.count))
+            .collect();
+        let to = chrono::NaiveDate::parse_from_str(to, "%Y-%m-%d")?;
+        let days = chrono::NaiveDate::parse_from_str(from, "%Y-%m-%d")?
+            .iter_days()
+            .take_while(|day| *day < to)
+            .map(|day| {
+                let date = day.format("%Y-%m-%d").to_string();
+                let count = counts.get(&date).copied().unwrap_or(0);
+                (date, count)
+            })
+            .collect();
+
+        Ok(days)
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        // The (note_id, tag) primary key makes each row a distinct note
+        let count = sqlx::query_scalar!("SELECT COUNT(*) AS \"count!: i64\" FROM note_tag JOIN note ON note.id = note_tag.note_id WHERE note_tag.tag = ? AND (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))", tag)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        // Loaded in one query rather than streamed: exports build their archive in memory and are capped by
+        // MAX_EXPORT_NOTES, so a stream would only move where the notes are held. A negative LIMIT is no limit in SQLite
+        self.search_by(filter, -1, 0).await
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        let pattern = escape_like(&filter.query);
+        let tag = filter.tag.as_deref();
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) AS \"count!: i64\" FROM note WHERE (title LIKE '%' || $1 || '%' ESCAPE '\\' OR content LIKE '%' || $1 || '%' ESCAPE '\\') AND ($2 IS NULL OR EXISTS(SELECT 1 FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag = $2)) AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))",
+            pattern, tag
+        )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let mut query = tag_match_query("SELECT * FROM note", tags, matching);
+        query.push(" ORDER BY created_at, id LIMIT ").push_bind(limit).push(" OFFSET ").push_bind(offset);
+        let notes = query.build_query_as::<Note>()
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(notes)
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        let mut query = QueryBuilder::new(
+            "SELECT note.*, COUNT(*) AS matched_tags FROM note JOIN note_tag ON note_tag.note_id = note.id WHERE note_tag.tag IN ("
+        );
+        let mut bound = query.separated(", ");
+        for tag in tags {
+            bound.push_bind(tag);
+        }
+        query.push(") AND (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))");
+        query.push(" GROUP BY note.id ORDER BY matched_tags DESC, note.created_at, note.id LIMIT ").push_bind(limit);
+        let rows = query.build()
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|row| Ok((Note::from_row(row)?, row.try_get("matched_tags")?)))
+            .collect()
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        let mut query = tag_match_query("SELECT COUNT(*) FROM note", tags, matching);
+        let count = query.build_query_scalar::<i64>()
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The number of ids varies, so the query is built at runtime like `tag_match_query`
+        let mut query = QueryBuilder::new("SELECT * FROM note WHERE id IN (");
+        let mut bound = query.separated(", ");
+        for id in ids {
+            bound.push_bind(id);
+        }
+        query.push(") AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))");
+        let mut notes: HashMap<String, Note> = query.build_query_as::<Note>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|note| (note.id.clone(), note))
+            .collect();
+
+        // Missing ids are skipped and a repeated id is returned at its first position
+        Ok(ids.iter().filter_map(|id| notes.remove(id)).collect())
+    }
+}
+
+/// Turns the unique violation of an insert whose id is already taken into `DbError::IdTaken`.
+fn conflict_on_taken_id(error: sqlx::Error) -> anyhow::Error {
+    match error {
+        sqlx::Error::Database(ref database_error) if database_error.is_unique_violation() => DbError::IdTaken.into(),
+        error => error.into(),
+    }
+}
+
+/// Appends to `select` the conditions of a `by_tags` lookup. The number of tags varies, so unlike
+/// the other queries this one is built at runtime instead of being checked by `sqlx::query!`.
+fn tag_match_query<'a>(select: &str, tags: &'a [String], matching: TagMatch) -> QueryBuilder<'a, Sqlite> {
+    let mut query = QueryBuilder::new(select);
+    query.push(" WHERE id IN (SELECT note_id FROM note_tag WHERE tag IN (");
+    let mut bound = query.separated(", ");
+    for tag in tags {
+        bound.push_bind(tag);
+    }
+    query.push(")");
+    if matching == TagMatch::All {
+        // Each (note_id, tag) row is unique, so a note has every tag when it matches as many rows as there are distinct tags
+        let distinct_tags = tags.iter().collect::<HashSet<_>>().len() as i64;
+        query.push(" GROUP BY note_id HAVING COUNT(*) = ").push_bind(distinct_tags);
+    }
+    query.push(") AND (expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))");
+
+    query
+}
+
+/// Shortest word compared by `rank_related`, which leaves out most filler words.
+const RELATED_MIN_WORD_LENGTH: usize = 4;
+
+fn significant_words(note: &Note) -> HashSet<String> {
+    note.title.split(|c: char| !c.is_alphanumeric())
+        .chain(note.content.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| word.chars().count() >= RELATED_MIN_WORD_LENGTH)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Selects the notes other than `source_id` that share a tag with it or contain one of `source_words`,
+/// with the number of shared tags as `shared_tags`. Words are matched as substrings and `LIKE` only
+/// folds ASCII case, so this narrows the candidates for `rank_related` without deciding the ranking.
+fn related_candidates_query<'a>(source_id: &'a str, source_words: &HashSet<String>) -> QueryBuilder<'a, Sqlite> {
+    let mut query = QueryBuilder::new("SELECT note.*, (SELECT COUNT(*) FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag IN (SELECT tag FROM note_tag WHERE note_id = ");
+    query.push_bind(source_id);
+    query.push(")) AS shared_tags FROM note WHERE note.id != ").push_bind(source_id);
+    query.push(" AND (note.expires_at IS NULL OR note.expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))");
+    query.push(" AND (EXISTS(SELECT 1 FROM note_tag WHERE note_tag.note_id = note.id AND note_tag.tag IN (SELECT tag FROM note_tag WHERE note_id = ");
+    query.push_bind(source_id);
+    query.push("))");
+    for word in source_words {
+        let pattern = format!("%{}%", escape_like(word));
+        query.push(" OR note.title LIKE ").push_bind(pattern.clone()).push(" ESCAPE '\\'");
+        query.push(" OR note.content LIKE ").push_bind(pattern).push(" ESCAPE '\\'");
+    }
+    query.push(") ORDER BY note.created_at, note.id");
+
+    query
+}
+
+/// Orders `candidates` by how many tags, then significant words, they share with the source note,
+/// leaving out notes that share nothing. Each candidate comes with its number of shared tags.
+fn rank_related(source_words: &HashSet<String>, candidates: Vec<(Note, i64)>, limit: i64) -> Vec<Note> {
+    let mut ranked: Vec<((i64, usize), Note)> = candidates
+        .into_iter()
+        .map(|(note, shared_tags)| {
+            let shared_words = significant_words(&note).intersection(source_words).count();
+            ((shared_tags, shared_words), note)
+        })
+        .filter(|(overlap, _)| *overlap != (0, 0))
+        .collect();
+    ranked.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    ranked.into_iter().take(limit.max(0) as usize).map(|(_, note)| note).collect()
+}
+
+/// Escapes `%`, `_` and the escape character itself so user input is matched literally by `LIKE ... ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn repository() -> SqliteNoteRepository {
+        // A single, never recycled connection keeps the in-memory database alive for the whole test.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(crate::collation::connect_options("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+
+        SqliteNoteRepository::from_pool(pool)
+    }
+
+    async fn insert(repository: &SqliteNoteRepository, id: &str, title: &str) -> Note {
+        repository.create(&NewNote {
+            id: String::from(id),
+            title: String::from(title),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_migrations_with_foreign_keys() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(crate::collation::connect_options("sqlite::memory:").unwrap().foreign_keys(true))
+            .await
+            .unwrap();
+        MIGRATOR.run(&pool).await.unwrap();
+        assert!(sqlx::query("PRAGMA foreign_key_check").fetch_all(&pool).await.unwrap().is_empty());
+
+        let repository = SqliteNoteRepository::from_pool(pool);
+        insert(&repository, "1", "Note 1").await;
+        repository.replace_tags("1", &[String::from("work")]).await.unwrap();
+        repository.record_view("1", "2021-01-01 12:00:00").await.unwrap();
+        repository.delete("1").await.unwrap();
+        assert!(repository.all_tags().await.unwrap().is_empty());
+        let views: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM note_view").fetch_one(&repository.pool).await.unwrap();
+        assert_eq!(views, 0);
+    }
+
+    #[tokio::test]
+    async fn test_min_connections_are_opened_up_front() {
+        let repository = SqliteNoteRepository::new_with_min_connections("sqlite::memory:", 3).await.unwrap();
+
+        assert!(repository.pool.size() >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_single_connection_crud() {
+        let repository = SqliteNoteRepository::single_connection("sqlite::memory:").unwrap();
+        MIGRATOR.run(&repository.pool).await.unwrap();
+
+        let created = insert(&repository, "1", "Single").await;
+        assert_eq!(repository.get("1").await.unwrap().title, created.title);
+
+        let updated = repository.update("1", &UpdateNote {
+            title: String::from("Updated"),
+            content: String::from("content"),
+            updated_at: String::from("2021-01-02 00:00:00"),
+            version: None,
+        }).await.unwrap();
+        assert_eq!(updated.title, "Updated");
+        assert_eq!(repository.count().await.unwrap(), 1);
+
+        repository.delete("1").await.unwrap();
+        assert!(repository.try_get("1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_single_connection_pool_is_shared() {
+        let repository = SqliteNoteRepository::single_connection("sqlite::memory:").unwrap();
+        let health = SqliteHealthRepository::from_pool(repository.pool());
+        health.migrate().await.unwrap();
+
+        // A separate pool would have opened its own, empty in-memory database
+        insert(&repository, "1", "Shared").await;
+        assert_eq!(repository.count().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_title_prefix() {
+        let repository = repository().await;
+        insert(&repository, "1", "Shopping list").await;
+        insert(&repository, "2", "Shower thoughts").await;
+        insert(&repository, "3", "Washing machine").await;
+        insert(&repository, "4", "100% done").await;
+
+        let titles = repository.title_prefix("Sh", 10).await.unwrap();
+        let ids: Vec<&str> = titles.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2"]);
+
+        let titles = repository.title_prefix("Sh", 1).await.unwrap();
+        assert_eq!(titles.len(), 1);
+
+        assert!(repository.title_prefix("hing", 10).await.unwrap().is_empty());
+        assert!(repository.title_prefix("%", 10).await.unwrap().is_empty());
+        assert_eq!(repository.title_prefix("100%", 10).await.unwrap()[0].id, "4");
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let repository = repository().await;
+        insert(&repository, "1", "Shopping list").await;
+        insert(&repository, "2", "Groceries").await;
+        repository.create(&NewNote {
+            id: String::from("3"),
+            title: String::from("Weekend"),
+            content: String::from("Go shopping for shoes"),
+            created_at: String::from("2021-01-02 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await.unwrap();
+
+        let notes = repository.search("shopping", 10, 0).await.unwrap();
+        let ids: Vec<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, vec!["3", "1"]);
+
+        let notes = repository.search("shopping", 1, 1).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, "1");
+    }
+
+    #[tokio::test]
+    async fn test_all_by() {
+        let repository = repository().await;
+        for id in ["1", "2", "3"] {
+            insert(&repository, id, &format!("Note {}", id)).await;
+        }
+        repository.replace_tags("1", &[String::from("work")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("work")]).await.unwrap();
+
+        let filter = SearchFilter { query: String::new(), tag: Some(String::from("work")), sort: SearchSort::Oldest };
+        let notes = repository.all_by(&filter).await.unwrap();
+        let ids: Vec<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+    }
+
+    #[tokio::test]
+    async fn test_replace_tags() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+
+        let tags = repository.replace_tags("1", &[String::from("b"), String::from("a")]).await.unwrap();
+        assert_eq!(tags, vec!["a", "b"]);
+
+        let tags = repository.replace_tags("1", &[String::from("x")]).await.unwrap();
+        assert_eq!(tags, vec!["x"]);
+        assert_eq!(repository.tags("1").await.unwrap(), vec!["x"]);
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+
+        let notes = repository.transaction(&[
+            NoteWrite::Create(NewNote {
+                id: String::from("2"),
+                title: String::from("Note 2"),
+                content: String::from("content"),
+                created_at: String::from("2021-01-02 00:00:00"),
+                expires_at: None,
+                format: String::from("markdown"),
+            }),
+            NoteWrite::ReplaceTags { id: String::from("2"), tags: vec![String::from("work")] },
+            NoteWrite::Delete { id: String::from("1") },
+        ]).await.unwrap();
+        let ids: Vec<&str> = notes.iter().map(|note| note.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "2", "1"]);
+        assert_eq!(repository.tags("2").await.unwrap(), vec!["work"]);
+        assert!(repository.try_get("1").await.unwrap().is_none());
+
+        let result = repository.transaction(&[
+            NoteWrite::Create(NewNote {
+                id: String::from("2"),
+                title: String::from("Note 2 again"),
+                content: String::from("content"),
+                created_at: String::from("2021-01-03 00:00:00"),
+                expires_at: None,
+                format: String::from("markdown"),
+            }),
+        ]).await;
+        assert!(matches!(result.unwrap_err().downcast_ref::<DbError>(), Some(DbError::IdTaken)));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+
+        let result = repository.transaction(&[
+            NoteWrite::Delete { id: String::from("1") },
+            NoteWrite::Create(NewNote {
+                id: String::from("2"),
+                title: String::from("Note 2"),
+                content: String::from("content"),
+                created_at: String::from("2021-01-02 00:00:00"),
+                expires_at: None,
+                format: String::from("markdown"),
+            }),
+            NoteWrite::Update {
+                id: String::from("missing"),
+                note: UpdateNote {
+                    title: String::from("Missing"),
+                    content: String::from("content"),
+                    updated_at: String::from("2021-01-03 00:00:00"),
+                    version: None,
+                },
+            },
+        ]).await;
+        assert!(matches!(result.unwrap_err().downcast::<DbError>(), Ok(DbError::NotFound)));
+
+        assert!(repository.try_get("1").await.unwrap().is_some());
+        assert!(repository.try_get("2").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_expired_notes() {
+        let repository = repository().await;
+        insert(&repository, "1", "Kept").await;
+        for (id, expires_at) in [("2", "2000-01-01 00:00:00"), ("3", "2999-01-01 00:00:00")] {
+            repository.create(&NewNote {
+                id: String::from(id),
+                title: String::from("Ephemeral"),
+                content: String::from("content"),
+                created_at: String::from("1999-01-01 00:00:00"),
+                expires_at: Some(String::from(expires_at)),
+                format: String::from("markdown"),
+            }).await.unwrap();
+        }
+
+        assert!(repository.try_get("2").await.unwrap().is_none());
+        assert!(repository.try_get("3").await.unwrap().is_some());
+        assert_eq!(repository.count().await.unwrap(), 2);
+        let ids: Vec<String> = repository.all().await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(ids, vec!["1", "3"]);
+
+        assert_eq!(repository.delete_expired("2023-12-05 00:00:00").await.unwrap(), vec!["2"]);
+        assert!(repository.delete_expired("2023-12-05 00:00:00").await.unwrap().is_empty());
+        assert!(repository.exists_any().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_writes_to_expired_note() {
+        let repository = repository().await;
+        repository.create(&NewNote {
+            id: String::from("1"),
+            title: String::from("Ephemeral"),
+            content: String::from("content"),
+            created_at: String::from("1999-01-01 00:00:00"),
+            expires_at: Some(String::from("2000-01-01 00:00:00")),
+            format: String::from("markdown"),
+        }).await.unwrap();
+        let is_not_found = |result: Result<()>| matches!(result.unwrap_err().downcast_ref::<DbError>(), Some(DbError::NotFound));
+
+        let update = UpdateNote {
+            title: String::from("Revived"),
+            content: String::from("content"),
+            updated_at: String::from("2021-01-02 00:00:00"),
+            version: None,
+        };
+        assert!(is_not_found(repository.update("1", &update).await.map(drop)));
+        assert!(is_not_found(repository.append("1", " more", "2021-01-02 00:00:00").await.map(drop)));
+        assert!(is_not_found(repository.touch("1", "2021-01-02 00:00:00").await.map(drop)));
+        assert!(is_not_found(repository.touch_many(&[String::from("1")], "2021-01-02 00:00:00").await.map(drop)));
+        assert!(is_not_found(repository.replace_tags("1", &[String::from("work")]).await.map(drop)));
+        assert!(is_not_found(repository.set_created_at("1", "2021-01-01 00:00:00").await.map(drop)));
+        assert!(is_not_found(repository.delete("1").await.map(drop)));
+
+        // Left for the reaper, untouched
+        assert_eq!(repository.delete_expired("2023-12-05 00:00:00").await.unwrap(), vec!["1"]);
+    }
+
+    #[tokio::test]
+    async fn test_exists_any() {
+        let repository = repository().await;
+        assert!(!repository.exists_any().await.unwrap());
+
+        insert(&repository, "1", "Note 1").await;
+        assert!(repository.exists_any().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_create_with_taken_id() {
+        let repository = repository().await;
+        let note = insert(&repository, "1", "Note 1").await;
+
+        let result = repository.create(&NewNote {
+            id: note.id,
+            title: String::from("Note 2"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await;
+
+        assert!(matches!(result.unwrap_err().downcast_ref::<DbError>(), Some(DbError::IdTaken)));
+    }
+
+    #[tokio::test]
+    async fn test_try_get() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+
+        assert_eq!(repository.try_get("1").await.unwrap().map(|note| note.title), Some(String::from("Note 1")));
+        assert!(repository.try_get("missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recently_viewed() {
+        let repository = repository().await;
+        for id in ["1", "2", "3"] {
+            insert(&repository, id, &format!("Note {}", id)).await;
+        }
+        repository.record_view("1", "2021-01-01 10:00:00").await.unwrap();
+        repository.record_view("2", "2021-01-01 11:00:00").await.unwrap();
+        repository.record_view("1", "2021-01-01 12:00:00").await.unwrap();
+
+        let viewed = repository.recently_viewed(10).await.unwrap();
+
+        assert_eq!(viewed, vec![
+            ViewedNote { id: String::from("1"), title: String::from("Note 1"), view_count: 2, last_viewed_at: String::from("2021-01-01 12:00:00") },
+            ViewedNote { id: String::from("2"), title: String::from("Note 2"), view_count: 1, last_viewed_at: String::from("2021-01-01 11:00:00") },
+        ]);
+        assert_eq!(repository.recently_viewed(1).await.unwrap().len(), 1);
+
+        repository.delete("1").await.unwrap();
+        assert_eq!(repository.recently_viewed(10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_storage_size() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+        repository.replace_tags("1", &[String::from("work"), String::from("café")]).await.unwrap();
+
+        let size = repository.storage_size("1").await.unwrap();
+        assert_eq!(size, NoteSize { title: 6, content: 7, tags: 9 });
+        assert_eq!(size.total(), 22);
+
+        let missing = repository.storage_size("missing").await.unwrap_err();
+        assert!(matches!(missing.downcast_ref::<DbError>(), Some(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_created_between() {
+        let repository = repository().await;
+        for (id, created_at) in [("1", "2023-11-30 23:59:59"), ("2", "2023-12-01 00:00:00"), ("3", "2023-12-31 23:59:59.999"), ("4", "2024-01-01 00:00:00")] {
+            repository.create(&NewNote {
+                id: String::from(id),
+                title: format!("Note {}", id),
+                content: String::from("content"),
+                created_at: String::from(created_at),
+                expires_at: None,
+                format: String::from("markdown"),
+            }).await.unwrap();
+        }
+
+        let ids: Vec<String> = repository.created_between("2023-12-01 00:00:00", "2024-01-01 00:00:00").await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+        let ids: Vec<String> = repository.created_between_paged("2023-12-01 00:00:00", "2024-01-01 00:00:00", 1, 1).await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(ids, vec!["3"]);
+        assert_eq!(repository.count_created_between("2023-12-01 00:00:00", "2024-01-01 00:00:00").await.unwrap(), 2);
+
+        repository.replace_tags("1", &[String::from("old")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("work"), String::from("home")]).await.unwrap();
+        let tags = repository.tags_created_between("2023-12-01 00:00:00", "2024-01-01 00:00:00").await.unwrap();
+        assert_eq!(tags, vec![
+            NoteTag { note_id: String::from("3"), tag: String::from("home") },
+            NoteTag { note_id: String::from("3"), tag: String::from("work") },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_most_edited() {
+        let repository = repository().await;
+        for id in ["1", "2", "3"] {
+            insert(&repository, id, &format!("Note {}", id)).await;
+        }
+        for _ in 0..2 {
+            repository.append("2", " more", "2021-01-02 00:00:00").await.unwrap();
+        }
+        repository.append("3", " more", "2021-01-02 00:00:00").await.unwrap();
+
+        let ids: Vec<String> = repository.most_edited(2).await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn test_updated_since_viewed() {
+        let repository = repository().await;
+        for id in ["1", "2", "3"] {
+            insert(&repository, id, &format!("Note {}", id)).await;
+        }
+        repository.record_view("1", "2021-01-02 00:00:00").await.unwrap();
+        repository.record_view("2", "2021-01-02 00:00:00").await.unwrap();
+        repository.append("2", " more", "2021-01-03 00:00:00").await.unwrap();
+
+        let ids: Vec<String> = repository.updated_since_viewed(10).await.unwrap().into_iter().map(|note| note.id).collect();
+        assert_eq!(ids, vec!["2", "3"]);
+    }
+
+    #[tokio::test]
+    async fn test_tag_trend() {
+        let repository = repository().await;
+        for (id, created_at, tag) in [("1", "2023-11-05 10:00:00", "rust"), ("2", "2023-12-01 09:00:00", "rust"), ("3", "2023-12-24 18:00:00", "rust"), ("4", "2023-12-24 18:00:00", "web")] {
+            repository.create_with_tags(&NewNote {
+                id: String::from(id),
+                title: format!("Note {}", id),
+                content: String::from("content"),
+                created_at: String::from(created_at),
+                expires_at: None,
+                format: String::from("markdown"),
+            }, &[String::from(tag)]).await.unwrap();
+        }
+
+        let trend = repository.tag_trend("rust").await.unwrap();
+        assert_eq!(trend, vec![
+            ActivityCount { bucket: String::from("2023-11"), count: 1 },
+            ActivityCount { bucket: String::from("2023-12"), count: 2 },
+        ]);
+        assert!(repository.tag_trend("missing").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tag_co_occurrence() {
+        let repository = repository().await;
+        for (id, tags) in [("1", vec!["rust", "web", "todo"]), ("2", vec!["web", "rust"]), ("3", vec!["rust", "web"]), ("4", vec!["todo"])] {
+            insert(&repository, id, &format!("Note {}", id)).await;
+            repository.replace_tags(id, &tags.into_iter().map(String::from).collect::<Vec<_>>()).await.unwrap();
+        }
+
+        let pairs = repository.tag_co_occurrence(2).await.unwrap();
+
+        assert_eq!(pairs, vec![
+            TagPair { tag: String::from("rust"), other_tag: String::from("web"), count: 3 },
+            TagPair { tag: String::from("rust"), other_tag: String::from("todo"), count: 1 },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_tag_colors() {
+        let repository = repository().await;
+        repository.set_tag_color("work", "#ff0000").await.unwrap();
+        repository.set_tag_color("home", "#00ff00").await.unwrap();
+        repository.set_tag_color("work", "#0000ff").await.unwrap();
+
+        assert_eq!(repository.tag_colors().await.unwrap(), vec![
+            TagColor { tag: String::from("home"), color: String::from("#00ff00") },
+            TagColor { tag: String::from("work"), color: String::from("#0000ff") },
+        ]);
+
+        assert!(repository.remove_tag_color("home").await.unwrap());
+        assert!(!repository.remove_tag_color("home").await.unwrap());
+        assert_eq!(repository.tag_colors().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replace_tags_of_missing_note() {
+        let repository = repository().await;
+
+        let result = repository.replace_tags("missing", &[String::from("x")]).await;
+        assert!(matches!(result.unwrap_err().downcast::<DbError>(), Ok(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_create_with_tags_rolls_back() {
+        let repository = repository().await;
+        let note = NewNote {
+            id: String::from("1"),
+            title: String::from("Shopping list"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        // The duplicate tag violates the primary key after the note row was inserted
+        let result = repository.create_with_tags(&note, &[String::from("x"), String::from("x")]).await;
+        assert!(result.is_err());
+        assert_eq!(repository.count().await.unwrap(), 0);
+
+        let (created, tags) = repository.create_with_tags(&note, &[String::from("y"), String::from("x")]).await.unwrap();
+        assert_eq!(created.id, "1");
+        assert_eq!(tags, vec!["x", "y"]);
+    }
+
+    #[tokio::test]
+    async fn test_create_many() {
+        let repository = repository().await;
+        let notes: Vec<NewNote> = ["1", "2"].iter().map(|id| NewNote {
+            id: id.to_string(),
+            title: format!("Note {}", id),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).collect();
+
+        let created = repository.create_many(&notes).await.unwrap();
+        assert_eq!(created.len(), 2);
+        assert_eq!(repository.count().await.unwrap(), 2);
+
+        // A duplicate id fails the whole batch
+        let result = repository.create_many(&[notes[0].clone()]).await;
+        assert!(matches!(result.unwrap_err().downcast_ref::<DbError>(), Some(DbError::IdTaken)));
+        assert_eq!(repository.count().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_create_many_large_batch() {
+        let repository = repository().await;
+        let notes: Vec<NewNote> = (0..5000).map(|i| NewNote {
+            id: format!("{:05}", i),
+            title: format!("Note {}", i),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).collect();
+
+        assert_eq!(repository.create_many(&notes).await.unwrap().len(), 5000);
+        let ids: Vec<String> = notes.into_iter().map(|note| note.id).collect();
+        assert_eq!(repository.ids_after("", 10000).await.unwrap(), ids);
+    }
+
+    #[tokio::test]
+    async fn test_activity() {
+        let repository = repository().await;
+        let created_at = ["2021-01-01 09:00:00", "2021-01-01 18:00:00", "2021-01-03 12:00:00", "2021-02-01 00:00:00"];
+        let mut notes: Vec<NewNote> = created_at.iter().enumerate().map(|(i, created_at)| NewNote {
+            id: i.to_string(),
+            title: format!("Note {}", i),
+            content: String::from("content"),
+            created_at: created_at.to_string(),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).collect();
+        notes.push(NewNote {
+            id: String::from("expired"),
+            title: String::from("Expired"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 12:00:00"),
+            expires_at: Some(String::from("2021-01-02 00:00:00")),
+            format: String::from("markdown"),
+        });
+        repository.create_many(&notes).await.unwrap();
+
+        let days = repository.activity("2021-01-01", "2021-02-01", ActivityBucket::Day).await.unwrap();
+        assert_eq!(days, vec![
+            ActivityCount { bucket: String::from("2021-01-01"), count: 2 },
+            ActivityCount { bucket: String::from("2021-01-03"), count: 1 },
+        ]);
+
+        let months = repository.activity("2021-01-01", "2021-03-01", ActivityBucket::Month).await.unwrap();
+        assert_eq!(months, vec![
+            ActivityCount { bucket: String::from("2021-01"), count: 3 },
+            ActivityCount { bucket: String::from("2021-02"), count: 1 },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_creation_minutes() {
+        let repository = repository().await;
+        let created_at = ["2021-01-01 09:00:10.5", "2021-01-01 09:00:50", "2021-01-03 23:30:00"];
+        let notes: Vec<NewNote> = created_at.iter().enumerate().map(|(i, created_at)| NewNote {
+            id: i.to_string(),
+            title: format!("Note {}", i),
+            content: String::from("content"),
+            created_at: created_at.to_string(),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).collect();
+        repository.create_many(&notes).await.unwrap();
+
+        assert_eq!(repository.creation_minutes().await.unwrap(), vec![
+            ActivityCount { bucket: String::from("2021-01-01 09:00"), count: 2 },
+            ActivityCount { bucket: String::from("2021-01-03 23:30"), count: 1 },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_activity_heatmap_fills_empty_days() {
+        let repository = repository().await;
+        for (id, created_at) in [("1", "2021-01-01 09:00:00"), ("2", "2021-01-01 18:00:00"), ("3", "2021-01-03 12:00:00")] {
+            repository.create(&NewNote {
+                id: String::from(id),
+                title: String::from("Note"),
+                content: String::from("content"),
+                created_at: String::from(created_at),
+                expires_at: None,
+                format: String::from("markdown"),
+            }).await.unwrap();
+        }
+
+        let days = repository.activity_heatmap("2021-01-01", "2021-01-05").await.unwrap();
+
+        assert_eq!(days, vec![
+            (String::from("2021-01-01"), 2),
+            (String::from("2021-01-02"), 0),
+            (String::from("2021-01-03"), 1),
+            (String::from("2021-01-04"), 0),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_count_by_tag() {
+        let repository = repository().await;
+        insert(&repository, "1", "Shopping list").await;
+        insert(&repository, "2", "Recipes").await;
+        insert(&repository, "3", "Trip").await;
+        repository.replace_tags("1", &[String::from("home"), String::from("todo")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("home")]).await.unwrap();
+        repository.create_with_tags(&NewNote {
+            id: String::from("expired"),
+            title: String::from("Expired"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: Some(String::from("2021-01-02 00:00:00")),
+            format: String::from("markdown"),
+        }, &[String::from("home")]).await.unwrap();
+
+        assert_eq!(repository.count_by_tag("home").await.unwrap(), 2);
+        assert_eq!(repository.count_by_tag("todo").await.unwrap(), 1);
+        assert_eq!(repository.count_by_tag("missing").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writes_are_queued() {
+        let repository = repository().await;
+        let new_note = |id: &str| NewNote {
+            id: String::from(id),
+            title: String::from("Note"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+        let (first, second) = (new_note("1"), new_note("2"));
+
+        let (first, second) = tokio::join!(repository.create(&first), repository.create(&second));
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert_eq!(repository.count().await.unwrap(), 2);
+        assert_eq!(repository.write_permits.available_permits(), DEFAULT_MAX_CONCURRENT_WRITES);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_tag_and_sort() {
+        let repository = repository().await;
+        insert(&repository, "1", "Beta plan").await;
+        insert(&repository, "2", "Alpha plan").await;
+        insert(&repository, "3", "Gamma plan").await;
+        repository.replace_tags("1", &[String::from("work")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("work")]).await.unwrap();
+
+        let mut filter = SearchFilter { query: String::from("plan"), tag: Some(String::from("work")), sort: SearchSort::Title };
+        let notes = repository.search_by(&filter, 10, 0).await.unwrap();
+        assert_eq!(notes.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(), vec!["2", "1"]);
+
+        filter.tag = None;
+        let notes = repository.search_by(&filter, 10, 0).await.unwrap();
+        assert_eq!(notes.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_search_by_title_sorts_accented_titles_by_locale() {
+        let repository = repository().await;
+        insert(&repository, "1", "Zebra").await;
+        insert(&repository, "2", "\u{e9}cole").await;
+        insert(&repository, "3", "Eagle").await;
+        insert(&repository, "4", "apple").await;
+
+        let filter = SearchFilter { query: String::new(), tag: None, sort: SearchSort::Title };
+        let notes = repository.search_by(&filter, 10, 0).await.unwrap();
+        assert_eq!(notes.iter().map(|note| note.title.as_str()).collect::<Vec<_>>(), vec!["apple", "Eagle", "\u{e9}cole", "Zebra"]);
+
+        let titles = repository.title_prefix("", 10).await.unwrap();
+        assert_eq!(titles.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(), vec!["4", "3", "2", "1"]);
+    }
+
+    #[tokio::test]
+    async fn test_related() {
+        let repository = repository().await;
+        insert(&repository, "1", "Source").await;
+        insert(&repository, "2", "Two tags").await;
+        insert(&repository, "3", "One tag").await;
+        repository.create(&NewNote {
+            id: String::from("4"),
+            title: String::from("Unrelated"),
+            content: String::from("Nothing in common"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await.unwrap();
+        repository.replace_tags("1", &[String::from("a"), String::from("b")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("a"), String::from("b")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("b")]).await.unwrap();
+        repository.create(&NewNote {
+            id: String::from("5"),
+            title: String::from("Untagged"),
+            content: String::from("Shares only the CONTENT word"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        }).await.unwrap();
+
+        let related = repository.related("1", 10).await.unwrap();
+        assert_eq!(related.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(), vec!["2", "3", "5"]);
+
+        assert_eq!(repository.related("1", 1).await.unwrap().len(), 1);
+        assert!(matches!(repository.related("missing", 10).await.unwrap_err().downcast::<DbError>(), Ok(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_neighbors() {
+        let repository = repository().await;
+        for (id, created_at) in [("b", "2021-01-02 00:00:00"), ("a", "2021-01-01 00:00:00"), ("c", "2021-01-02 00:00:00")] {
+            repository.create(&NewNote {
+                id: String::from(id),
+                title: id.to_uppercase(),
+                content: String::from("content"),
+                created_at: String::from(created_at),
+                expires_at: None,
+                format: String::from("markdown"),
+            }).await.unwrap();
+        }
+
+        let middle = repository.neighbors("b").await.unwrap();
+        assert_eq!(middle.previous.map(|note| note.id).as_deref(), Some("a"));
+        assert_eq!(middle.next.map(|note| (note.id, note.title)), Some((String::from("c"), String::from("C"))));
+
+        let first = repository.neighbors("a").await.unwrap();
+        assert!(first.previous.is_none());
+        assert_eq!(first.next.map(|note| note.id).as_deref(), Some("b"));
+
+        let last = repository.neighbors("c").await.unwrap();
+        assert_eq!(last.previous.map(|note| note.id).as_deref(), Some("b"));
+        assert!(last.next.is_none());
+
+        assert!(repository.neighbors("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_page_total_matches_count() {
+        let repository = repository().await;
+        for id in ["1", "2", "3", "4", "5"] {
+            insert(&repository, id, "Note").await;
+        }
+
+        for (limit, offset, expected_ids) in [(2, 0, vec!["1", "2"]), (2, 4, vec!["5"]), (2, 10, vec![])] {
+            let (notes, total) = repository.page(limit, offset).await.unwrap();
+            assert_eq!(notes.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(), expected_ids);
+            assert_eq!(total, repository.count().await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ids_after() {
+        let repository = repository().await;
+        assert!(repository.ids_after("", 10).await.unwrap().is_empty());
+
+        insert(&repository, "b", "Second").await;
+        insert(&repository, "a", "First").await;
+        insert(&repository, "d", "Fourth").await;
+        insert(&repository, "c", "Third").await;
+        repository.delete("c").await.unwrap();
+
+        assert_eq!(repository.ids_after("", 10).await.unwrap(), vec!["a", "b", "d"]);
+        assert_eq!(repository.ids_after("", 2).await.unwrap(), vec!["a", "b"]);
+        assert_eq!(repository.ids_after("b", 2).await.unwrap(), vec!["d"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_tags() {
+        let repository = repository().await;
+        insert(&repository, "a", "First").await;
+        repository.replace_tags("a", &[String::from("work")]).await.unwrap();
+
+        repository.delete("a").await.unwrap();
+
+        assert!(repository.all_tags().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repair_orphans() {
+        let repository = repository().await;
+        insert(&repository, "a", "First").await;
+        repository.replace_tags("a", &[String::from("work")]).await.unwrap();
+        // Orphans are left by databases that predate the foreign key, which is bypassed here to create one
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&repository.pool).await.unwrap();
+        sqlx::query("INSERT INTO note_tag (note_id, tag) VALUES ('gone', 'work'), ('gone', 'home')").execute(&repository.pool).await.unwrap();
+        sqlx::query("PRAGMA foreign_keys = ON").execute(&repository.pool).await.unwrap();
+
+        assert_eq!(repository.repair_orphans().await.unwrap(), RepairReport { orphaned_tags: 2 });
+        assert_eq!(repository.all_tags().await.unwrap(), vec![NoteTag { note_id: String::from("a"), tag: String::from("work") }]);
+        assert_eq!(repository.repair_orphans().await.unwrap(), RepairReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_get_many_ordered() {
+        let repository = repository().await;
+        for (id, title) in [("a", "First"), ("b", "Second"), ("c", "Third")] {
+            insert(&repository, id, title).await;
+        }
+
+        let ids = ["c", "missing", "a", "b", "c"].map(String::from);
+        let notes = repository.get_many_ordered(&ids).await.unwrap();
+        assert_eq!(notes.iter().map(|note| note.id.as_str()).collect::<Vec<_>>(), vec!["c", "a", "b"]);
+        assert!(repository.get_many_ordered(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_created_at() {
+        let repository = repository().await;
+        let inserted = insert(&repository, "a", "First").await;
+
+        let note = repository.set_created_at("a", "2019-05-01 12:00:00").await.unwrap();
+        assert_eq!(note.created_at, "2019-05-01 12:00:00");
+        assert_eq!((note.updated_at, note.version), (inserted.updated_at, inserted.version));
+
+        let missing = repository.set_created_at("missing", "2019-05-01 12:00:00").await.unwrap_err();
+        assert!(matches!(missing.downcast_ref::<DbError>(), Some(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_raw() {
+        let repository = repository().await;
+        insert(&repository, "a", "First").await;
+
+        let columns = repository.raw("a").await.unwrap();
+        let value = |name: &str| columns.iter().find(|column| column.name == name).map(|column| column.value.clone());
+        assert_eq!(value("id"), Some(RawValue::Text(String::from("a"))));
+        assert_eq!(value("created_at"), Some(RawValue::Text(String::from("2021-01-01 00:00:00"))));
+        assert_eq!(value("version"), Some(RawValue::Integer(1)));
+        assert_eq!(value("expires_at"), Some(RawValue::Null));
+
+        let missing = repository.raw("missing").await.unwrap_err();
+        assert!(matches!(missing.downcast_ref::<DbError>(), Some(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_oldest_and_newest() {
+        let repository = repository().await;
+        assert!(repository.oldest().await.unwrap().is_none());
+        assert!(repository.newest().await.unwrap().is_none());
+
+        for (id, created_at) in [("middle", "2021-01-02 00:00:00"), ("first", "2021-01-01 00:00:00"), ("last", "2021-01-03 00:00:00")] {
+            repository.create(&NewNote {
+                id: String::from(id),
+                title: String::from(id),
+                content: String::from("content"),
+                created_at: String::from(created_at),
+                expires_at: None,
+                format: String::from("markdown"),
+            }).await.unwrap();
+        }
+
+        assert_eq!(repository.oldest().await.unwrap().unwrap().id, "first");
+        assert_eq!(repository.newest().await.unwrap().unwrap().id, "last");
+    }
+
+    #[tokio::test]
+    async fn test_by_tags() {
+        let repository = repository().await;
+        insert(&repository, "1", "Both").await;
+        insert(&repository, "2", "Work only").await;
+        insert(&repository, "3", "Neither").await;
+        repository.replace_tags("1", &[String::from("home"), String::from("work")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("work")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("misc")]).await.unwrap();
+        let tags = [String::from("work"), String::from("home")];
+
+        let ids = |notes: Vec<Note>| notes.into_iter().map(|note| note.id).collect::<Vec<_>>();
+        assert_eq!(ids(repository.by_tags(&tags, TagMatch::All, 10, 0).await.unwrap()), vec!["1"]);
+        assert_eq!(ids(repository.by_tags(&tags, TagMatch::Any, 10, 0).await.unwrap()), vec!["1", "2"]);
+        assert_eq!(repository.count_by_tags(&tags, TagMatch::All).await.unwrap(), 1);
+        assert_eq!(repository.count_by_tags(&tags, TagMatch::Any).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_by_tag_overlap() {
+        let repository = repository().await;
+        insert(&repository, "1", "Work only").await;
+        insert(&repository, "2", "Both").await;
+        insert(&repository, "3", "Neither").await;
+        repository.replace_tags("1", &[String::from("work")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("home"), String::from("work")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("misc")]).await.unwrap();
+        let tags = [String::from("work"), String::from("home")];
+
+        let notes = repository.by_tag_overlap(&tags, 10).await.unwrap();
+        let matched: Vec<(&str, i64)> = notes.iter().map(|(note, matched)| (note.id.as_str(), *matched)).collect();
+
+        assert_eq!(matched, vec![("2", 2), ("1", 1)]);
+        assert_eq!(repository.by_tag_overlap(&tags, 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_count_by_matches_search_by() {
+        let repository = repository().await;
+        insert(&repository, "1", "Beta plan").await;
+        insert(&repository, "2", "Alpha plan").await;
+        insert(&repository, "3", "Shopping").await;
+        repository.replace_tags("1", &[String::from("work")]).await.unwrap();
+        repository.replace_tags("3", &[String::from("work")]).await.unwrap();
+
+        for (query, tag) in [("plan", None), ("plan", Some("work")), ("", Some("work")), ("", None), ("nothing", None)] {
+            let filter = SearchFilter { query: String::from(query), tag: tag.map(String::from), sort: SearchSort::Newest };
+            let notes = repository.search_by(&filter, 100, 0).await.unwrap();
+            assert_eq!(repository.count_by(&filter).await.unwrap(), notes.len() as i64, "{:?}", filter);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_with_version() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+        let update = |version: i64| UpdateNote {
+            title: String::from("Note 1"),
+            content: String::from("edited"),
+            updated_at: String::from("2021-01-02 00:00:00"),
+            version: Some(version),
+        };
+
+        let updated = repository.update("1", &update(1)).await.unwrap();
+        assert_eq!(updated.version, 2);
+
+        let stale = repository.update("1", &update(1)).await.unwrap_err();
+        assert!(matches!(stale.downcast_ref::<DbError>(), Some(DbError::Conflict)));
+        assert_eq!(repository.get("1").await.unwrap().version, 2);
+
+        repository.delete("1").await.unwrap();
+        let deleted = repository.update("1", &update(2)).await.unwrap_err();
+        assert!(matches!(deleted.downcast_ref::<DbError>(), Some(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_updated_at_never_goes_backwards() {
+        let repository = repository().await;
+        insert(&repository, "1", "Note 1").await;
+        let update = |updated_at: &str| UpdateNote {
+            title: String::from("Note 1"),
+            content: String::from("edited"),
+            updated_at: String::from(updated_at),
+            version: None,
+        };
+
+        let first = repository.update("1", &update("2021-01-02 00:00:00")).await.unwrap();
+        let second = repository.update("1", &update("2021-01-02 00:00:00")).await.unwrap();
+        let skewed = repository.update("1", &update("2020-12-31 00:00:00")).await.unwrap();
+        assert_eq!(first.updated_at, "2021-01-02 00:00:00");
+        assert_eq!(second.updated_at, "2021-01-02 00:00:00.001");
+        assert_eq!(skewed.updated_at, "2021-01-02 00:00:00.002");
+
+        let touched = repository.touch("1", "2020-12-31 00:00:00").await.unwrap();
+        assert_eq!(touched.updated_at, "2021-01-02 00:00:00.003");
+    }
+
+    #[tokio::test]
+    async fn test_append() {
+        let repository = repository().await;
+        insert(&repository, "1", "Journal").await;
+
+        let note = repository.append("1", "\nmore", "2021-01-02 00:00:00").await.unwrap();
+        assert_eq!(note.content, "content\nmore");
+        assert_eq!(note.version, 2);
+
+        let too_long = "x".repeat(CONTENT_MAX_LENGTH);
+        let result = repository.append("1", &too_long, "2021-01-03 00:00:00").await;
+        assert!(result.unwrap_err().downcast::<ValidationErrors>().is_ok());
+        assert_eq!(repository.get("1").await.unwrap().content, "content\nmore");
+
+        let result = repository.append("missing", "x", "2021-01-03 00:00:00").await;
+        assert!(matches!(result.unwrap_err().downcast::<DbError>(), Ok(DbError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_rename_tag_merges() {
+        let repository = repository().await;
+        insert(&repository, "1", "Closures").await;
+        insert(&repository, "2", "Promises").await;
+        repository.replace_tags("1", &[String::from("js")]).await.unwrap();
+        repository.replace_tags("2", &[String::from("javascript"), String::from("js")]).await.unwrap();
+
+        let renamed = repository.rename_tag("js", "javascript").await.unwrap();
+        assert_eq!(renamed, vec!["1", "2"]);
+
+        assert_eq!(repository.tags("1").await.unwrap(), vec!["javascript"]);
+        assert_eq!(repository.tags("2").await.unwrap(), vec!["javascript"]);
+        assert_eq!(repository.count_by_tag("js").await.unwrap(), 0);
     }
 }
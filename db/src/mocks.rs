@@ -0,0 +1,75 @@
+//! Mocks shared by the tests of the repository decorators.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mockall::mock;
+
+use crate::{ActivityBucket, ActivityCount, NewNote, Note, NoteNeighbors, NoteRepository, NoteSize, NoteTag, NoteTitle, NoteWrite, RawColumn, RepairReport, SearchFilter, TagColor, TagMatch, TagPair, UpdateNote, ViewedNote};
+
+mock! {
+    pub(crate) Repository {}
+    #[async_trait]
+    impl NoteRepository for Repository {
+        async fn all(&self) -> Result<Vec<Note>>;
+        async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>>;
+        async fn count(&self) -> Result<i64>;
+        async fn get(&self, id: &str) -> Result<Note>;
+        async fn create(&self, note: &NewNote) -> Result<Note>;
+        async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note>;
+        async fn delete(&self, id: &str) -> Result<Note>;
+        async fn touch(&self, id: &str, updated_at: &str) -> Result<Note>;
+        async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>>;
+        async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>>;
+        async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+        async fn tags(&self, id: &str) -> Result<Vec<String>>;
+        async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>>;
+        async fn all_tags(&self) -> Result<Vec<NoteTag>>;
+        async fn recent(&self, limit: i64) -> Result<Vec<Note>>;
+        async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)>;
+        async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>>;
+        async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>>;
+        async fn count_by_tag(&self, tag: &str) -> Result<i64>;
+        async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>>;
+        async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note>;
+        async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>>;
+        async fn try_get(&self, id: &str) -> Result<Option<Note>>;
+        async fn exists_any(&self) -> Result<bool>;
+        async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>>;
+        async fn delete_expired(&self, now: &str) -> Result<Vec<String>>;
+        async fn count_by(&self, filter: &SearchFilter) -> Result<i64>;
+        async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>>;
+        async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>>;
+        async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64>;
+        async fn oldest(&self) -> Result<Option<Note>>;
+        async fn newest(&self) -> Result<Option<Note>>;
+        async fn neighbors(&self, id: &str) -> Result<NoteNeighbors>;
+        async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>>;
+        async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)>;
+        async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>>;
+        async fn creation_minutes(&self) -> Result<Vec<ActivityCount>>;
+        async fn raw(&self, id: &str) -> Result<Vec<RawColumn>>;
+        async fn repair_orphans(&self) -> Result<RepairReport>;
+        async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>>;
+        async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note>;
+        async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>>;
+        async fn id_with_content(&self, content: &str) -> Result<Option<String>>;
+        async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()>;
+        async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>>;
+        async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>>;
+        async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()>;
+        async fn remove_tag_color(&self, tag: &str) -> Result<bool>;
+        async fn tag_colors(&self) -> Result<Vec<TagColor>>;
+        async fn most_edited(&self, limit: i64) -> Result<Vec<Note>>;
+        async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>>;
+        async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>>;
+        async fn storage_size(&self, id: &str) -> Result<NoteSize>;
+        async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>>;
+        async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>>;
+        async fn count_created_between(&self, from: &str, to: &str) -> Result<i64>;
+        async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>>;
+        async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>>;
+        async fn get_for_update(&self, id: &str) -> Result<Note>;
+        async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>>;
+        async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+
+/// One stored column of a row, as returned by `NoteRepository::raw`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawColumn {
+    pub name: String,
+    pub value: RawValue,
+}
+
+/// A value in SQLite's own storage class, before any conversion to a domain type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RawValue {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+/// Every column of `row` in table order, typed by the storage class of the stored value
+/// rather than the declared column type.
+pub(crate) fn columns(row: &SqliteRow) -> Result<Vec<RawColumn>> {
+    row.columns()
+        .iter()
+        .map(|column| {
+            let index = column.ordinal();
+            let raw = row.try_get_raw(index)?;
+            let value = if raw.is_null() {
+                RawValue::Null
+            } else {
+                match raw.type_info().name() {
+                    "INTEGER" => RawValue::Integer(row.try_get(index)?),
+                    "REAL" => RawValue::Real(row.try_get(index)?),
+                    "BLOB" => RawValue::Blob(row.try_get(index)?),
+                    _ => RawValue::Text(row.try_get(index)?),
+                }
+            };
+
+            Ok(RawColumn { name: column.name().to_string(), value })
+        })
+        .collect()
+}
@@ -0,0 +1,89 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{NewNote, Note, UpdateNote};
+
+/// Whether `Debug` output of notes leaves out their title and content. On unless disabled on startup,
+/// so a note logged anywhere with `{:?}` only shows its metadata.
+static REDACT_CONTENT: AtomicBool = AtomicBool::new(true);
+
+pub fn set_redact_content(redact: bool) {
+    REDACT_CONTENT.store(redact, Ordering::Relaxed);
+}
+
+/// A title or content that `Debug` prints as its length while redaction is on.
+struct Text<'a>(&'a str);
+
+impl Debug for Text<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if REDACT_CONTENT.load(Ordering::Relaxed) {
+            write!(f, "<{} chars>", self.0.chars().count())
+        } else {
+            Debug::fmt(self.0, f)
+        }
+    }
+}
+
+impl Debug for Note {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Note")
+            .field("id", &self.id)
+            .field("title", &Text(&self.title))
+            .field("content", &Text(&self.content))
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .field("version", &self.version)
+            .field("expires_at", &self.expires_at)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl Debug for NewNote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NewNote")
+            .field("id", &self.id)
+            .field("title", &Text(&self.title))
+            .field("content", &Text(&self.content))
+            .field("created_at", &self.created_at)
+            .field("expires_at", &self.expires_at)
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+impl Debug for UpdateNote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateNote")
+            .field("title", &Text(&self.title))
+            .field("content", &Text(&self.content))
+            .field("updated_at", &self.updated_at)
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NoteWrite;
+
+    #[test]
+    fn test_debug_output_omits_content() {
+        let create = NoteWrite::Create(NewNote {
+            id: String::from("new-id"),
+            title: String::from("Diagnosis"),
+            content: String::from("Private medical details."),
+            created_at: String::from("2021-01-01 00:00:00"),
+            expires_at: None,
+            format: String::from("markdown"),
+        });
+
+        let logged = format!("{:?}", create);
+
+        assert!(logged.contains("new-id"));
+        assert!(logged.contains("<9 chars>"));
+        assert!(!logged.contains("Private medical details."));
+        assert!(!logged.contains("Diagnosis"));
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{ActivityBucket, ActivityCount, NewNote, Note, NoteRepository, NoteSize, NoteTag, NoteTitle, NoteNeighbors, NoteWrite, RawColumn, RepairReport, SearchFilter, TagColor, TagMatch, TagPair, UpdateNote, ViewedNote};
+
+/// Sends read operations to a read replica and everything that modifies notes to the primary.
+pub struct ReplicatedNoteRepository<R: NoteRepository + Send + Sync, W: NoteRepository + Send + Sync> {
+    read: R,
+    write: W,
+}
+
+impl<R: NoteRepository + Send + Sync, W: NoteRepository + Send + Sync> ReplicatedNoteRepository<R, W> {
+    pub fn new(read: R, write: W) -> Self {
+        ReplicatedNoteRepository { read, write }
+    }
+}
+
+#[async_trait]
+impl<R: NoteRepository + Send + Sync, W: NoteRepository + Send + Sync> NoteRepository for ReplicatedNoteRepository<R, W> {
+    async fn all(&self) -> Result<Vec<Note>> {
+        self.read.all().await
+    }
+
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.read.all_paged(limit, offset).await
+    }
+
+    async fn count(&self) -> Result<i64> {
+        self.read.count().await
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        self.read.exists_any().await
+    }
+
+    async fn get(&self, id: &str) -> Result<Note> {
+        self.read.get(id).await
+    }
+
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        self.read.try_get(id).await
+    }
+
+    async fn create(&self, note: &NewNote) -> Result<Note> {
+        self.write.create(note).await
+    }
+
+    async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)> {
+        self.write.create_with_tags(note, tags).await
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        self.write.create_many(notes).await
+    }
+
+    async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
+        self.write.update(id, note).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Note> {
+        self.write.delete(id).await
+    }
+
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        self.write.delete_expired(now).await
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        self.write.repair_orphans().await
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        self.write.touch(id, updated_at).await
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        self.write.set_created_at(id, created_at).await
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        self.write.touch_many(ids, updated_at).await
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        self.write.append(id, text, updated_at).await
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        self.read.title_prefix(prefix, limit).await
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.read.search(query, limit, offset).await
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.read.search_by(filter, limit, offset).await
+    }
+
+    async fn tags(&self, id: &str) -> Result<Vec<String>> {
+        self.read.tags(id).await
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>> {
+        self.write.replace_tags(id, tags).await
+    }
+
+    async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>> {
+        self.write.transaction(writes).await
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        self.write.rename_tag(from, to).await
+    }
+
+    async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>> {
+        self.read.ids_with_tag(tag).await
+    }
+
+    // Reads that decide a write go to the primary, since the replica may not have caught up yet
+    async fn get_for_update(&self, id: &str) -> Result<Note> {
+        self.write.get_for_update(id).await
+    }
+
+    async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>> {
+        self.write.ids_with_tag_for_update(tag).await
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        self.read.all_tags().await
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        self.read.related(id, limit).await
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        self.read.recent(limit).await
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        self.read.activity(from, to, bucket).await
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        self.read.all_by(filter).await
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        self.read.count_by(filter).await
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.read.by_tags(tags, matching, limit, offset).await
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        self.read.by_tag_overlap(tags, limit).await
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        self.write.set_tag_color(tag, color).await
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        self.write.remove_tag_color(tag).await
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        self.read.tag_colors().await
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        self.read.tag_co_occurrence(limit).await
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        self.read.count_by_tags(tags, matching).await
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        self.read.oldest().await
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        self.read.newest().await
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        self.read.neighbors(id).await
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        self.read.activity_heatmap(from, to).await
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        self.read.page(limit, offset).await
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        self.read.ids_after(after, limit).await
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        self.read.creation_minutes().await
+    }
+
+    async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>> {
+        self.read.get_many_ordered(ids).await
+    }
+
+    /// Read from the primary, which holds the row as it was last written.
+    /// Read from the primary, so a note created right before is seen by the duplicate check.
+    async fn id_with_content(&self, content: &str) -> Result<Option<String>> {
+        self.write.id_with_content(content).await
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        self.write.record_view(id, viewed_at).await
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        self.read.recently_viewed(limit).await
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        self.read.most_edited(limit).await
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        self.read.created_between(from, to).await
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        self.read.count_created_between(from, to).await
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        self.read.tags_created_between(from, to).await
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.read.created_between_paged(from, to, limit, offset).await
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        self.read.storage_size(id).await
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        self.read.updated_since_viewed(limit).await
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        self.read.tag_trend(tag).await
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        self.write.raw(id).await
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        self.read.count_by_tag(tag).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mocks::MockRepository;
+
+    fn note(id: &str) -> Note {
+        Note {
+            id: String::from(id),
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            updated_at: String::from("2021-01-01T00:00:00Z"),
+            version: 1,
+            expires_at: None,
+            format: String::from("markdown"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reads_go_to_read_repository() {
+        let mut read = MockRepository::new();
+        read.expect_all()
+            .times(1)
+            .returning(|| Ok(vec![note("some-id")]));
+        read.expect_get()
+            .times(1)
+            .returning(|id| Ok(note(id)));
+        read.expect_search()
+            .times(1)
+            .returning(|_, _, _| Ok(vec![]));
+        // Expectations left unset on the write mock make any call to it panic
+        let repository = ReplicatedNoteRepository::new(read, MockRepository::new());
+
+        assert_eq!(repository.all().await.unwrap().len(), 1);
+        assert_eq!(repository.get("some-id").await.unwrap().id, "some-id");
+        assert!(repository.search("note", 10, 0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_writes_go_to_write_repository() {
+        let mut write = MockRepository::new();
+        write.expect_create()
+            .times(1)
+            .returning(|new_note| Ok(note(&new_note.id)));
+        write.expect_delete()
+            .times(1)
+            .returning(|id| Ok(note(id)));
+        let repository = ReplicatedNoteRepository::new(MockRepository::new(), write);
+        let new_note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content: String::from("This is a new note."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        assert_eq!(repository.create(&new_note).await.unwrap().id, "new-id");
+        assert_eq!(repository.delete("new-id").await.unwrap().id, "new-id");
+    }
+
+    #[tokio::test]
+    async fn test_reads_for_update_go_to_write_repository() {
+        let mut write = MockRepository::new();
+        write.expect_get_for_update()
+            .times(1)
+            .returning(|id| Ok(note(id)));
+        write.expect_ids_with_tag_for_update()
+            .times(1)
+            .returning(|_| Ok(vec![String::from("some-id")]));
+        let repository = ReplicatedNoteRepository::new(MockRepository::new(), write);
+
+        assert_eq!(repository.get_for_update("some-id").await.unwrap().id, "some-id");
+        assert_eq!(repository.ids_with_tag_for_update("work").await.unwrap(), vec!["some-id"]);
+    }
+}
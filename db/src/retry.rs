@@ -0,0 +1,67 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::time::Instant;
+
+const FIRST_DELAY: Duration = Duration::from_millis(100);
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Calls `connect` until it succeeds or `timeout` has passed, waiting twice as long after each
+/// failure, up to 5 seconds. Returns the last error once time is up. A zero `timeout` tries once.
+pub async fn connect_with_retry<T, F, Fut>(timeout: Duration, mut connect: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut delay = FIRST_DELAY;
+    loop {
+        let error = match connect().await {
+            Ok(connected) => return Ok(connected),
+            Err(error) => error,
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(error);
+        }
+        log::warn!("Database not available yet, retrying in {:?}: {:#}", delay, error);
+        tokio::time::sleep(delay.min(deadline - now)).await;
+        delay = (delay * 2).min(MAX_DELAY);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::SqliteNoteRepository;
+
+    fn missing_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("retry-test-{}-{}-{}", name, std::process::id(), chrono::offset::Utc::now().timestamp_millis()))
+    }
+
+    #[tokio::test]
+    async fn test_connects_once_database_is_available() {
+        let dir = missing_dir("available");
+        let database_url = format!("sqlite://{}?mode=rwc", dir.join("notes.db").display());
+        let created = dir.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            std::fs::create_dir_all(created).unwrap();
+        });
+
+        let repository = connect_with_retry(Duration::from_secs(5), || SqliteNoteRepository::new(&database_url)).await;
+
+        assert!(repository.is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_timeout() {
+        let database_url = format!("sqlite://{}?mode=rwc", missing_dir("timeout").join("notes.db").display());
+
+        let repository = connect_with_retry(Duration::from_millis(250), || SqliteNoteRepository::new(&database_url)).await;
+
+        assert!(repository.is_err());
+    }
+}
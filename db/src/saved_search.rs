@@ -0,0 +1,87 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use sqlx::FromRow;
+use sqlx::sqlite::SqlitePool;
+
+use crate::DbError;
+
+#[derive(Debug, FromRow, PartialEq, Eq, Clone)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+    pub tag: Option<String>,
+    /// One of the `SearchSort` names
+    pub sort: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NewSavedSearch {
+    pub name: String,
+    pub query: String,
+    pub tag: Option<String>,
+    pub sort: String,
+    pub created_at: String,
+}
+
+#[async_trait]
+pub trait SavedSearchRepository: Sync + Send {
+    async fn create(&self, search: &NewSavedSearch) -> Result<SavedSearch>;
+    async fn all(&self) -> Result<Vec<SavedSearch>>;
+    async fn get(&self, id: i64) -> Result<SavedSearch>;
+}
+
+#[derive(Clone)]
+pub struct SqliteSavedSearchRepository {
+    pool: SqlitePool,
+}
+
+impl SqliteSavedSearchRepository {
+    pub async fn new(database_url: &str) -> Result<Self> {
+        let pool = SqlitePool::connect(database_url).await?;
+
+        Ok(SqliteSavedSearchRepository { pool })
+    }
+
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        SqliteSavedSearchRepository { pool }
+    }
+}
+
+#[async_trait]
+impl SavedSearchRepository for SqliteSavedSearchRepository {
+    async fn create(&self, search: &NewSavedSearch) -> Result<SavedSearch> {
+        let created = sqlx::query_as!(
+            SavedSearch,
+            "INSERT INTO saved_search (name, query, tag, sort, created_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            search.name, search.query, search.tag, search.sort, search.created_at
+        )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(created)
+    }
+
+    async fn all(&self) -> Result<Vec<SavedSearch>> {
+        let searches = sqlx::query_as!(SavedSearch, "SELECT * FROM saved_search ORDER BY name, id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(searches)
+    }
+
+    async fn get(&self, id: i64) -> Result<SavedSearch> {
+        let search = sqlx::query_as!(SavedSearch, "SELECT * FROM saved_search WHERE id = ?", id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                match e {
+                    sqlx::Error::RowNotFound => DbError::NotFound,
+                    _ => DbError::SqlxError(e),
+                }
+            })?;
+
+        Ok(search)
+    }
+}
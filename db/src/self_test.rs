@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Context, Result};
+
+use crate::{NewNote, NoteFormat, NoteRepository, UpdateNote};
+
+/// Creates, reads, updates and deletes a temporary note to check that the database and its schema
+/// work end to end. The note is deleted again even when a later step fails.
+pub async fn self_test(repository: &dyn NoteRepository) -> Result<()> {
+    let now = chrono::offset::Utc::now().naive_utc();
+    let id = format!("self-test-{}-{}", std::process::id(), now.timestamp_millis());
+    repository.create(&NewNote {
+        id: id.clone(),
+        title: String::from("Self-test"),
+        content: String::from("Temporary note written by the startup self-test."),
+        created_at: now.to_string(),
+        expires_at: None,
+        format: NoteFormat::Markdown.as_str().to_string(),
+    }).await.context("self-test failed to create a note")?;
+
+    let result = read_and_update(repository, &id, &now.to_string()).await;
+    let deleted = repository.delete(&id).await.context("self-test failed to delete its note");
+    if let (Err(_), Err(error)) = (&result, &deleted) {
+        log::error!("Failed to clean up self-test note {}: {:#}", id, error);
+    }
+    result?;
+    deleted?;
+
+    Ok(())
+}
+
+async fn read_and_update(repository: &dyn NoteRepository, id: &str, now: &str) -> Result<()> {
+    let note = repository.get(id).await.context("self-test failed to read its note")?;
+    if note.title != "Self-test" {
+        return Err(anyhow!("self-test read back title {:?}", note.title));
+    }
+
+    let updated = repository.update(id, &UpdateNote {
+        title: String::from("Self-test (updated)"),
+        content: note.content,
+        updated_at: now.to_string(),
+        version: Some(note.version),
+    }).await.context("self-test failed to update its note")?;
+    if updated.title != "Self-test (updated)" {
+        return Err(anyhow!("self-test read back title {:?} after update", updated.title));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{SqliteNoteRepository, MIGRATOR};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+
+    async fn pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect_with(crate::collation::connect_options("sqlite::memory:").unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_self_test_passes_and_cleans_up() {
+        let pool = pool().await;
+        MIGRATOR.run(&pool).await.unwrap();
+        let repository = SqliteNoteRepository::from_pool(pool);
+
+        self_test(&repository).await.unwrap();
+        assert_eq!(repository.count().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_self_test_fails_on_broken_schema() {
+        let pool = pool().await;
+        sqlx::query("CREATE TABLE note (id TEXT PRIMARY KEY NOT NULL, title TEXT NOT NULL, content TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let repository = SqliteNoteRepository::from_pool(pool);
+
+        assert!(self_test(&repository).await.is_err());
+    }
+}
@@ -0,0 +1,539 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{ActivityBucket, ActivityCount, NewNote, Note, NoteRepository, NoteSize, NoteTag, NoteTitle, NoteNeighbors, NoteWrite, RawColumn, RepairReport, SearchFilter, TagColor, TagMatch, TagPair, UpdateNote, ViewedNote};
+
+/// Decorates a `NoteRepository`, logging a warning for every operation that takes longer than `threshold`.
+pub struct SlowQueryLoggingRepository<R: NoteRepository + Send + Sync> {
+    inner: R,
+    threshold: Duration,
+}
+
+impl<R: NoteRepository + Send + Sync> SlowQueryLoggingRepository<R> {
+    pub fn new(inner: R, threshold: Duration) -> Self {
+        SlowQueryLoggingRepository { inner, threshold }
+    }
+
+    fn observe(&self, operation: &str, id: Option<&str>, started: Instant) {
+        let elapsed = started.elapsed();
+        if elapsed <= self.threshold {
+            return;
+        }
+
+        match id {
+            Some(id) => log::warn!("slow query: {} took {}ms (note id {})", operation, elapsed.as_millis(), id),
+            None => log::warn!("slow query: {} took {}ms", operation, elapsed.as_millis()),
+        }
+    }
+}
+
+#[async_trait]
+impl<R: NoteRepository + Send + Sync> NoteRepository for SlowQueryLoggingRepository<R> {
+    async fn all(&self) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.all().await;
+        self.observe("all", None, started);
+        result
+    }
+
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.all_paged(limit, offset).await;
+        self.observe("all_paged", None, started);
+        result
+    }
+
+    async fn count(&self) -> Result<i64> {
+        let started = Instant::now();
+        let result = self.inner.count().await;
+        self.observe("count", None, started);
+        result
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        let started = Instant::now();
+        let result = self.inner.exists_any().await;
+        self.observe("exists_any", None, started);
+        result
+    }
+
+    async fn get(&self, id: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.get(id).await;
+        self.observe("get", Some(id), started);
+        result
+    }
+
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        let started = Instant::now();
+        let result = self.inner.try_get(id).await;
+        self.observe("try_get", Some(id), started);
+        result
+    }
+
+    async fn create(&self, note: &NewNote) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.create(note).await;
+        self.observe("create", Some(&note.id), started);
+        result
+    }
+
+    async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)> {
+        let started = Instant::now();
+        let result = self.inner.create_with_tags(note, tags).await;
+        self.observe("create_with_tags", Some(&note.id), started);
+        result
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.create_many(notes).await;
+        self.observe("create_many", None, started);
+        result
+    }
+
+    async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.update(id, note).await;
+        self.observe("update", Some(id), started);
+        result
+    }
+
+    async fn delete(&self, id: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.delete(id).await;
+        self.observe("delete", Some(id), started);
+        result
+    }
+
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.delete_expired(now).await;
+        self.observe("delete_expired", None, started);
+        result
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        let started = Instant::now();
+        let result = self.inner.repair_orphans().await;
+        self.observe("repair_orphans", None, started);
+        result
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.touch(id, updated_at).await;
+        self.observe("touch", Some(id), started);
+        result
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.set_created_at(id, created_at).await;
+        self.observe("set_created_at", Some(id), started);
+        result
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.touch_many(ids, updated_at).await;
+        self.observe("touch_many", None, started);
+        result
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.append(id, text, updated_at).await;
+        self.observe("append", Some(id), started);
+        result
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        let started = Instant::now();
+        let result = self.inner.title_prefix(prefix, limit).await;
+        self.observe("title_prefix", None, started);
+        result
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.search(query, limit, offset).await;
+        self.observe("search", None, started);
+        result
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.search_by(filter, limit, offset).await;
+        self.observe("search_by", None, started);
+        result
+    }
+
+    async fn tags(&self, id: &str) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.tags(id).await;
+        self.observe("tags", Some(id), started);
+        result
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.replace_tags(id, tags).await;
+        self.observe("replace_tags", Some(id), started);
+        result
+    }
+
+    async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.transaction(writes).await;
+        self.observe("transaction", None, started);
+        result
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.rename_tag(from, to).await;
+        self.observe("rename_tag", None, started);
+        result
+    }
+
+    async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.ids_with_tag(tag).await;
+        self.observe("ids_with_tag", None, started);
+        result
+    }
+
+    async fn get_for_update(&self, id: &str) -> Result<Note> {
+        let started = Instant::now();
+        let result = self.inner.get_for_update(id).await;
+        self.observe("get_for_update", Some(id), started);
+        result
+    }
+
+    async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.ids_with_tag_for_update(tag).await;
+        self.observe("ids_with_tag_for_update", None, started);
+        result
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        let started = Instant::now();
+        let result = self.inner.all_tags().await;
+        self.observe("all_tags", None, started);
+        result
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.related(id, limit).await;
+        self.observe("related", Some(id), started);
+        result
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.recent(limit).await;
+        self.observe("recent", None, started);
+        result
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        let started = Instant::now();
+        let result = self.inner.activity(from, to, bucket).await;
+        self.observe("activity", None, started);
+        result
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.all_by(filter).await;
+        self.observe("all_by", None, started);
+        result
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        let started = Instant::now();
+        let result = self.inner.count_by(filter).await;
+        self.observe("count_by", None, started);
+        result
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.by_tags(tags, matching, limit, offset).await;
+        self.observe("by_tags", None, started);
+        result
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        let started = Instant::now();
+        let result = self.inner.by_tag_overlap(tags, limit).await;
+        self.observe("by_tag_overlap", None, started);
+        result
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.set_tag_color(tag, color).await;
+        self.observe("set_tag_color", None, started);
+        result
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        let started = Instant::now();
+        let result = self.inner.remove_tag_color(tag).await;
+        self.observe("remove_tag_color", None, started);
+        result
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        let started = Instant::now();
+        let result = self.inner.tag_colors().await;
+        self.observe("tag_colors", None, started);
+        result
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        let started = Instant::now();
+        let result = self.inner.tag_co_occurrence(limit).await;
+        self.observe("tag_co_occurrence", None, started);
+        result
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        let started = Instant::now();
+        let result = self.inner.count_by_tags(tags, matching).await;
+        self.observe("count_by_tags", None, started);
+        result
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        let started = Instant::now();
+        let result = self.inner.oldest().await;
+        self.observe("oldest", None, started);
+        result
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        let started = Instant::now();
+        let result = self.inner.newest().await;
+        self.observe("newest", None, started);
+        result
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        let started = Instant::now();
+        let result = self.inner.neighbors(id).await;
+        self.observe("neighbors", Some(id), started);
+        result
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        let started = Instant::now();
+        let result = self.inner.activity_heatmap(from, to).await;
+        self.observe("activity_heatmap", None, started);
+        result
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        let started = Instant::now();
+        let result = self.inner.page(limit, offset).await;
+        self.observe("page", None, started);
+        result
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self.inner.ids_after(after, limit).await;
+        self.observe("ids_after", None, started);
+        result
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        let started = Instant::now();
+        let result = self.inner.creation_minutes().await;
+        self.observe("creation_minutes", None, started);
+        result
+    }
+
+    async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.get_many_ordered(ids).await;
+        self.observe("get_many_ordered", None, started);
+        result
+    }
+
+    async fn id_with_content(&self, content: &str) -> Result<Option<String>> {
+        let started = Instant::now();
+        let result = self.inner.id_with_content(content).await;
+        self.observe("id_with_content", None, started);
+        result
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        let started = Instant::now();
+        let result = self.inner.record_view(id, viewed_at).await;
+        self.observe("record_view", Some(id), started);
+        result
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        let started = Instant::now();
+        let result = self.inner.recently_viewed(limit).await;
+        self.observe("recently_viewed", None, started);
+        result
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.most_edited(limit).await;
+        self.observe("most_edited", None, started);
+        result
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.created_between(from, to).await;
+        self.observe("created_between", None, started);
+        result
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        let started = Instant::now();
+        let result = self.inner.count_created_between(from, to).await;
+        self.observe("count_created_between", None, started);
+        result
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        let started = Instant::now();
+        let result = self.inner.tags_created_between(from, to).await;
+        self.observe("tags_created_between", None, started);
+        result
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.created_between_paged(from, to, limit, offset).await;
+        self.observe("created_between_paged", None, started);
+        result
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        let started = Instant::now();
+        let result = self.inner.storage_size(id).await;
+        self.observe("storage_size", Some(id), started);
+        result
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        let started = Instant::now();
+        let result = self.inner.updated_since_viewed(limit).await;
+        self.observe("updated_since_viewed", None, started);
+        result
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        let started = Instant::now();
+        let result = self.inner.tag_trend(tag).await;
+        self.observe("tag_trend", None, started);
+        result
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        let started = Instant::now();
+        let result = self.inner.raw(id).await;
+        self.observe("raw", Some(id), started);
+        result
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        let started = Instant::now();
+        let result = self.inner.count_by_tag(tag).await;
+        self.observe("count_by_tag", None, started);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mocks::MockRepository;
+    use std::sync::Mutex;
+    use log::{LevelFilter, Log, Metadata, Record};
+
+    static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    fn warnings_about(id: &str) -> Vec<String> {
+        let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(LevelFilter::Warn));
+        WARNINGS.lock().unwrap().iter().filter(|warning| warning.contains(id)).cloned().collect()
+    }
+
+    fn note(id: &str) -> Note {
+        Note {
+            id: String::from(id),
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            updated_at: String::from("2021-01-01T00:00:00Z"),
+            version: 1,
+            expires_at: None,
+            format: String::from("markdown"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_query_is_logged() {
+        warnings_about("slow-id");
+        let mut mock = MockRepository::new();
+        mock.expect_get()
+            .times(1)
+            .returning(|id| {
+                std::thread::sleep(Duration::from_millis(20));
+                Ok(note(id))
+            });
+        let repository = SlowQueryLoggingRepository::new(mock, Duration::from_millis(5));
+
+        repository.get("slow-id").await.unwrap();
+
+        let warnings = warnings_about("slow-id");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("slow query: get took"));
+    }
+
+    #[tokio::test]
+    async fn test_fast_query_is_not_logged() {
+        warnings_about("fast-id");
+        let mut mock = MockRepository::new();
+        mock.expect_get()
+            .times(1)
+            .returning(|id| Ok(note(id)));
+        let repository = SlowQueryLoggingRepository::new(mock, Duration::from_secs(60));
+
+        repository.get("fast-id").await.unwrap();
+
+        assert!(warnings_about("fast-id").is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use std::future::Future;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use db::{ActivityBucket, ActivityCount, AuditRepository, NewAuditEntry, NewNote, Note, NoteNeighbors, NoteSize, NoteTag, NoteTags, NoteTitle, RawColumn, RepairReport, SearchFilter, TagColor, TagMatch, TagPair, UpdateNote, ViewedNote};
+
+use crate::NoteService;
+
+tokio::task_local! {
+    static ACTOR: Option<String>;
+    static SESSION: Option<String>;
+}
+
+/// Runs `f` with `actor` recorded as the user responsible for any mutation it performs.
+pub async fn with_actor<F: Future>(actor: Option<String>, f: F) -> F::Output {
+    ACTOR.scope(actor, f).await
+}
+
+fn current_actor() -> Option<String> {
+    ACTOR.try_with(|actor| actor.clone()).ok().flatten()
+}
+
+/// Runs `f` with any mutation it performs recorded as part of the editing session `session`.
+pub async fn with_session<F: Future>(session: Option<String>, f: F) -> F::Output {
+    SESSION.scope(session, f).await
+}
+
+fn current_session() -> Option<String> {
+    SESSION.try_with(|session| session.clone()).ok().flatten()
+}
+
+/// Decorates a `NoteService` so every successful mutation is written to the audit log.
+pub struct AuditingNoteService<S: NoteService, A: AuditRepository> {
+    inner: S,
+    audit: A,
+}
+
+impl<S: NoteService, A: AuditRepository> AuditingNoteService<S, A> {
+    pub fn new(inner: S, audit: A) -> Self {
+        AuditingNoteService { inner, audit }
+    }
+
+    async fn record(&self, operation: &str, note_id: &str) -> Result<()> {
+        self.audit.record(&NewAuditEntry {
+            operation: operation.to_string(),
+            note_id: note_id.to_string(),
+            user_id: current_actor(),
+            created_at: chrono::offset::Utc::now().naive_utc().to_string(),
+            session_id: current_session(),
+        }).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: NoteService, A: AuditRepository> NoteService for AuditingNoteService<S, A> {
+    async fn all(&self) -> Result<Vec<Note>> {
+        self.inner.all().await
+    }
+
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.all_paged(limit, offset).await
+    }
+
+    async fn count(&self) -> Result<i64> {
+        self.inner.count().await
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        self.inner.exists_any().await
+    }
+
+    async fn get(&self, id: &str) -> Result<Note> {
+        self.inner.get(id).await
+    }
+
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        self.inner.try_get(id).await
+    }
+
+    async fn create(&self, note: &NewNote) -> Result<Note> {
+        let created = self.inner.create(note).await?;
+        self.record("create", &created.id).await?;
+
+        Ok(created)
+    }
+
+    async fn create_with_tags(&self, note: &NewNote, tags: &NoteTags) -> Result<(Note, Vec<String>)> {
+        let (created, tags) = self.inner.create_with_tags(note, tags).await?;
+        self.record("create", &created.id).await?;
+
+        Ok((created, tags))
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        let created = self.inner.create_many(notes).await?;
+        for note in &created {
+            self.record("create", &note.id).await?;
+        }
+
+        Ok(created)
+    }
+
+    async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
+        let updated = self.inner.update(id, note).await?;
+        self.record("update", &updated.id).await?;
+
+        Ok(updated)
+    }
+
+    async fn update_many(&self, updates: &[(String, UpdateNote)]) -> Result<Vec<Note>> {
+        let updated = self.inner.update_many(updates).await?;
+        for note in &updated {
+            self.record("update", &note.id).await?;
+        }
+
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &str) -> Result<Note> {
+        let deleted = self.inner.delete(id).await?;
+        self.record("delete", &deleted.id).await?;
+
+        Ok(deleted)
+    }
+
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        let ids = self.inner.delete_expired(now).await?;
+        for id in &ids {
+            self.record("delete", id).await?;
+        }
+
+        Ok(ids)
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        self.inner.repair_orphans().await
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        let touched = self.inner.touch(id, updated_at).await?;
+        self.record("touch", &touched.id).await?;
+
+        Ok(touched)
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        let updated = self.inner.set_created_at(id, created_at).await?;
+        self.record("update", &updated.id).await?;
+
+        Ok(updated)
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        let touched = self.inner.touch_many(ids, updated_at).await?;
+        for note in &touched {
+            self.record("touch", &note.id).await?;
+        }
+
+        Ok(touched)
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        let appended = self.inner.append(id, text, updated_at).await?;
+        self.record("update", &appended.id).await?;
+
+        Ok(appended)
+    }
+
+    async fn insert(&self, id: &str, offset: usize, text: &str, updated_at: &str) -> Result<Note> {
+        let edited = self.inner.insert(id, offset, text, updated_at).await?;
+        self.record("update", &edited.id).await?;
+
+        Ok(edited)
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        self.inner.title_prefix(prefix, limit).await
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search(query, limit, offset).await
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search_by(filter, limit, offset).await
+    }
+
+    async fn search_in_tag(&self, tag: &str, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search_in_tag(tag, query, limit, offset).await
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &NoteTags) -> Result<Vec<String>> {
+        let replaced = self.inner.replace_tags(id, tags).await?;
+        self.record("replace_tags", id).await?;
+
+        Ok(replaced)
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let note_ids = self.inner.rename_tag(from, to).await?;
+        for note_id in &note_ids {
+            self.record("replace_tags", note_id).await?;
+        }
+
+        Ok(note_ids)
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        self.inner.all_tags().await
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        self.inner.related(id, limit).await
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.recent(limit).await
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        self.inner.activity(from, to, bucket).await
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        self.inner.record_view(id, viewed_at).await
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        self.inner.recently_viewed(limit).await
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.most_edited(limit).await
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        self.inner.created_between(from, to).await
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        self.inner.count_created_between(from, to).await
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        self.inner.tags_created_between(from, to).await
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.created_between_paged(from, to, limit, offset).await
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        self.inner.storage_size(id).await
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.updated_since_viewed(limit).await
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        self.inner.tag_trend(tag).await
+    }
+
+    async fn get_with_tags(&self, id: &str) -> Result<(Note, Vec<String>)> {
+        self.inner.get_with_tags(id).await
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        self.inner.all_by(filter).await
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        self.inner.count_by(filter).await
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.by_tags(tags, matching, limit, offset).await
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        self.inner.by_tag_overlap(tags, limit).await
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        self.inner.set_tag_color(tag, color).await
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        self.inner.remove_tag_color(tag).await
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        self.inner.tag_colors().await
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        self.inner.tag_co_occurrence(limit).await
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        self.inner.count_by_tags(tags, matching).await
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        self.inner.oldest().await
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        self.inner.newest().await
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        self.inner.neighbors(id).await
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        self.inner.activity_heatmap(from, to).await
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        self.inner.page(limit, offset).await
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        self.inner.ids_after(after, limit).await
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        self.inner.creation_minutes().await
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        self.inner.raw(id).await
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        self.inner.count_by_tag(tag).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mocks::MockService;
+    use db::AuditEntry;
+    use futures_util::future::FutureExt;
+    use mockall::{mock, predicate};
+
+    mock! {
+        Audit {}
+        #[async_trait]
+        impl AuditRepository for Audit {
+            async fn record(&self, entry: &NewAuditEntry) -> Result<AuditEntry>;
+            async fn recent(&self, limit: i64) -> Result<Vec<AuditEntry>>;
+            async fn by_session(&self, session_id: &str) -> Result<Vec<AuditEntry>>;
+        }
+    }
+
+    fn note(id: &str) -> Note {
+        Note {
+            id: String::from(id),
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            updated_at: String::from("2021-01-01T00:00:00Z"),
+            version: 1,
+            expires_at: None,
+            format: String::from("markdown"),
+        }
+    }
+
+    #[test]
+    fn test_create_records_one_entry() {
+        let mut inner = MockService::new();
+        inner.expect_create()
+            .times(1)
+            .returning(|_| Ok(note("new-id")));
+        let mut audit = MockAudit::new();
+        audit.expect_record()
+            .with(predicate::function(|entry: &NewAuditEntry| {
+                entry.operation == "create"
+                    && entry.note_id == "new-id"
+                    && entry.user_id.as_deref() == Some("user-1")
+            }))
+            .times(1)
+            .returning(|entry| Ok(AuditEntry {
+                id: 1,
+                operation: entry.operation.clone(),
+                note_id: entry.note_id.clone(),
+                user_id: entry.user_id.clone(),
+                created_at: entry.created_at.clone(),
+                session_id: entry.session_id.clone(),
+            }));
+        let service = AuditingNoteService::new(inner, audit);
+        let new_note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+        let created = with_actor(Some(String::from("user-1")), service.create(&new_note))
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(created.id, "new-id");
+    }
+
+    #[test]
+    fn test_mutation_records_session() {
+        let mut inner = MockService::new();
+        inner.expect_delete()
+            .times(2)
+            .returning(|id| Ok(note(id)));
+        let mut audit = MockAudit::new();
+        audit.expect_record()
+            .with(predicate::function(|entry: &NewAuditEntry| entry.note_id == "in-session" && entry.session_id.as_deref() == Some("session-1")))
+            .times(1)
+            .returning(|entry| Ok(AuditEntry {
+                id: 1,
+                operation: entry.operation.clone(),
+                note_id: entry.note_id.clone(),
+                user_id: entry.user_id.clone(),
+                created_at: entry.created_at.clone(),
+                session_id: entry.session_id.clone(),
+            }));
+        audit.expect_record()
+            .with(predicate::function(|entry: &NewAuditEntry| entry.note_id == "outside" && entry.session_id.is_none()))
+            .times(1)
+            .returning(|entry| Ok(AuditEntry {
+                id: 2,
+                operation: entry.operation.clone(),
+                note_id: entry.note_id.clone(),
+                user_id: entry.user_id.clone(),
+                created_at: entry.created_at.clone(),
+                session_id: None,
+            }));
+        let service = AuditingNoteService::new(inner, audit);
+
+        assert!(with_session(Some(String::from("session-1")), service.delete("in-session")).now_or_never().unwrap().is_ok());
+        assert!(service.delete("outside").now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_failed_mutation_is_not_recorded() {
+        let mut inner = MockService::new();
+        inner.expect_delete()
+            .times(1)
+            .returning(|_| Err(anyhow::anyhow!(db::DbError::NotFound)));
+        let mut audit = MockAudit::new();
+        audit.expect_record().times(0);
+        let service = AuditingNoteService::new(inner, audit);
+        let result = service.delete("missing").now_or_never().unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_reads_are_not_recorded() {
+        let mut inner = MockService::new();
+        inner.expect_get()
+            .times(1)
+            .returning(|id| Ok(note(id)));
+        let mut audit = MockAudit::new();
+        audit.expect_record().times(0);
+        let service = AuditingNoteService::new(inner, audit);
+        let fetched = service.get("some-id").now_or_never().unwrap().unwrap();
+        assert_eq!(fetched.id, "some-id");
+    }
+}
@@ -0,0 +1,15 @@
+use anyhow::Result;
+
+/// Callbacks `NoteServiceImpl` runs around every mutation, for cross-cutting concerns such as
+/// webhooks or cache invalidation. Operations are named as in the audit log: `create`, `update`,
+/// `delete`, `touch` and `replace_tags`.
+pub trait MutationHook: Send + Sync {
+    /// Runs after validation and before the change is written, once per note id known up front.
+    /// An error aborts the mutation.
+    fn before(&self, _operation: &str, _note_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Runs once per changed note after the change was written.
+    fn after(&self, _operation: &str, _note_id: &str) {}
+}
@@ -1,30 +1,387 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
 use anyhow::Result;
 use async_trait::async_trait;
+use db::ActivityBucket;
+use db::ActivityCount;
+use db::DbError;
 use db::NewNote;
 use db::Note;
 use db::NoteRepository;
+use db::NoteNeighbors;
+use db::NoteSize;
+use db::NoteTag;
+use db::NoteTags;
+use db::NoteTitle;
+use db::NoteWrite;
+use db::RawColumn;
+use db::RepairReport;
+use db::SearchFilter;
+use db::SearchSort;
+use db::TagColor;
+use db::TagMatch;
+use db::TagPair;
 use db::UpdateNote;
-use validator::Validate;
+use db::ViewedNote;
+use uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors};
 #[cfg(test)]
 use mockall::{mock, predicate::*};
 
+pub use audit::{with_actor, with_session, AuditingNoteService};
+pub use hook::MutationHook;
+pub use title_case::TitleCase;
+pub use write_lock::{WriteLock, WriteLockGuard, WriteLockedNoteService, WritesLocked};
+
+mod audit;
+mod hook;
+#[cfg(test)]
+mod mocks;
+mod title_case;
+mod write_lock;
+
 #[async_trait]
 pub trait NoteService: Sync + Send {
     async fn all(&self) -> Result<Vec<Note>>;
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn count(&self) -> Result<i64>;
     async fn get(&self, id: &str) -> Result<Note>;
+    /// Notes with an empty id get a generated one.
     async fn create(&self, note: &NewNote) -> Result<Note>;
     async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note>;
     async fn delete(&self, id: &str) -> Result<Note>;
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note>;
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>>;
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>>;
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn replace_tags(&self, id: &str, tags: &NoteTags) -> Result<Vec<String>>;
+    async fn all_tags(&self) -> Result<Vec<NoteTag>>;
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>>;
+    /// Notes with an empty id get a generated one.
+    async fn create_with_tags(&self, note: &NewNote, tags: &NoteTags) -> Result<(Note, Vec<String>)>;
+    /// Notes with an empty id get a generated one.
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>>;
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>>;
+    async fn count_by_tag(&self, tag: &str) -> Result<i64>;
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note>;
+    async fn insert(&self, id: &str, offset: usize, text: &str, updated_at: &str) -> Result<Note>;
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>>;
+    async fn try_get(&self, id: &str) -> Result<Option<Note>>;
+    async fn exists_any(&self) -> Result<bool>;
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>>;
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64>;
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>>;
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64>;
+    async fn oldest(&self) -> Result<Option<Note>>;
+    async fn newest(&self) -> Result<Option<Note>>;
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors>;
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>>;
+    async fn search_in_tag(&self, tag: &str, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)>;
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>>;
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>>;
+    async fn update_many(&self, updates: &[(String, UpdateNote)]) -> Result<Vec<Note>>;
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>>;
+    async fn repair_orphans(&self) -> Result<RepairReport>;
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note>;
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>>;
+    async fn get_with_tags(&self, id: &str) -> Result<(Note, Vec<String>)>;
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()>;
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>>;
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>>;
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()>;
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool>;
+    async fn tag_colors(&self) -> Result<Vec<TagColor>>;
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>>;
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>>;
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>>;
+    async fn storage_size(&self, id: &str) -> Result<NoteSize>;
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>>;
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>>;
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64>;
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>>;
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+}
+
+/// Returned for notes created with the same content as an existing note while unique content is enforced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateContent {
+    pub existing_id: String,
+}
+
+impl Display for DuplicateContent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "note {} already has this content", self.existing_id)
+    }
+}
+
+impl Error for DuplicateContent {}
+
+/// Times a create is retried with a fresh id after the id the service generated turned out to be taken.
+const ID_COLLISION_RETRIES: usize = 3;
+
+/// Whether `error` is the repository refusing an insert because the id is already taken.
+fn id_taken(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<DbError>(), Some(DbError::IdTaken))
 }
 
 pub struct NoteServiceImpl<R: NoteRepository + Send + Sync> {
     repository: R,
+    min_content_length: usize,
+    min_tags: usize,
+    max_tags: usize,
+    auto_title: bool,
+    default_content: Option<String>,
+    lowercase_tags: bool,
+    unique_content: bool,
+    reject_binary_content: bool,
+    title_case: TitleCase,
+    hooks: Vec<Box<dyn MutationHook>>,
 }
 
 impl<R: NoteRepository + Send + Sync> NoteServiceImpl<R> {
     pub fn new(repository: R) -> Self {
-        NoteServiceImpl { repository }
+        NoteServiceImpl { repository, min_content_length: 0, min_tags: 0, max_tags: 0, auto_title: false, default_content: None, lowercase_tags: false, unique_content: false, reject_binary_content: false, title_case: TitleCase::None, hooks: Vec::new() }
+    }
+
+    /// Rejects notes whose trimmed content is shorter than `min_content_length` characters.
+    /// Zero, the default, disables the check.
+    pub fn with_min_content_length(mut self, min_content_length: usize) -> Self {
+        self.min_content_length = min_content_length;
+        self
+    }
+
+    /// Rejects notes created or retagged with fewer than `min_tags` or more than `max_tags` distinct tags.
+    /// Zero, the default for both, disables that bound.
+    pub fn with_tag_count_limits(mut self, min_tags: usize, max_tags: usize) -> Self {
+        self.min_tags = min_tags;
+        self.max_tags = max_tags;
+        self
+    }
+
+    /// Creates notes sent with a blank title under a title taken from the first line of their
+    /// content, instead of rejecting them.
+    pub fn with_auto_title(mut self, auto_title: bool) -> Self {
+        self.auto_title = auto_title;
+        self
+    }
+
+    /// Creates notes sent without content with `default_content` instead of rejecting them.
+    pub fn with_default_content(mut self, default_content: Option<String>) -> Self {
+        self.default_content = default_content;
+        self
+    }
+
+    /// Stores tags in lowercase, so `Work` and `work` are the same tag.
+    pub fn with_lowercase_tags(mut self, lowercase_tags: bool) -> Self {
+        self.lowercase_tags = lowercase_tags;
+        self
+    }
+
+    /// Rejects notes created with the same content as an existing note with `DuplicateContent`.
+    pub fn with_unique_content(mut self, unique_content: bool) -> Self {
+        self.unique_content = unique_content;
+        self
+    }
+
+    /// Rejects content with NUL or other control characters besides tabs and line breaks, or with
+    /// U+FFFD replacement characters left behind by a lossy decode of non-UTF-8 data.
+    pub fn with_reject_binary_content(mut self, reject_binary_content: bool) -> Self {
+        self.reject_binary_content = reject_binary_content;
+        self
+    }
+
+    /// Cases the titles of created and updated notes.
+    pub fn with_title_case(mut self, title_case: TitleCase) -> Self {
+        self.title_case = title_case;
+        self
+    }
+
+    /// Runs `hook` around every mutation, after the hooks added before it.
+    pub fn with_hook(mut self, hook: impl MutationHook + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    fn before_mutation(&self, operation: &str, note_id: &str) -> Result<()> {
+        for hook in &self.hooks {
+            hook.before(operation, note_id)?;
+        }
+
+        Ok(())
+    }
+
+    fn after_mutation(&self, operation: &str, note_id: &str) {
+        for hook in &self.hooks {
+            hook.after(operation, note_id);
+        }
+    }
+
+    /// Fills in the default content and then the title, where those are enabled and missing, and
+    /// cases the title.
+    fn completed<'a>(&self, note: &'a NewNote) -> Cow<'a, NewNote> {
+        let note = match &self.default_content {
+            Some(content) if note.content.trim().is_empty() => Cow::Owned(NewNote { content: content.clone(), ..note.clone() }),
+            _ => Cow::Borrowed(note),
+        };
+        let auto_title = if self.auto_title && note.title.trim().is_empty() { db::title_from_content(&note.content) } else { None };
+        let note = match auto_title {
+            Some(title) => Cow::Owned(NewNote { title, ..note.into_owned() }),
+            None => note,
+        };
+
+        let title = self.title_case.apply(&note.title);
+        if title == note.title {
+            return note;
+        }
+        Cow::Owned(NewNote { title, ..note.into_owned() })
+    }
+
+    fn cased<'a>(&self, note: &'a UpdateNote) -> Cow<'a, UpdateNote> {
+        let title = self.title_case.apply(&note.title);
+        if title == note.title {
+            return Cow::Borrowed(note);
+        }
+        Cow::Owned(UpdateNote { title, ..note.clone() })
+    }
+
+    /// Lowercases `tag` while `lowercase_tags` is set, so lookups find tags stored by `normalized_tags`.
+    fn normalized_tag(&self, tag: &str) -> String {
+        if self.lowercase_tags { tag.to_lowercase() } else { tag.to_string() }
+    }
+
+    /// Lowercases the tags if enabled and drops repeated ones, keeping the first occurrence of each.
+    fn normalized_tags(&self, tags: &NoteTags) -> NoteTags {
+        let mut seen = HashSet::new();
+        let tags = tags.tags.iter()
+            .map(|tag| self.normalized_tag(tag))
+            .filter(|tag| seen.insert(tag.clone()))
+            .collect();
+
+        NoteTags { tags }
+    }
+
+    /// Rejects binary `text` in `field` while `reject_binary_content` is set.
+    fn validate_text(&self, field: &'static str, text: &str) -> Result<(), ValidationErrors> {
+        if self.reject_binary_content && text.chars().any(|c| c == char::REPLACEMENT_CHARACTER || (c.is_control() && !matches!(c, '\t' | '\n' | '\r'))) {
+            let mut error = ValidationError::new("binary_content");
+            error.message = Some(format!("{} must be text, without control characters or invalid UTF-8", field).into());
+            let mut errors = ValidationErrors::new();
+            errors.add(field, error);
+            return Err(errors);
+        }
+
+        Ok(())
+    }
+
+    fn validate_content(&self, content: &str) -> Result<(), ValidationErrors> {
+        self.validate_text("content", content)?;
+        if content.trim().chars().count() >= self.min_content_length {
+            return Ok(());
+        }
+
+        let mut error = ValidationError::new("min_content_length");
+        error.message = Some(format!("content must be at least {} characters long", self.min_content_length).into());
+        error.add_param("min".into(), &self.min_content_length);
+        let mut errors = ValidationErrors::new();
+        errors.add("content", error);
+        Err(errors)
+    }
+
+    fn validate_tag_count(&self, tags: &[String]) -> Result<(), ValidationErrors> {
+        let count = tags.iter().collect::<HashSet<_>>().len();
+        let message = if count < self.min_tags {
+            format!("notes need at least {} tags", self.min_tags)
+        } else if self.max_tags > 0 && count > self.max_tags {
+            format!("notes may have at most {} tags", self.max_tags)
+        } else {
+            return Ok(());
+        };
+
+        let mut error = ValidationError::new("tag_count");
+        error.message = Some(message.into());
+        error.add_param("min".into(), &self.min_tags);
+        error.add_param("max".into(), &self.max_tags);
+        error.add_param("value".into(), &count);
+        let mut errors = ValidationErrors::new();
+        errors.add("tags", error);
+        Err(errors)
     }
+
+    async fn check_unique_content(&self, content: &str) -> Result<()> {
+        if !self.unique_content {
+            return Ok(());
+        }
+
+        match self.repository.id_with_content(content).await? {
+            Some(existing_id) => Err(DuplicateContent { existing_id }.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Applies `edit` to the stored content and saves the result, failing with a conflict if the
+    /// note changed in between.
+    async fn edit_content<F>(&self, id: &str, updated_at: &str, edit: F) -> Result<Note>
+    where
+        F: FnOnce(&str) -> Result<String, ValidationErrors> + Send,
+    {
+        let note = self.repository.get_for_update(id).await?;
+        let update = UpdateNote {
+            content: edit(&note.content)?,
+            title: note.title,
+            updated_at: updated_at.to_string(),
+            version: Some(note.version),
+        };
+        update.validate()?;
+        self.validate_content(&update.content)?;
+        self.before_mutation("update", id)?;
+
+        let updated = self.repository.update(id, &update).await?;
+        self.after_mutation("update", id);
+
+        Ok(updated)
+    }
+}
+
+/// Inserts `text` before the character at `offset`, which may be at most the content's length in characters.
+fn insert_at(content: &str, offset: usize, text: &str) -> Result<String, ValidationErrors> {
+    let byte_index = if offset == content.chars().count() {
+        Some(content.len())
+    } else {
+        content.char_indices().nth(offset).map(|(index, _)| index)
+    };
+    let Some(byte_index) = byte_index else {
+        let mut error = ValidationError::new("range");
+        error.add_param("value".into(), &offset);
+        error.add_param("max".into(), &content.chars().count());
+        let mut errors = ValidationErrors::new();
+        errors.add("offset", error);
+        return Err(errors);
+    };
+
+    let mut edited = String::with_capacity(content.len() + text.len());
+    edited.push_str(&content[..byte_index]);
+    edited.push_str(text);
+    edited.push_str(&content[byte_index..]);
+    Ok(edited)
+}
+
+/// Accepts `#rrggbb` hex colors only, so every client can render them as is.
+fn validate_color(color: &str) -> Result<(), ValidationErrors> {
+    if color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(());
+    }
+
+    let mut error = ValidationError::new("color");
+    error.message = Some(Cow::from("color must be a #rrggbb hex color"));
+    let mut errors = ValidationErrors::new();
+    errors.add("color", error);
+    Err(errors)
 }
 
 #[async_trait]
@@ -33,24 +390,410 @@ impl<R: NoteRepository + Send + Sync> NoteService for NoteServiceImpl<R> {
         self.repository.all().await
     }
 
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.repository.all_paged(limit, offset).await
+    }
+
+    async fn count(&self) -> Result<i64> {
+        self.repository.count().await
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        self.repository.exists_any().await
+    }
+
     async fn get(&self, id: &str) -> Result<Note> {
         self.repository.get(id).await
     }
 
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        self.repository.try_get(id).await
+    }
+
     async fn create(&self, note: &NewNote) -> Result<Note> {
+        let mut note = self.completed(note).into_owned();
+        note.validate()?;
+        self.validate_content(&note.content)?;
+        self.validate_tag_count(&[])?;
+        self.check_unique_content(&note.content).await?;
+
+        let generated = note.id.is_empty();
+        let mut attempt = 0;
+        let created = loop {
+            if generated {
+                note.id = Uuid::new_v4().to_string();
+            }
+            self.before_mutation("create", &note.id)?;
+            match self.repository.create(&note).await {
+                Err(error) if generated && attempt < ID_COLLISION_RETRIES && id_taken(&error) => attempt += 1,
+                result => break result?,
+            }
+        };
+        self.after_mutation("create", &created.id);
+
+        Ok(created)
+    }
+
+    async fn create_with_tags(&self, note: &NewNote, tags: &NoteTags) -> Result<(Note, Vec<String>)> {
+        let mut note = self.completed(note).into_owned();
+        let tags = self.normalized_tags(tags);
         note.validate()?;
+        self.validate_content(&note.content)?;
+        tags.validate()?;
+        self.validate_tag_count(&tags.tags)?;
+        self.check_unique_content(&note.content).await?;
+
+        let generated = note.id.is_empty();
+        let mut attempt = 0;
+        let (created, tags) = loop {
+            if generated {
+                note.id = Uuid::new_v4().to_string();
+            }
+            self.before_mutation("create", &note.id)?;
+            match self.repository.create_with_tags(&note, &tags.tags).await {
+                Err(error) if generated && attempt < ID_COLLISION_RETRIES && id_taken(&error) => attempt += 1,
+                result => break result?,
+            }
+        };
+        self.after_mutation("create", &created.id);
+
+        Ok((created, tags))
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        let mut notes: Vec<NewNote> = notes.iter().map(|note| self.completed(note).into_owned()).collect();
+        for note in &notes {
+            note.validate()?;
+            self.validate_content(&note.content)?;
+            self.validate_tag_count(&[])?;
+            self.check_unique_content(&note.content).await?;
+        }
+
+        // A taken id rolls back the whole batch, so every generated id is drawn again
+        let generated: Vec<bool> = notes.iter().map(|note| note.id.is_empty()).collect();
+        let mut attempt = 0;
+        let created = loop {
+            for (note, generated) in notes.iter_mut().zip(&generated) {
+                if *generated {
+                    note.id = Uuid::new_v4().to_string();
+                }
+            }
+            for note in &notes {
+                self.before_mutation("create", &note.id)?;
+            }
+            match self.repository.create_many(&notes).await {
+                Err(error) if generated.contains(&true) && attempt < ID_COLLISION_RETRIES && id_taken(&error) => attempt += 1,
+                result => break result?,
+            }
+        };
+        for note in &created {
+            self.after_mutation("create", &note.id);
+        }
 
-        self.repository.create(note).await
+        Ok(created)
     }
 
     async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
+        let note = self.cased(note);
         note.validate()?;
+        self.validate_content(&note.content)?;
+        self.before_mutation("update", id)?;
+
+        let updated = self.repository.update(id, &note).await?;
+        self.after_mutation("update", id);
+
+        Ok(updated)
+    }
+
+    async fn update_many(&self, updates: &[(String, UpdateNote)]) -> Result<Vec<Note>> {
+        let updates: Vec<(String, UpdateNote)> = updates.iter()
+            .map(|(id, note)| (id.clone(), self.cased(note).into_owned()))
+            .collect();
+        for (_, note) in &updates {
+            note.validate()?;
+            self.validate_content(&note.content)?;
+        }
+        for (id, _) in &updates {
+            self.before_mutation("update", id)?;
+        }
+
+        let writes: Vec<NoteWrite> = updates.iter()
+            .map(|(id, note)| NoteWrite::Update { id: id.clone(), note: note.clone() })
+            .collect();
+        let updated = self.repository.transaction(&writes).await?;
+        for note in &updated {
+            self.after_mutation("update", &note.id);
+        }
 
-        self.repository.update(id, note).await
+        Ok(updated)
     }
 
     async fn delete(&self, id: &str) -> Result<Note> {
-        self.repository.delete(id).await
+        self.before_mutation("delete", id)?;
+
+        let deleted = self.repository.delete(id).await?;
+        self.after_mutation("delete", id);
+
+        Ok(deleted)
+    }
+
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        let ids = self.repository.delete_expired(now).await?;
+        for id in &ids {
+            self.after_mutation("delete", id);
+        }
+
+        Ok(ids)
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        self.repository.repair_orphans().await
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        self.before_mutation("touch", id)?;
+
+        let touched = self.repository.touch(id, updated_at).await?;
+        self.after_mutation("touch", id);
+
+        Ok(touched)
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        self.before_mutation("update", id)?;
+
+        let updated = self.repository.set_created_at(id, created_at).await?;
+        self.after_mutation("update", id);
+
+        Ok(updated)
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        for id in ids {
+            self.before_mutation("touch", id)?;
+        }
+
+        let touched = self.repository.touch_many(ids, updated_at).await?;
+        for note in &touched {
+            self.after_mutation("touch", &note.id);
+        }
+
+        Ok(touched)
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        if text.is_empty() {
+            let mut errors = ValidationErrors::new();
+            errors.add("text", ValidationError::new("length"));
+            return Err(errors.into());
+        }
+        self.validate_text("text", text)?;
+        self.before_mutation("update", id)?;
+
+        let appended = self.repository.append(id, text, updated_at).await?;
+        self.after_mutation("update", id);
+
+        Ok(appended)
+    }
+
+    async fn insert(&self, id: &str, offset: usize, text: &str, updated_at: &str) -> Result<Note> {
+        self.edit_content(id, updated_at, |content| insert_at(content, offset, text)).await
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        self.repository.title_prefix(prefix, limit).await
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.repository.search(query, limit, offset).await
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.repository.search_by(filter, limit, offset).await
+    }
+
+    async fn search_in_tag(&self, tag: &str, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        let filter = SearchFilter {
+            query: query.to_string(),
+            tag: Some(tag.to_string()),
+            // Same order as `search`
+            sort: SearchSort::Newest,
+        };
+
+        self.repository.search_by(&filter, limit, offset).await
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &NoteTags) -> Result<Vec<String>> {
+        let tags = self.normalized_tags(tags);
+        tags.validate()?;
+        self.validate_tag_count(&tags.tags)?;
+        self.before_mutation("replace_tags", id)?;
+
+        let replaced = self.repository.replace_tags(id, &tags.tags).await?;
+        self.after_mutation("replace_tags", id);
+
+        Ok(replaced)
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let (from, to) = (self.normalized_tag(from), self.normalized_tag(to));
+        NoteTags { tags: vec![from.clone(), to.clone()] }.validate()?;
+
+        // Notes tagged between this lookup and the rename are renamed without a `before` call
+        if !self.hooks.is_empty() {
+            for note_id in self.repository.ids_with_tag_for_update(&from).await? {
+                self.before_mutation("replace_tags", &note_id)?;
+            }
+        }
+        let note_ids = self.repository.rename_tag(&from, &to).await?;
+        for note_id in &note_ids {
+            self.after_mutation("replace_tags", note_id);
+        }
+
+        Ok(note_ids)
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        self.repository.all_tags().await
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        self.repository.related(id, limit).await
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        self.repository.recent(limit).await
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        self.repository.activity(from, to, bucket).await
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        self.repository.record_view(id, viewed_at).await
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        self.repository.recently_viewed(limit).await
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        self.repository.most_edited(limit).await
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        self.repository.created_between(from, to).await
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        self.repository.count_created_between(from, to).await
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        self.repository.tags_created_between(from, to).await
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.repository.created_between_paged(from, to, limit, offset).await
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        self.repository.storage_size(id).await
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        self.repository.updated_since_viewed(limit).await
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        let tag = self.normalized_tags(&NoteTags { tags: vec![tag.to_string()] });
+
+        self.repository.tag_trend(&tag.tags[0]).await
+    }
+
+    async fn get_with_tags(&self, id: &str) -> Result<(Note, Vec<String>)> {
+        let note = self.repository.get(id).await?;
+        let tags = self.repository.tags(id).await?;
+
+        Ok((note, tags))
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        self.repository.all_by(filter).await
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        self.repository.count_by(filter).await
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.repository.by_tags(tags, matching, limit, offset).await
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        self.repository.by_tag_overlap(tags, limit).await
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        let tag = self.normalized_tags(&NoteTags { tags: vec![tag.to_string()] });
+        tag.validate()?;
+        validate_color(color)?;
+
+        self.repository.set_tag_color(&tag.tags[0], color).await
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        let tag = self.normalized_tags(&NoteTags { tags: vec![tag.to_string()] });
+
+        self.repository.remove_tag_color(&tag.tags[0]).await
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        self.repository.tag_colors().await
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        self.repository.tag_co_occurrence(limit).await
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        self.repository.count_by_tags(tags, matching).await
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        self.repository.oldest().await
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        self.repository.newest().await
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        self.repository.neighbors(id).await
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        self.repository.activity_heatmap(from, to).await
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        self.repository.page(limit, offset).await
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        self.repository.ids_after(after, limit).await
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        self.repository.creation_minutes().await
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        self.repository.raw(id).await
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        self.repository.count_by_tag(&self.normalized_tag(tag)).await
     }
 }
 
@@ -58,17 +801,75 @@ impl<R: NoteRepository + Send + Sync> NoteService for NoteServiceImpl<R> {
 mod test {
     use super::*;
     use futures_util::future::FutureExt;
+    use db::NoteWrite;
     use mockall::predicate;
+    use std::sync::{Arc, Mutex};
 
     mock! {
         Repository {}
         #[async_trait]
         impl db::NoteRepository for Repository {
             async fn all(&self) -> Result<Vec<Note>>;
+            async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>>;
+            async fn count(&self) -> Result<i64>;
             async fn get(&self, id: &str) -> Result<Note>;
             async fn create(&self, note: &NewNote) -> Result<Note>;
             async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note>;
             async fn delete(&self, id: &str) -> Result<Note>;
+            async fn touch(&self, id: &str, updated_at: &str) -> Result<Note>;
+            async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>>;
+            async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>>;
+            async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
+            async fn tags(&self, id: &str) -> Result<Vec<String>>;
+            async fn replace_tags(&self, id: &str, tags: &[String]) -> Result<Vec<String>>;
+            async fn all_tags(&self) -> Result<Vec<NoteTag>>;
+            async fn recent(&self, limit: i64) -> Result<Vec<Note>>;
+            async fn create_with_tags(&self, note: &NewNote, tags: &[String]) -> Result<(Note, Vec<String>)>;
+            async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>>;
+            async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>>;
+            async fn count_by_tag(&self, tag: &str) -> Result<i64>;
+            async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>>;
+            async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note>;
+            async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>>;
+            async fn try_get(&self, id: &str) -> Result<Option<Note>>;
+            async fn exists_any(&self) -> Result<bool>;
+            async fn transaction(&self, writes: &[NoteWrite]) -> Result<Vec<Note>>;
+            async fn delete_expired(&self, now: &str) -> Result<Vec<String>>;
+            async fn count_by(&self, filter: &SearchFilter) -> Result<i64>;
+            async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>>;
+            async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>>;
+            async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64>;
+            async fn oldest(&self) -> Result<Option<Note>>;
+            async fn newest(&self) -> Result<Option<Note>>;
+            async fn neighbors(&self, id: &str) -> Result<NoteNeighbors>;
+            async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>>;
+            async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)>;
+            async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>>;
+            async fn creation_minutes(&self) -> Result<Vec<ActivityCount>>;
+            async fn raw(&self, id: &str) -> Result<Vec<RawColumn>>;
+            async fn repair_orphans(&self) -> Result<RepairReport>;
+            async fn get_many_ordered(&self, ids: &[String]) -> Result<Vec<Note>>;
+            async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note>;
+            async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>>;
+            async fn id_with_content(&self, content: &str) -> Result<Option<String>>;
+            async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()>;
+            async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>>;
+            async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>>;
+            async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()>;
+            async fn remove_tag_color(&self, tag: &str) -> Result<bool>;
+            async fn tag_colors(&self) -> Result<Vec<TagColor>>;
+            async fn most_edited(&self, limit: i64) -> Result<Vec<Note>>;
+            async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>>;
+            async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>>;
+            async fn storage_size(&self, id: &str) -> Result<NoteSize>;
+            async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>>;
+            async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>>;
+            async fn count_created_between(&self, from: &str, to: &str) -> Result<i64>;
+            async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>>;
+            async fn ids_with_tag(&self, tag: &str) -> Result<Vec<String>>;
+            async fn get_for_update(&self, id: &str) -> Result<Note>;
+            async fn ids_with_tag_for_update(&self, tag: &str) -> Result<Vec<String>>;
+            async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>>;
         }
     }
 
@@ -82,6 +883,10 @@ mod test {
                 title: String::from("Note 1"),
                 content: String::from("This is note #1."),
                 created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from("2021-01-01T00:00:00Z"),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
             }]));
         let service = NoteServiceImpl::new(mock);
         let notes = service.all().now_or_never().unwrap().unwrap();
@@ -89,6 +894,29 @@ mod test {
         assert_eq!(notes[0].id, "14322988-32fe-447c-ac38-06fb6c699b4a")
     }
 
+    #[test]
+    fn test_all_paged() {
+        let mut mock = MockRepository::new();
+        mock.expect_all_paged()
+            .with(predicate::eq(10), predicate::eq(20))
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        let service = NoteServiceImpl::new(mock);
+        let notes = service.all_paged(10, 20).now_or_never().unwrap().unwrap();
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn test_count() {
+        let mut mock = MockRepository::new();
+        mock.expect_count()
+            .times(1)
+            .returning(|| Ok(3));
+        let service = NoteServiceImpl::new(mock);
+        let count = service.count().now_or_never().unwrap().unwrap();
+        assert_eq!(count, 3);
+    }
+
     #[test]
     fn test_get() {
         let mut mock = MockRepository::new();
@@ -101,6 +929,10 @@ mod test {
                 title: String::from("Note 1"),
                 content: String::from("This is note #2."),
                 created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from("2021-01-01T00:00:00Z"),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
             }));
         let service = NoteServiceImpl::new(mock);
         let note = service.get(expected_id).now_or_never().unwrap().unwrap();
@@ -115,6 +947,8 @@ mod test {
             title: String::from("Note 1"),
             content: String::from("This is note #2."),
             created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
         };
         let new_note_test = new_note.clone();
         mock.expect_create()
@@ -125,6 +959,10 @@ mod test {
                 title: new_note_test.title.clone(),
                 content: new_note_test.content.clone(),
                 created_at: new_note_test.created_at.clone(),
+                updated_at: new_note_test.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
             }));
         let service = NoteServiceImpl::new(mock);
         let note = service.create(&new_note).now_or_never().unwrap().unwrap();
@@ -140,20 +978,535 @@ mod test {
             title: String::new(),
             content: String::from("This is a new note."),
             created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
         };
         let result = service.create(&invalid_note).now_or_never();
         assert!(result.is_some(), "Expected a synchronous result");
         assert!(result.unwrap().is_err(), "Expected an error due to validation");
     }
 
+    struct RecordingHook {
+        events: Arc<Mutex<Vec<String>>>,
+        reject: bool,
+    }
+
+    impl MutationHook for RecordingHook {
+        fn before(&self, operation: &str, note_id: &str) -> Result<()> {
+            self.events.lock().unwrap().push(format!("before {} {}", operation, note_id));
+            if self.reject {
+                return Err(anyhow::anyhow!("rejected by hook"));
+            }
+            Ok(())
+        }
+
+        fn after(&self, operation: &str, note_id: &str) {
+            self.events.lock().unwrap().push(format!("after {} {}", operation, note_id));
+        }
+    }
+
     #[test]
-    fn test_update() {
+    fn test_create_runs_hooks() {
         let mut mock = MockRepository::new();
-        let note_id = "update-id";
-        let update_note = UpdateNote {
-            title: String::from("Note 1"),
-            content: String::from("This is note #1."),
-        };
+        mock.expect_create()
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let service = NoteServiceImpl::new(mock).with_hook(RecordingHook { events: events.clone(), reject: false });
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content: String::from("This is a new note."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        service.create(&note).now_or_never().unwrap().unwrap();
+        assert_eq!(*events.lock().unwrap(), vec!["before create new-id", "after create new-id"]);
+    }
+
+    #[test]
+    fn test_before_hook_aborts_mutation() {
+        let mut mock = MockRepository::new();
+        mock.expect_delete().times(0);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let service = NoteServiceImpl::new(mock).with_hook(RecordingHook { events: events.clone(), reject: true });
+
+        assert!(service.delete("some-id").now_or_never().unwrap().is_err());
+        assert_eq!(*events.lock().unwrap(), vec!["before delete some-id"]);
+    }
+
+    #[test]
+    fn test_rename_tag_runs_hooks() {
+        let mut mock = MockRepository::new();
+        mock.expect_ids_with_tag_for_update()
+            .withf(|tag| tag == "work")
+            .times(1)
+            .returning(|_| Ok(vec![String::from("1"), String::from("2")]));
+        mock.expect_rename_tag()
+            .times(1)
+            .returning(|_, _| Ok(vec![String::from("1"), String::from("2")]));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let service = NoteServiceImpl::new(mock).with_hook(RecordingHook { events: events.clone(), reject: false });
+
+        service.rename_tag("work", "job").now_or_never().unwrap().unwrap();
+        assert_eq!(*events.lock().unwrap(), vec![
+            "before replace_tags 1", "before replace_tags 2", "after replace_tags 1", "after replace_tags 2",
+        ]);
+    }
+
+    #[test]
+    fn test_before_hook_aborts_rename_tag() {
+        let mut mock = MockRepository::new();
+        mock.expect_ids_with_tag_for_update()
+            .times(1)
+            .returning(|_| Ok(vec![String::from("1")]));
+        mock.expect_rename_tag().times(0);
+        let service = NoteServiceImpl::new(mock).with_hook(RecordingHook { events: Arc::new(Mutex::new(Vec::new())), reject: true });
+
+        assert!(service.rename_tag("work", "job").now_or_never().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_create_with_auto_title() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .with(predicate::function(|note: &NewNote| note.title == "Groceries"))
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::new(),
+            content: String::from("\n# Groceries\nMilk and eggs"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let created = NoteServiceImpl::new(mock).with_auto_title(true).create(&note).now_or_never().unwrap().unwrap();
+        assert_eq!(created.title, "Groceries");
+
+        let rejected = NoteServiceImpl::new(MockRepository::new()).create(&note).now_or_never().unwrap();
+        assert!(rejected.unwrap_err().downcast::<ValidationErrors>().unwrap().field_errors().contains_key("title"));
+    }
+
+    #[test]
+    fn test_create_with_default_content() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .with(predicate::function(|note: &NewNote| note.content == "## Notes\n"))
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("Meeting"),
+            content: String::new(),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let service = NoteServiceImpl::new(mock).with_default_content(Some(String::from("## Notes\n")));
+        let created = service.create(&note).now_or_never().unwrap().unwrap();
+        assert_eq!(created.content, "## Notes\n");
+
+        let rejected = NoteServiceImpl::new(MockRepository::new()).create(&note).now_or_never().unwrap();
+        assert!(rejected.unwrap_err().downcast::<ValidationErrors>().unwrap().field_errors().contains_key("content"));
+    }
+
+    #[test]
+    fn test_create_with_title_case() {
+        for (title_case, expected) in [(TitleCase::Title, "Hello World"), (TitleCase::None, "hello world")] {
+            let mut mock = MockRepository::new();
+            mock.expect_create()
+                .with(predicate::function(move |note: &NewNote| note.title == expected))
+                .times(1)
+                .returning(|note| Ok(Note {
+                    id: note.id.clone(),
+                    title: note.title.clone(),
+                    content: note.content.clone(),
+                    created_at: note.created_at.clone(),
+                    updated_at: note.created_at.clone(),
+                    version: 1,
+                    expires_at: None,
+                    format: String::from("markdown"),
+                }));
+            let note = NewNote {
+                id: String::from("new-id"),
+                title: String::from("hello world"),
+                content: String::from("Content"),
+                created_at: String::from("2021-01-01T00:00:00Z"),
+                expires_at: None,
+                format: String::from("markdown"),
+            };
+
+            let service = NoteServiceImpl::new(mock).with_title_case(title_case);
+            assert_eq!(service.create(&note).now_or_never().unwrap().unwrap().title, expected);
+        }
+    }
+
+    #[test]
+    fn test_update_with_title_case() {
+        let mut mock = MockRepository::new();
+        mock.expect_update()
+            .with(predicate::eq("some-id"), predicate::function(|note: &UpdateNote| note.title == "Hello world"))
+            .times(1)
+            .returning(|id, note| Ok(Note {
+                id: id.to_string(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.updated_at.clone(),
+                updated_at: note.updated_at.clone(),
+                version: 2,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let note = UpdateNote {
+            title: String::from("hello WORLD"),
+            content: String::from("Content"),
+            updated_at: String::from("2021-01-01T00:00:00Z"),
+            version: None,
+        };
+
+        let service = NoteServiceImpl::new(mock).with_title_case(TitleCase::Sentence);
+        assert!(service.update("some-id", &note).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_create_with_duplicate_content() {
+        let mut mock = MockRepository::new();
+        mock.expect_id_with_content()
+            .with(predicate::eq("Buy milk"))
+            .times(1)
+            .returning(|_| Ok(Some(String::from("existing-id"))));
+        mock.expect_create()
+            .times(0);
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("Groceries"),
+            content: String::from("Buy milk"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let service = NoteServiceImpl::new(mock).with_unique_content(true);
+        let error = service.create(&note).now_or_never().unwrap().unwrap_err();
+        assert_eq!(error.downcast::<DuplicateContent>().unwrap().existing_id, "existing-id");
+    }
+
+    #[test]
+    fn test_create_with_unique_content() {
+        let mut mock = MockRepository::new();
+        mock.expect_id_with_content()
+            .times(1)
+            .returning(|_| Ok(None));
+        mock.expect_create()
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("Groceries"),
+            content: String::from("Buy eggs"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let service = NoteServiceImpl::new(mock).with_unique_content(true);
+        assert!(service.create(&note).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_title_from_content_is_truncated() {
+        let title = db::title_from_content(&"word ".repeat(db::TITLE_MAX_LENGTH)).unwrap();
+        assert_eq!(title.chars().count(), db::TITLE_MAX_LENGTH - 1);
+        assert_eq!(db::title_from_content("  \n "), None);
+    }
+
+    #[test]
+    fn test_create_with_min_content_length() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock).with_min_content_length(10);
+        let mut note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content: String::from("  short  "),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+        let err = service.create(&note).now_or_never().unwrap().unwrap_err();
+        let errors = err.downcast::<ValidationErrors>().unwrap();
+        assert!(errors.field_errors().contains_key("content"));
+
+        note.content = String::from("Long enough content.");
+        assert!(service.create(&note).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_create_without_min_content_length() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock);
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content: String::from("x"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+        assert!(service.create(&note).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_reject_binary_content() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .times(1)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock).with_reject_binary_content(true);
+        let note = |content: String| NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content,
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let lossy = String::from_utf8_lossy(b"PNG\x89\xff\xfe header").into_owned();
+        for invalid in [lossy, String::from("a\0b"), String::from("bell\x07")] {
+            let result = service.create(&note(invalid.clone())).now_or_never().unwrap();
+            let errors = result.unwrap_err().downcast::<ValidationErrors>().unwrap();
+            assert!(errors.field_errors().contains_key("content"), "Expected {:?} to be rejected", invalid);
+        }
+        assert!(service.create(&note(String::from("line one\r\n\tline two"))).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_reject_binary_append() {
+        let mut mock = MockRepository::new();
+        mock.expect_append().times(0);
+        let service = NoteServiceImpl::new(mock).with_reject_binary_content(true);
+
+        let result = service.append("some-id", "more\0", "2021-01-02 00:00:00").now_or_never().unwrap();
+        let errors = result.unwrap_err().downcast::<ValidationErrors>().unwrap();
+        assert!(errors.field_errors().contains_key("text"));
+    }
+
+    #[test]
+    fn test_create_retries_taken_generated_id() {
+        let mut mock = MockRepository::new();
+        let mut ids = Vec::new();
+        mock.expect_create()
+            .times(2)
+            .returning(move |note| {
+                ids.push(note.id.clone());
+                if ids.len() == 1 {
+                    return Err(DbError::IdTaken.into());
+                }
+                assert_ne!(ids[0], ids[1]);
+                Ok(Note {
+                    id: note.id.clone(),
+                    title: note.title.clone(),
+                    content: note.content.clone(),
+                    created_at: note.created_at.clone(),
+                    updated_at: note.created_at.clone(),
+                    version: 1,
+                    expires_at: None,
+                    format: String::from("markdown"),
+                })
+            });
+        let service = NoteServiceImpl::new(mock);
+        let note = NewNote {
+            id: String::new(),
+            title: String::from("New note"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let created = service.create(&note).now_or_never().unwrap().unwrap();
+        assert!(!created.id.is_empty());
+    }
+
+    #[test]
+    fn test_create_many_retries_taken_generated_ids() {
+        let mut mock = MockRepository::new();
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        mock.expect_create_many()
+            .times(2)
+            .returning(move |notes| {
+                batches.push(notes.iter().map(|note| note.id.clone()).collect());
+                if batches.len() == 1 {
+                    return Err(DbError::IdTaken.into());
+                }
+                assert_ne!(batches[0][0], batches[1][0]);
+                assert_eq!(batches[1][1], "client-id");
+                Ok(notes.iter().map(|note| Note {
+                    id: note.id.clone(),
+                    title: note.title.clone(),
+                    content: note.content.clone(),
+                    created_at: note.created_at.clone(),
+                    updated_at: note.created_at.clone(),
+                    version: 1,
+                    expires_at: None,
+                    format: String::from("markdown"),
+                }).collect())
+            });
+        let service = NoteServiceImpl::new(mock);
+        let note = |id: &str| NewNote {
+            id: String::from(id),
+            title: String::from("New note"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let created = service.create_many(&[note(""), note("client-id")]).now_or_never().unwrap().unwrap();
+        assert!(!created[0].id.is_empty());
+    }
+
+    #[test]
+    fn test_create_does_not_retry_client_id() {
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .times(1)
+            .returning(|_| Err(DbError::IdTaken.into()));
+        let service = NoteServiceImpl::new(mock);
+        let note = NewNote {
+            id: String::from("taken-id"),
+            title: String::from("New note"),
+            content: String::from("content"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let result = service.create(&note).now_or_never().unwrap();
+        assert!(matches!(result.unwrap_err().downcast_ref::<DbError>(), Some(DbError::IdTaken)));
+    }
+
+    #[test]
+    fn test_create_with_emoji_title() {
+        // A family emoji is one grapheme made of seven chars and 25 bytes
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let mut mock = MockRepository::new();
+        mock.expect_create()
+            .times(2)
+            .returning(|note| Ok(Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock);
+        let emoji_note = |title: String| NewNote {
+            id: String::from("new-id"),
+            title,
+            content: String::from("This is a new note."),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let single = service.create(&emoji_note(family.to_string())).now_or_never().unwrap();
+        assert!(single.is_ok(), "A single emoji satisfies the minimum length");
+
+        let at_max = service.create(&emoji_note(family.repeat(db::TITLE_MAX_LENGTH))).now_or_never().unwrap();
+        assert!(at_max.is_ok(), "Max length is counted in graphemes, not bytes");
+
+        let over_max = service.create(&emoji_note(family.repeat(db::TITLE_MAX_LENGTH + 1))).now_or_never().unwrap();
+        assert!(over_max.is_err(), "Expected an error due to validation");
+    }
+
+    #[test]
+    fn test_update() {
+        let mut mock = MockRepository::new();
+        let note_id = "update-id";
+        let update_note = UpdateNote {
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            updated_at: String::from("2021-01-02T00:00:00Z"),
+            version: Some(1),
+        };
         let update_note_test = update_note.clone();
         mock.expect_update()
             .with(predicate::eq(note_id), predicate::eq(update_note_test.clone()))
@@ -163,25 +1516,82 @@ mod test {
                 title: update_note_test.title.clone(),
                 content: update_note_test.content.clone(),
                 created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from("2021-01-01T00:00:00Z"),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
             }));
         let service = NoteServiceImpl::new(mock);
         let note = service.update(note_id, &update_note).now_or_never().unwrap().unwrap();
         assert_eq!(note.id, note_id);
     }
 
+    #[test]
+    fn test_update_many() {
+        let mut mock = MockRepository::new();
+        let update_note = UpdateNote {
+            title: String::from("Note 1"),
+            content: String::from("This is note #1."),
+            updated_at: String::from("2021-01-02T00:00:00Z"),
+            version: Some(1),
+        };
+        let expected = vec![
+            NoteWrite::Update { id: String::from("a"), note: update_note.clone() },
+            NoteWrite::Update { id: String::from("b"), note: update_note.clone() },
+        ];
+        mock.expect_transaction()
+            .withf(move |writes| writes == expected.as_slice())
+            .times(1)
+            .returning(|_| Ok(vec![]));
+        let service = NoteServiceImpl::new(mock);
+        let updates = vec![(String::from("a"), update_note.clone()), (String::from("b"), update_note)];
+        assert!(service.update_many(&updates).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_update_many_with_invalid_note() {
+        let mut mock = MockRepository::new();
+        mock.expect_transaction().times(0);
+        let service = NoteServiceImpl::new(mock);
+        let invalid_note = UpdateNote {
+            title: String::new(),
+            content: String::new(),
+            updated_at: String::from("2021-01-02T00:00:00Z"),
+            version: None,
+        };
+        let result = service.update_many(&[(String::from("a"), invalid_note)]).now_or_never();
+        assert!(result.unwrap().is_err(), "Expected an error due to validation");
+    }
+
     #[test]
     fn test_update_with_invalid_note() {
         let mock = MockRepository::new();
         let service = NoteServiceImpl::new(mock);
         let invalid_note = UpdateNote {
             title: String::new(),
-            content: String::from("")
+            content: String::from(""),
+            updated_at: String::from("2021-01-02T00:00:00Z"),
+            version: None,
         };
         let result = service.update("id", &invalid_note).now_or_never();
         assert!(result.is_some(), "Expected a synchronous result");
         assert!(result.unwrap().is_err(), "Expected an error due to validation");
     }
 
+    #[test]
+    fn test_update_with_emoji_title() {
+        let mock = MockRepository::new();
+        let service = NoteServiceImpl::new(mock);
+        let invalid_note = UpdateNote {
+            title: "\u{1F600}".repeat(db::TITLE_MAX_LENGTH + 1),
+            content: String::from("This is note #1."),
+            updated_at: String::from("2021-01-02T00:00:00Z"),
+            version: None,
+        };
+        let result = service.update("id", &invalid_note).now_or_never();
+        assert!(result.unwrap().is_err(), "Expected an error due to validation");
+    }
+
     #[test]
     fn test_delete() {
         let mut mock = MockRepository::new();
@@ -194,9 +1604,249 @@ mod test {
                 title: String::from("Note 1"),
                 content: String::from("This is note #1."),
                 created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from("2021-01-01T00:00:00Z"),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
             }));
         let service = NoteServiceImpl::new(mock);
         let note = service.delete(delete_id).now_or_never().unwrap().unwrap();
         assert_eq!(note.id, delete_id);
     }
+
+    #[test]
+    fn test_touch() {
+        let mut mock = MockRepository::new();
+        mock.expect_touch()
+            .with(predicate::eq("touch-id"), predicate::eq("2021-01-02T00:00:00Z"))
+            .times(1)
+            .returning(|id, updated_at| Ok(Note {
+                id: String::from(id),
+                title: String::from("Note 1"),
+                content: String::from("This is note #1."),
+                created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from(updated_at),
+                version: 2,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock);
+        let note = service.touch("touch-id", "2021-01-02T00:00:00Z").now_or_never().unwrap().unwrap();
+        assert_eq!(note.updated_at, "2021-01-02T00:00:00Z");
+        assert_eq!(note.content, "This is note #1.");
+    }
+
+    #[test]
+    fn test_title_prefix() {
+        let mut mock = MockRepository::new();
+        mock.expect_title_prefix()
+            .with(predicate::eq("Sho"), predicate::eq(5))
+            .times(1)
+            .returning(|_, _| Ok(vec![NoteTitle {
+                id: String::from("some-id"),
+                title: String::from("Shopping list"),
+            }]));
+        let service = NoteServiceImpl::new(mock);
+        let titles = service.title_prefix("Sho", 5).now_or_never().unwrap().unwrap();
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].title, "Shopping list");
+    }
+
+    #[test]
+    fn test_search_in_tag() {
+        let mut mock = MockRepository::new();
+        mock.expect_search_by()
+            .withf(|filter, limit, offset| {
+                filter.query == "plan" && filter.tag.as_deref() == Some("work") && filter.sort == SearchSort::Newest && *limit == 10 && *offset == 0
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(vec![]));
+        let service = NoteServiceImpl::new(mock);
+        let notes = service.search_in_tag("work", "plan", 10, 0).now_or_never().unwrap().unwrap();
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn test_replace_tags() {
+        let mut mock = MockRepository::new();
+        mock.expect_replace_tags()
+            .withf(|id, tags| id == "tag-id" && tags == [String::from("x")])
+            .times(1)
+            .returning(|_, tags| Ok(tags.to_vec()));
+        let service = NoteServiceImpl::new(mock);
+        let tags = NoteTags { tags: vec![String::from("x")] };
+        let result = service.replace_tags("tag-id", &tags).now_or_never().unwrap().unwrap();
+        assert_eq!(result, vec!["x"]);
+    }
+
+    #[test]
+    fn test_replace_tags_stores_duplicates_once() {
+        let mut mock = MockRepository::new();
+        mock.expect_replace_tags()
+            .withf(|_, tags| tags == [String::from("a"), String::from("b")])
+            .times(1)
+            .returning(|_, tags| Ok(tags.to_vec()));
+        let service = NoteServiceImpl::new(mock);
+        let tags = NoteTags { tags: vec![String::from("a"), String::from("a"), String::from("b")] };
+        let result = service.replace_tags("tag-id", &tags).now_or_never().unwrap().unwrap();
+        assert_eq!(result, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_lowercase_tags() {
+        let mut mock = MockRepository::new();
+        mock.expect_replace_tags()
+            .withf(|_, tags| tags == [String::from("work"), String::from("home")])
+            .times(1)
+            .returning(|_, tags| Ok(tags.to_vec()));
+        let service = NoteServiceImpl::new(mock).with_lowercase_tags(true);
+        let tags = NoteTags { tags: vec![String::from("Work"), String::from("home"), String::from("WORK")] };
+        assert!(service.replace_tags("tag-id", &tags).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_set_tag_color() {
+        let mut mock = MockRepository::new();
+        mock.expect_set_tag_color()
+            .withf(|tag, color| tag == "work" && color == "#a1b2c3")
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let service = NoteServiceImpl::new(mock).with_lowercase_tags(true);
+        assert!(service.set_tag_color("Work", "#a1b2c3").now_or_never().unwrap().is_ok());
+
+        for invalid in ["red", "#fff", "#12345g", "a1b2c3d"] {
+            let result = service.set_tag_color("work", invalid).now_or_never().unwrap();
+            assert!(result.unwrap_err().downcast_ref::<ValidationErrors>().is_some(), "Expected {:?} to be rejected", invalid);
+        }
+        assert!(service.set_tag_color("a,b", "#a1b2c3").now_or_never().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_rename_and_count_lowercase_tags() {
+        let mut mock = MockRepository::new();
+        mock.expect_rename_tag()
+            .withf(|from, to| from == "work" && to == "job")
+            .times(1)
+            .returning(|_, _| Ok(vec![String::from("1")]));
+        mock.expect_count_by_tag()
+            .withf(|tag| tag == "job")
+            .times(1)
+            .returning(|_| Ok(1));
+        let service = NoteServiceImpl::new(mock).with_lowercase_tags(true);
+
+        assert_eq!(service.rename_tag("Work", "JOB").now_or_never().unwrap().unwrap(), vec!["1"]);
+        assert_eq!(service.count_by_tag("Job").now_or_never().unwrap().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_remove_tag_color() {
+        let mut mock = MockRepository::new();
+        mock.expect_remove_tag_color()
+            .withf(|tag| tag == "work")
+            .times(1)
+            .returning(|_| Ok(true));
+        let service = NoteServiceImpl::new(mock).with_lowercase_tags(true);
+        assert!(service.remove_tag_color("Work").now_or_never().unwrap().unwrap());
+    }
+
+    #[test]
+    fn test_replace_tags_with_invalid_tag() {
+        let mock = MockRepository::new();
+        let service = NoteServiceImpl::new(mock);
+        for invalid in ["", "a,b", " padded", &"x".repeat(db::TAG_MAX_LENGTH + 1)] {
+            let tags = NoteTags { tags: vec![String::from("ok"), invalid.to_string()] };
+            let result = service.replace_tags("tag-id", &tags).now_or_never().unwrap();
+            assert!(result.is_err(), "Expected an error due to validation of {:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_min_tags() {
+        let mut mock = MockRepository::new();
+        mock.expect_create().times(0);
+        mock.expect_create_with_tags()
+            .times(1)
+            .returning(|note, tags| Ok((Note {
+                id: note.id.clone(),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: note.created_at.clone(),
+                updated_at: note.created_at.clone(),
+                version: 1,
+                expires_at: None,
+                format: String::from("markdown"),
+            }, tags.to_vec())));
+        mock.expect_replace_tags().times(0);
+        let service = NoteServiceImpl::new(mock).with_tag_count_limits(1, 0);
+        let note = NewNote {
+            id: String::from("new-id"),
+            title: String::from("New note"),
+            content: String::from("Untagged"),
+            created_at: String::from("2021-01-01T00:00:00Z"),
+            expires_at: None,
+            format: String::from("markdown"),
+        };
+
+        let err = service.create(&note).now_or_never().unwrap().unwrap_err();
+        let errors = err.downcast::<ValidationErrors>().unwrap();
+        assert!(errors.field_errors().contains_key("tags"));
+
+        let err = service.replace_tags("new-id", &NoteTags { tags: Vec::new() }).now_or_never().unwrap().unwrap_err();
+        assert!(err.downcast::<ValidationErrors>().is_ok());
+
+        let tags = NoteTags { tags: vec![String::from("work")] };
+        assert!(service.create_with_tags(&note, &tags).now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_max_tags() {
+        let mock = MockRepository::new();
+        let service = NoteServiceImpl::new(mock).with_tag_count_limits(0, 2);
+        let tags = NoteTags { tags: vec![String::from("a"), String::from("b"), String::from("c")] };
+
+        let err = service.replace_tags("tag-id", &tags).now_or_never().unwrap().unwrap_err();
+        let errors = err.downcast::<ValidationErrors>().unwrap();
+        assert!(errors.field_errors().contains_key("tags"));
+    }
+
+    #[test]
+    fn test_insert_at() {
+        assert_eq!(insert_at("world", 0, "hello ").unwrap(), "hello world");
+        assert_eq!(insert_at("caf\u{e9} au lait", 4, "!").unwrap(), "caf\u{e9}! au lait");
+        assert_eq!(insert_at("abc", 3, "d").unwrap(), "abcd");
+        assert!(insert_at("abc", 4, "d").unwrap_err().field_errors().contains_key("offset"));
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut mock = MockRepository::new();
+        mock.expect_get_for_update()
+            .times(1)
+            .returning(|id| Ok(Note {
+                id: String::from(id),
+                title: String::from("Note 1"),
+                content: String::from("Day one."),
+                created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: String::from("2021-01-01T00:00:00Z"),
+                version: 3,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        mock.expect_update()
+            .withf(|id, note| id == "some-id" && note.content == "Day one, again." && note.version == Some(3))
+            .times(1)
+            .returning(|id, note| Ok(Note {
+                id: String::from(id),
+                title: note.title.clone(),
+                content: note.content.clone(),
+                created_at: String::from("2021-01-01T00:00:00Z"),
+                updated_at: note.updated_at.clone(),
+                version: 4,
+                expires_at: None,
+                format: String::from("markdown"),
+            }));
+        let service = NoteServiceImpl::new(mock);
+        let note = service.insert("some-id", 7, ", again", "2021-01-02T00:00:00Z").now_or_never().unwrap().unwrap();
+        assert_eq!(note.content, "Day one, again.");
+    }
 }
@@ -0,0 +1,78 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// How `NoteServiceImpl` cases note titles on create and update.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TitleCase {
+    /// Titles are stored as given
+    #[default]
+    None,
+    /// `Every Word Capitalized`
+    Title,
+    /// `Only the first word capitalized`
+    Sentence,
+}
+
+impl FromStr for TitleCase {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value {
+            "none" => Ok(TitleCase::None),
+            "title" => Ok(TitleCase::Title),
+            "sentence" => Ok(TitleCase::Sentence),
+            _ => Err(anyhow!("unknown title case: {}", value)),
+        }
+    }
+}
+
+impl TitleCase {
+    /// Uppercases the first letter of each word, or of the first word only, and lowercases the rest.
+    /// Whitespace is kept as is, and acronyms are lowercased like any other word.
+    pub fn apply(self, title: &str) -> String {
+        if self == TitleCase::None {
+            return title.to_string();
+        }
+
+        let mut cased = String::with_capacity(title.len());
+        let mut word_start = true;
+        let mut first_word = true;
+        for c in title.chars() {
+            if c.is_whitespace() {
+                cased.push(c);
+                first_word &= word_start;
+                word_start = true;
+                continue;
+            }
+            if word_start && (self == TitleCase::Title || first_word) {
+                cased.extend(c.to_uppercase());
+            } else {
+                cased.extend(c.to_lowercase());
+            }
+            word_start = false;
+        }
+
+        cased
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(TitleCase::Title.apply("hello world"), "Hello World");
+        assert_eq!(TitleCase::Title.apply("  éCOLE  notes"), "  École  Notes");
+        assert_eq!(TitleCase::Sentence.apply("hello World"), "Hello world");
+        assert_eq!(TitleCase::Sentence.apply("  hello"), "  Hello");
+        assert_eq!(TitleCase::None.apply("hello world"), "hello world");
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("sentence".parse::<TitleCase>().unwrap(), TitleCase::Sentence);
+        assert!("upper".parse::<TitleCase>().is_err());
+    }
+}
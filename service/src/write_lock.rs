@@ -0,0 +1,376 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use db::{ActivityBucket, ActivityCount, NewNote, Note, NoteNeighbors, NoteSize, NoteTag, NoteTags, NoteTitle, RawColumn, RepairReport, SearchFilter, TagColor, TagMatch, TagPair, UpdateNote, ViewedNote};
+use tokio::sync::{OwnedRwLockWriteGuard, RwLock, RwLockReadGuard};
+
+use crate::NoteService;
+
+/// Shared barrier that pauses note writes, for example while a migration runs.
+/// Each write holds a read guard for its whole call, so the lock cannot be taken while a write is in flight.
+#[derive(Clone, Default)]
+pub struct WriteLock(Arc<RwLock<()>>);
+
+impl WriteLock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pauses writes until the returned guard is dropped.
+    /// Returns `None` when writes are already paused or a write is still in flight.
+    pub fn try_lock(&self) -> Option<WriteLockGuard> {
+        let guard = self.0.clone().try_write_owned().ok()?;
+
+        Some(WriteLockGuard { _guard: guard })
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.0.try_read().is_err()
+    }
+}
+
+/// Resumes writes when dropped.
+pub struct WriteLockGuard {
+    _guard: OwnedRwLockWriteGuard<()>,
+}
+
+/// Returned for writes attempted while a `WriteLock` is held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WritesLocked;
+
+impl Display for WritesLocked {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "writes are paused while a migration runs")
+    }
+}
+
+impl Error for WritesLocked {}
+
+/// Decorates a `NoteService` so mutations fail with `WritesLocked` while the lock is held.
+/// Reads always go through.
+pub struct WriteLockedNoteService<S: NoteService> {
+    inner: S,
+    lock: WriteLock,
+}
+
+impl<S: NoteService> WriteLockedNoteService<S> {
+    pub fn new(inner: S, lock: WriteLock) -> Self {
+        WriteLockedNoteService { inner, lock }
+    }
+
+    /// Returns a guard that keeps the lock from being taken until the write finishes.
+    fn check(&self) -> Result<RwLockReadGuard<'_, ()>> {
+        self.lock.0.try_read().map_err(|_| WritesLocked.into())
+    }
+}
+
+#[async_trait]
+impl<S: NoteService> NoteService for WriteLockedNoteService<S> {
+    async fn all(&self) -> Result<Vec<Note>> {
+        self.inner.all().await
+    }
+
+    async fn all_paged(&self, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.all_paged(limit, offset).await
+    }
+
+    async fn count(&self) -> Result<i64> {
+        self.inner.count().await
+    }
+
+    async fn get(&self, id: &str) -> Result<Note> {
+        self.inner.get(id).await
+    }
+
+    async fn create(&self, note: &NewNote) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.create(note).await
+    }
+
+    async fn update(&self, id: &str, note: &UpdateNote) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.update(id, note).await
+    }
+
+    async fn update_many(&self, updates: &[(String, UpdateNote)]) -> Result<Vec<Note>> {
+        let _permit = self.check()?;
+        self.inner.update_many(updates).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.delete(id).await
+    }
+
+    async fn touch(&self, id: &str, updated_at: &str) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.touch(id, updated_at).await
+    }
+
+    async fn set_created_at(&self, id: &str, created_at: &str) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.set_created_at(id, created_at).await
+    }
+
+    async fn touch_many(&self, ids: &[String], updated_at: &str) -> Result<Vec<Note>> {
+        let _permit = self.check()?;
+        self.inner.touch_many(ids, updated_at).await
+    }
+
+    async fn title_prefix(&self, prefix: &str, limit: i64) -> Result<Vec<NoteTitle>> {
+        self.inner.title_prefix(prefix, limit).await
+    }
+
+    async fn search(&self, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search(query, limit, offset).await
+    }
+
+    async fn replace_tags(&self, id: &str, tags: &NoteTags) -> Result<Vec<String>> {
+        let _permit = self.check()?;
+        self.inner.replace_tags(id, tags).await
+    }
+
+    async fn all_tags(&self) -> Result<Vec<NoteTag>> {
+        self.inner.all_tags().await
+    }
+
+    async fn recent(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.recent(limit).await
+    }
+
+    async fn create_with_tags(&self, note: &NewNote, tags: &NoteTags) -> Result<(Note, Vec<String>)> {
+        let _permit = self.check()?;
+        self.inner.create_with_tags(note, tags).await
+    }
+
+    async fn create_many(&self, notes: &[NewNote]) -> Result<Vec<Note>> {
+        let _permit = self.check()?;
+        self.inner.create_many(notes).await
+    }
+
+    async fn activity(&self, from: &str, to: &str, bucket: ActivityBucket) -> Result<Vec<ActivityCount>> {
+        self.inner.activity(from, to, bucket).await
+    }
+
+    async fn count_by_tag(&self, tag: &str) -> Result<i64> {
+        self.inner.count_by_tag(tag).await
+    }
+
+    async fn search_by(&self, filter: &SearchFilter, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search_by(filter, limit, offset).await
+    }
+
+    async fn append(&self, id: &str, text: &str, updated_at: &str) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.append(id, text, updated_at).await
+    }
+
+    async fn insert(&self, id: &str, offset: usize, text: &str, updated_at: &str) -> Result<Note> {
+        let _permit = self.check()?;
+        self.inner.insert(id, offset, text, updated_at).await
+    }
+
+    async fn rename_tag(&self, from: &str, to: &str) -> Result<Vec<String>> {
+        let _permit = self.check()?;
+        self.inner.rename_tag(from, to).await
+    }
+
+    async fn try_get(&self, id: &str) -> Result<Option<Note>> {
+        self.inner.try_get(id).await
+    }
+
+    async fn exists_any(&self) -> Result<bool> {
+        self.inner.exists_any().await
+    }
+
+    async fn delete_expired(&self, now: &str) -> Result<Vec<String>> {
+        let _permit = self.check()?;
+        self.inner.delete_expired(now).await
+    }
+
+    async fn repair_orphans(&self) -> Result<RepairReport> {
+        let _permit = self.check()?;
+        self.inner.repair_orphans().await
+    }
+
+    async fn record_view(&self, id: &str, viewed_at: &str) -> Result<()> {
+        let _permit = self.check()?;
+        self.inner.record_view(id, viewed_at).await
+    }
+
+    async fn recently_viewed(&self, limit: i64) -> Result<Vec<ViewedNote>> {
+        self.inner.recently_viewed(limit).await
+    }
+
+    async fn most_edited(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.most_edited(limit).await
+    }
+
+    async fn created_between(&self, from: &str, to: &str) -> Result<Vec<Note>> {
+        self.inner.created_between(from, to).await
+    }
+
+    async fn count_created_between(&self, from: &str, to: &str) -> Result<i64> {
+        self.inner.count_created_between(from, to).await
+    }
+
+    async fn tags_created_between(&self, from: &str, to: &str) -> Result<Vec<NoteTag>> {
+        self.inner.tags_created_between(from, to).await
+    }
+
+    async fn created_between_paged(&self, from: &str, to: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.created_between_paged(from, to, limit, offset).await
+    }
+
+    async fn storage_size(&self, id: &str) -> Result<NoteSize> {
+        self.inner.storage_size(id).await
+    }
+
+    async fn updated_since_viewed(&self, limit: i64) -> Result<Vec<Note>> {
+        self.inner.updated_since_viewed(limit).await
+    }
+
+    async fn tag_trend(&self, tag: &str) -> Result<Vec<ActivityCount>> {
+        self.inner.tag_trend(tag).await
+    }
+
+    async fn get_with_tags(&self, id: &str) -> Result<(Note, Vec<String>)> {
+        self.inner.get_with_tags(id).await
+    }
+
+    async fn all_by(&self, filter: &SearchFilter) -> Result<Vec<Note>> {
+        self.inner.all_by(filter).await
+    }
+
+    async fn count_by(&self, filter: &SearchFilter) -> Result<i64> {
+        self.inner.count_by(filter).await
+    }
+
+    async fn related(&self, id: &str, limit: i64) -> Result<Vec<Note>> {
+        self.inner.related(id, limit).await
+    }
+
+    async fn by_tags(&self, tags: &[String], matching: TagMatch, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.by_tags(tags, matching, limit, offset).await
+    }
+
+    async fn by_tag_overlap(&self, tags: &[String], limit: i64) -> Result<Vec<(Note, i64)>> {
+        self.inner.by_tag_overlap(tags, limit).await
+    }
+
+    async fn set_tag_color(&self, tag: &str, color: &str) -> Result<()> {
+        let _permit = self.check()?;
+        self.inner.set_tag_color(tag, color).await
+    }
+
+    async fn remove_tag_color(&self, tag: &str) -> Result<bool> {
+        let _permit = self.check()?;
+        self.inner.remove_tag_color(tag).await
+    }
+
+    async fn tag_colors(&self) -> Result<Vec<TagColor>> {
+        self.inner.tag_colors().await
+    }
+
+    async fn tag_co_occurrence(&self, limit: i64) -> Result<Vec<TagPair>> {
+        self.inner.tag_co_occurrence(limit).await
+    }
+
+    async fn count_by_tags(&self, tags: &[String], matching: TagMatch) -> Result<i64> {
+        self.inner.count_by_tags(tags, matching).await
+    }
+
+    async fn oldest(&self) -> Result<Option<Note>> {
+        self.inner.oldest().await
+    }
+
+    async fn newest(&self) -> Result<Option<Note>> {
+        self.inner.newest().await
+    }
+
+    async fn neighbors(&self, id: &str) -> Result<NoteNeighbors> {
+        self.inner.neighbors(id).await
+    }
+
+    async fn activity_heatmap(&self, from: &str, to: &str) -> Result<Vec<(String, i64)>> {
+        self.inner.activity_heatmap(from, to).await
+    }
+
+    async fn search_in_tag(&self, tag: &str, query: &str, limit: i64, offset: i64) -> Result<Vec<Note>> {
+        self.inner.search_in_tag(tag, query, limit, offset).await
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<(Vec<Note>, i64)> {
+        self.inner.page(limit, offset).await
+    }
+
+    async fn ids_after(&self, after: &str, limit: i64) -> Result<Vec<String>> {
+        self.inner.ids_after(after, limit).await
+    }
+
+    async fn creation_minutes(&self) -> Result<Vec<ActivityCount>> {
+        self.inner.creation_minutes().await
+    }
+
+    async fn raw(&self, id: &str) -> Result<Vec<RawColumn>> {
+        self.inner.raw(id).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mocks::MockService;
+    use futures_util::future::FutureExt;
+
+    fn note(id: &str) -> Note {
+        Note {
+            id: String::from(id),
+            title: String::from("Note 1"),
+            content: String::from("Content"),
+            created_at: String::from("2021-01-01 00:00:00"),
+            updated_at: String::from("2021-01-01 00:00:00"),
+            version: 1,
+            expires_at: None,
+            format: String::from("markdown"),
+        }
+    }
+
+    #[test]
+    fn test_writes_fail_and_reads_succeed_while_locked() {
+        let mut inner = MockService::new();
+        inner.expect_get().times(2).returning(|id| Ok(note(id)));
+        inner.expect_delete().times(1).returning(|id| Ok(note(id)));
+        let lock = WriteLock::new();
+        let service = WriteLockedNoteService::new(inner, lock.clone());
+
+        let guard = lock.try_lock().unwrap();
+        assert!(lock.try_lock().is_none());
+        let err = service.delete("1").now_or_never().unwrap().unwrap_err();
+        assert_eq!(err.downcast::<WritesLocked>().unwrap(), WritesLocked);
+        assert_eq!(service.get("1").now_or_never().unwrap().unwrap().id, "1");
+
+        drop(guard);
+        assert!(!lock.is_locked());
+        assert!(service.delete("1").now_or_never().unwrap().is_ok());
+        assert!(service.get("1").now_or_never().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_lock_waits_for_in_flight_write() {
+        let lock = WriteLock::new();
+        let observed_lock = lock.clone();
+        let mut inner = MockService::new();
+        inner.expect_delete().times(1).returning(move |id| {
+            assert!(observed_lock.try_lock().is_none());
+            Ok(note(id))
+        });
+        let service = WriteLockedNoteService::new(inner, lock.clone());
+
+        assert!(service.delete("1").now_or_never().unwrap().is_ok());
+        assert!(lock.try_lock().is_some());
+    }
+}